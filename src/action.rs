@@ -1,8 +1,16 @@
-use chrono::NaiveDate;
+use chrono::{NaiveDate, NaiveTime};
+use chrono_tz::Tz;
+use serde::{Deserialize, Serialize};
 
-use crate::database::{challenge_data::ChallengeData, task_data::TaskData};
+use crate::{
+    database::{
+        challenge_data::ChallengeData, delivery::Delivery, delivery_kind::DeliveryKind,
+        task_data::TaskData, update_schedule::UpdateSchedule,
+    },
+    report_window::ReportWindow,
+};
 
-#[derive(Debug)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub enum Action {
     AddTask(i32, String, TaskData),
     CreateNewChallenge(ChallengeData),
@@ -10,18 +18,165 @@ pub enum Action {
     SendHelp,
     SignupUser(i32, i64, String),
     ErrorMessage(String),
+    /// Replies with a message which is not an error.
+    Reply(String),
     CheckDateMaybeSendPolls,
     CheckDateMaybeSendChallengeUpdates,
+    CheckDateMaybeSendForecastNudges,
     ModifyUserTaskTimestamps(String, Vec<i32>),
+    RecordAmount(i32, f64),
     WritePollInfo(Vec<UserPollDateInfo>),
+    /// Records how sending the polls, updates or nudges went.
+    RecordDeliveries(Vec<DeliveryReport>),
+    ExportUserData(i32),
+    ExportChallengeCsv(i32, i64, String),
+    ListChallenges(i32),
+    ShowChallengeStats(i32, i64, String),
+    ShowStreaks(i32, i64, String),
+    /// Reports on the given challenge, or on all running challenges of the user.
+    ShowReport(i32, i64, ReportWindow, Option<String>),
+    /// Shows what is left to do this period, in the given challenge or all running challenges of the user.
+    ShowForecast(i32, i64, Option<String>),
+    ArchiveChallenge(i32, String),
+    SetTaskWeight(i32, String, String, f64),
+    SetWeightsAllowed(i32, String, bool),
+    RetireTask(i32, String, String),
+    /// Pauses the user from the first date (today if not given) until the second one,
+    /// or until further notice.
+    PauseUser(i32, Option<NaiveDate>, Option<NaiveDate>),
+    ResumeUser(i32),
+    /// Sets the timezone of the user, None goes back to the time of the server.
+    SetTimezone(i32, Option<Tz>),
+    /// Shows the timezone and poll time of the user and the update schedules of their challenges.
+    ShowSettings(i32),
+    /// Sets the local time the user gets their poll at, None goes back to the usual time.
+    SetPollTime(i32, Option<NaiveTime>),
+    SetUpdateSchedule(i32, String, UpdateSchedule),
+    RequestUserDeletion(i32),
+    DeleteUser(i32),
     Nothing,
 }
 
-#[derive(Debug)]
+impl Action {
+    /// The action without anything about the user, their chat or their polls, for when
+    /// the user is deleted. None if the action is only about them.
+    /// Challenges created by the user stay, but lose their owner.
+    pub fn without_user(
+        &self,
+        user_id: i32,
+        chat_id: Option<i64>,
+        poll_ids: &[String],
+    ) -> Option<Action> {
+        let is_user = |id: &i32| *id == user_id;
+        let is_chat = |id: &i64| Some(*id) == chat_id;
+        match self {
+            Action::WritePollInfo(infos) => {
+                let infos: Vec<UserPollDateInfo> = infos
+                    .iter()
+                    .filter(|info| !is_user(&info.user_id))
+                    .cloned()
+                    .collect();
+                Some(infos)
+                    .filter(|infos| !infos.is_empty())
+                    .map(Action::WritePollInfo)
+            }
+            Action::RecordDeliveries(reports) => {
+                let reports: Vec<DeliveryReport> = reports
+                    .iter()
+                    .filter(|report| {
+                        let delivery = &report.delivery;
+                        let is_poll_of_user = delivery.kind == DeliveryKind::Poll
+                            && delivery.subject_id == user_id as i64;
+                        !is_chat(&delivery.chat_id) && !is_poll_of_user
+                    })
+                    .cloned()
+                    .collect();
+                Some(reports)
+                    .filter(|reports| !reports.is_empty())
+                    .map(Action::RecordDeliveries)
+            }
+            Action::CreateNewChallenge(challenge_data)
+                if challenge_data.owner_id == Some(user_id) =>
+            {
+                Some(Action::CreateNewChallenge(ChallengeData {
+                    owner_id: None,
+                    ..challenge_data.clone()
+                }))
+            }
+            Action::ModifyUserTaskTimestamps(poll_id, _) if poll_ids.contains(poll_id) => None,
+            Action::AddTask(id, ..)
+            | Action::SubscribeToChallenge(id, ..)
+            | Action::RecordAmount(id, _)
+            | Action::ExportUserData(id)
+            | Action::ListChallenges(id)
+            | Action::ArchiveChallenge(id, _)
+            | Action::SetTaskWeight(id, ..)
+            | Action::SetWeightsAllowed(id, ..)
+            | Action::RetireTask(id, ..)
+            | Action::PauseUser(id, ..)
+            | Action::ResumeUser(id)
+            | Action::SetTimezone(id, _)
+            | Action::ShowSettings(id)
+            | Action::SetPollTime(id, _)
+            | Action::SetUpdateSchedule(id, ..)
+            | Action::RequestUserDeletion(id)
+            | Action::DeleteUser(id)
+                if is_user(id) =>
+            {
+                None
+            }
+            Action::SignupUser(id, chat, _)
+            | Action::ExportChallengeCsv(id, chat, _)
+            | Action::ShowChallengeStats(id, chat, _)
+            | Action::ShowStreaks(id, chat, _)
+            | Action::ShowReport(id, chat, ..)
+            | Action::ShowForecast(id, chat, _)
+                if is_user(id) || is_chat(chat) =>
+            {
+                None
+            }
+            action => Some(action.clone()),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct UserPollDateInfo {
     pub user_id: i32,
     pub date: NaiveDate,
-    pub task_id: String,
+    pub task_id: i64,
     pub poll_id: String,
     pub task_index: i32,
 }
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct DeliveryReport {
+    pub delivery: Delivery,
+    pub outcome: DeliveryOutcome,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum DeliveryOutcome {
+    Delivered,
+    /// Failed for a reason which might go away, such as a network error.
+    Failed,
+    /// The chat does not accept messages from the bot, e.g. because the user blocked it.
+    Refused,
+    /// Telegram rejected the message itself, so sending it again would not help.
+    Rejected,
+}
+
+/// The telegram user and chat an action originated from.
+/// Actions triggered by the bot itself have neither.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct ActionOrigin {
+    pub user_id: Option<i32>,
+    pub chat_id: Option<i64>,
+}
+
+impl ActionOrigin {
+    /// Whether the action came from the user or from their chat.
+    pub fn is_from(&self, user_id: i32, chat_id: Option<i64>) -> bool {
+        self.user_id == Some(user_id) || (chat_id.is_some() && self.chat_id == chat_id)
+    }
+}
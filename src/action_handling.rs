@@ -1,89 +1,605 @@
-use anyhow::Result;
-
-use std::path::Path;
+use anyhow::{anyhow, Result};
+use chrono::{NaiveDate, NaiveTime};
+use chrono_tz::Tz;
 
 use crate::{
-    action::{Action, UserPollDateInfo},
-    config,
-    database::{challenge_data::ChallengeData, task_data::TaskData, Database},
-    response::Response,
+    action::{Action, ActionOrigin, DeliveryReport, UserPollDateInfo},
+    csv_export::get_challenge_csv,
+    database::{
+        action_log_entry::ActionLogEntry, challenge::Challenge, challenge_data::ChallengeData,
+        challenge_state::ChallengeState, constraint_violation::ConstraintViolation,
+        storage::Storage, task::Task, task_data::TaskData, update_schedule::UpdateSchedule,
+    },
+    report_window::ReportWindow,
+    response::{AmountPromptData, ChallengeUpdateData, DocumentData, ReportData, Response},
 };
 
-pub fn perform_action(action: &Action) -> Response {
-    let database = Database::new(&Path::new(config::DEFAULT_DB_PATH));
-    let mb_response = match action {
+pub fn perform_action(database: &dyn Storage, origin: &ActionOrigin, action: &Action) -> Response {
+    let mb_response = try_perform_action(database, origin, action);
+    mb_response.unwrap_or_else(|err| Response::Reply(format!("Error: {}", err.to_string())))
+}
+
+/// Performs the action and writes it to the action log.
+/// Actions which do nothing are not logged, neither are the regular checks
+/// for whether polls and updates are due unless they actually are.
+/// Deleting a user is not logged either, since everything else about them is gone.
+pub fn try_perform_action(
+    database: &dyn Storage,
+    origin: &ActionOrigin,
+    action: &Action,
+) -> Result<Response> {
+    let result = handle_action(database, action);
+    let is_idle_check = matches!(
+        action,
+        Action::CheckDateMaybeSendPolls
+            | Action::CheckDateMaybeSendChallengeUpdates
+            | Action::CheckDateMaybeSendForecastNudges
+    ) && matches!(&result, Ok(response) if response.is_empty());
+    if !matches!(action, Action::Nothing | Action::DeleteUser(_)) && !is_idle_check {
+        let entry = ActionLogEntry {
+            timestamp: database.clock().now(),
+            utc_offset: database.clock().utc_offset(),
+            origin: origin.clone(),
+            action: action.clone(),
+            response_kind: match &result {
+                Ok(response) => response.kind().to_owned(),
+                Err(_) => "Error".to_owned(),
+            },
+        };
+        if let Err(err) = database.write_action_log_entry(&entry) {
+            log::error!("Failed to write {:?} to the action log: {:?}", entry, err);
+        }
+    }
+    result
+}
+
+/// Performs the action without writing it to the action log.
+pub fn handle_action(database: &dyn Storage, action: &Action) -> Result<Response> {
+    match action {
         Action::CreateNewChallenge(challenge_data) => {
-            create_new_challenge(&database, challenge_data)
+            create_new_challenge(database, challenge_data)
         }
         Action::SubscribeToChallenge(user_id, challenge_id, user_name) => {
-            subscribe_to_challenge(&database, user_id, challenge_id, user_name)
+            subscribe_to_challenge(database, user_id, challenge_id, user_name)
         }
         Action::AddTask(user_id, challenge_name, task_data) => {
-            add_task(&database, user_id, challenge_name, task_data)
+            add_task(database, user_id, challenge_name, task_data)
         }
         Action::SignupUser(user_id, chat_id, user_name) => {
-            signup_user(&database, user_id, chat_id, user_name)
+            signup_user(database, user_id, chat_id, user_name)
         }
-        Action::ErrorMessage(message_text) => reply(&message_text),
-        Action::CheckDateMaybeSendPolls => send_task_polls(&database),
-        Action::CheckDateMaybeSendChallengeUpdates => send_challenge_updates(&database),
+        Action::ErrorMessage(message_text) | Action::Reply(message_text) => reply(&message_text),
+        Action::CheckDateMaybeSendPolls => send_task_polls(database),
+        Action::CheckDateMaybeSendChallengeUpdates => send_challenge_updates(database),
+        Action::CheckDateMaybeSendForecastNudges => send_forecast_nudges(database),
         Action::SendHelp => Ok(Response::SendHelp),
         Action::ModifyUserTaskTimestamps(poll_id, option_ids) => {
-            modify_user_task_timestamps(&database, poll_id, option_ids)
+            modify_user_task_timestamps(database, poll_id, option_ids)
+        }
+        Action::RecordAmount(user_id, amount) => record_amount(database, user_id, *amount),
+        Action::WritePollInfo(info) => write_poll_info(database, info),
+        Action::RecordDeliveries(reports) => record_deliveries(database, reports),
+        Action::ExportUserData(user_id) => export_user_data(database, user_id),
+        Action::ExportChallengeCsv(user_id, chat_id, challenge_name) => {
+            export_challenge_csv(database, user_id, chat_id, challenge_name)
+        }
+        Action::ListChallenges(user_id) => list_challenges(database, user_id),
+        Action::ShowChallengeStats(user_id, chat_id, challenge_name) => {
+            show_challenge_stats(database, user_id, chat_id, challenge_name)
+        }
+        Action::ShowStreaks(user_id, chat_id, challenge_name) => {
+            show_streaks(database, user_id, chat_id, challenge_name)
         }
-        Action::WritePollInfo(info) => write_poll_info(&database, info),
+        Action::ShowReport(user_id, chat_id, window, challenge_name) => {
+            show_report(database, user_id, chat_id, window, challenge_name)
+        }
+        Action::ShowForecast(user_id, chat_id, challenge_name) => {
+            show_forecast(database, user_id, chat_id, challenge_name)
+        }
+        Action::ArchiveChallenge(user_id, challenge_name) => {
+            archive_challenge(database, user_id, challenge_name)
+        }
+        Action::SetTaskWeight(user_id, challenge_name, task_name, weight) => {
+            set_task_weight(database, user_id, challenge_name, task_name, *weight)
+        }
+        Action::SetWeightsAllowed(user_id, challenge_name, allowed) => {
+            set_weights_allowed(database, user_id, challenge_name, *allowed)
+        }
+        Action::RetireTask(user_id, challenge_name, task_name) => {
+            retire_task(database, user_id, challenge_name, task_name)
+        }
+        Action::PauseUser(user_id, start, end) => pause_user(database, user_id, *start, *end),
+        Action::ResumeUser(user_id) => resume_user(database, user_id),
+        Action::SetTimezone(user_id, timezone) => set_timezone(database, user_id, *timezone),
+        Action::ShowSettings(user_id) => show_settings(database, user_id),
+        Action::SetPollTime(user_id, poll_time) => set_poll_time(database, user_id, *poll_time),
+        Action::SetUpdateSchedule(user_id, challenge_name, schedule) => {
+            set_update_schedule(database, user_id, challenge_name, *schedule)
+        }
+        Action::RequestUserDeletion(user_id) => Ok(Response::UserDeletionPrompt(*user_id)),
+        Action::DeleteUser(user_id) => delete_user(database, user_id),
         Action::Nothing => Ok(Response::Nothing),
+    }
+}
+
+fn export_user_data(database: &dyn Storage, user_id: &i32) -> Result<Response> {
+    let export = database.get_user_export(user_id)?;
+    Ok(Response::Document(DocumentData {
+        chat_id: export.user.chat_id,
+        file_name: "deshittify_data.json".to_owned(),
+        content: serde_json::to_vec_pretty(&export)?,
+    }))
+}
+
+fn export_challenge_csv(
+    database: &dyn Storage,
+    user_id: &i32,
+    chat_id: &i64,
+    challenge_name: &str,
+) -> Result<Response> {
+    let challenge = database.get_challenge_for_user_by_name(user_id, challenge_name)?;
+    Ok(Response::Document(DocumentData {
+        chat_id: *chat_id,
+        file_name: format!("{}.csv", challenge.data.name),
+        content: get_challenge_csv(database, &challenge)?,
+    }))
+}
+
+fn list_challenges(database: &dyn Storage, user_id: &i32) -> Result<Response> {
+    let today = database.get_today_for_user(user_id)?;
+    let challenges = database.get_challenges_for_user(user_id)?;
+    if challenges.is_empty() {
+        return Ok(Response::Reply(
+            "You are not part of any challenge.".to_owned(),
+        ));
+    }
+    let lines: Vec<String> = challenges
+        .iter()
+        .map(|challenge| {
+            format!(
+                "{} ({} to {}): {}",
+                challenge.data.name,
+                challenge.data.time_frame.start,
+                challenge.data.time_frame.end,
+                challenge.state(today)
+            )
+        })
+        .collect();
+    Ok(Response::Reply(lines.join("\n")))
+}
+
+fn show_challenge_stats(
+    database: &dyn Storage,
+    user_id: &i32,
+    chat_id: &i64,
+    challenge_name: &str,
+) -> Result<Response> {
+    let challenge = database.get_challenge_for_user_by_name(user_id, challenge_name)?;
+    let today = database.get_today_for_user(user_id)?;
+    Ok(Response::ChallengeUpdates(ChallengeUpdateData(
+        vec![database.get_challenge_performance(&challenge, *chat_id, today)?],
+        vec![],
+    )))
+}
+
+fn show_streaks(
+    database: &dyn Storage,
+    user_id: &i32,
+    chat_id: &i64,
+    challenge_name: &str,
+) -> Result<Response> {
+    let challenge = database.get_challenge_for_user_by_name(user_id, challenge_name)?;
+    let today = database.get_today_for_user(user_id)?;
+    Ok(Response::Streaks(
+        database.get_challenge_performance(&challenge, *chat_id, today)?,
+    ))
+}
+
+fn show_report(
+    database: &dyn Storage,
+    user_id: &i32,
+    chat_id: &i64,
+    window: &ReportWindow,
+    challenge_name: &Option<String>,
+) -> Result<Response> {
+    let today = database.get_today_for_user(user_id)?;
+    let challenges = get_named_or_running_challenges(database, user_id, challenge_name, today)?;
+    Ok(Response::Report(ReportData {
+        window: window.clone(),
+        time_frame: window.get_time_frame(today),
+        previous_time_frame: window.get_previous_time_frame(today),
+        challenges: challenges
+            .iter()
+            .map(|challenge| database.get_challenge_performance(challenge, *chat_id, today))
+            .collect::<Result<_>>()?,
+    }))
+}
+
+/// Only shows the tasks of the user who asked.
+fn show_forecast(
+    database: &dyn Storage,
+    user_id: &i32,
+    chat_id: &i64,
+    challenge_name: &Option<String>,
+) -> Result<Response> {
+    let today = database.get_today_for_user(user_id)?;
+    let challenges = get_named_or_running_challenges(database, user_id, challenge_name, today)?;
+    challenges
+        .iter()
+        .map(|challenge| {
+            let mut challenge_performance =
+                database.get_challenge_performance(challenge, *chat_id, today)?;
+            challenge_performance
+                .user_performance
+                .retain(|user_performance| user_performance.user.user_id == *user_id as i64);
+            Ok(challenge_performance)
+        })
+        .collect::<Result<_>>()
+        .map(Response::Forecast)
+}
+
+/// The challenge with the given name, or all running challenges of the user if there is none.
+fn get_named_or_running_challenges(
+    database: &dyn Storage,
+    user_id: &i32,
+    challenge_name: &Option<String>,
+    today: NaiveDate,
+) -> Result<Vec<Challenge>> {
+    let challenges: Vec<Challenge> = match challenge_name {
+        Some(challenge_name) => {
+            vec![database.get_challenge_for_user_by_name(user_id, challenge_name)?]
+        }
+        None => database
+            .get_challenges_for_user(user_id)?
+            .into_iter()
+            .filter(|challenge| challenge.state(today) == ChallengeState::Active)
+            .collect(),
     };
-    mb_response.unwrap_or_else(|err| Response::Reply(format!("Error: {}", err.to_string())))
+    if challenges.is_empty() {
+        return Err(anyhow!("You are not part of any running challenge."));
+    }
+    Ok(challenges)
+}
+
+fn archive_challenge(
+    database: &dyn Storage,
+    user_id: &i32,
+    challenge_name: &str,
+) -> Result<Response> {
+    let today = database.get_today_for_user(user_id)?;
+    let challenge = database.get_challenge_for_user_by_name(user_id, challenge_name)?;
+    match challenge.state(today) {
+        ChallengeState::Finished => {}
+        ChallengeState::Archived => return Err(anyhow!("This challenge is already archived.")),
+        _ => return Err(anyhow!("Only finished challenges can be archived.")),
+    }
+    database.archive_challenge(challenge.id)?;
+    Ok(Response::Reply(format!(
+        "Challenge {} archived.",
+        challenge.data.name
+    )))
+}
+
+fn check_weight(weight: f64) -> Result<()> {
+    if !weight.is_finite() || weight <= 0.0 {
+        return Err(anyhow!("The weight has to be a positive number."));
+    }
+    Ok(())
+}
+
+fn set_task_weight(
+    database: &dyn Storage,
+    user_id: &i32,
+    challenge_name: &str,
+    task_name: &str,
+    weight: f64,
+) -> Result<Response> {
+    check_weight(weight)?;
+    let challenge = database.get_challenge_for_user_by_name(user_id, challenge_name)?;
+    if !challenge.weights_allowed {
+        return Err(anyhow!("Weights are not allowed in {}.", challenge_name));
+    }
+    let task = find_task(database, user_id, &challenge, task_name)?;
+    database.set_task_weight(task.id, weight)?;
+    Ok(Response::Reply(format!(
+        "Task {} now has a weight of {}.",
+        task_name, weight
+    )))
 }
 
-fn write_poll_info(database: &Database, info: &Vec<UserPollDateInfo>) -> Result<Response> {
+/// Stops asking about the task after today. The days it was active still count.
+fn retire_task(
+    database: &dyn Storage,
+    user_id: &i32,
+    challenge_name: &str,
+    task_name: &str,
+) -> Result<Response> {
+    let today = database.get_today_for_user(user_id)?;
+    let challenge = database.get_challenge_for_user_by_name(user_id, challenge_name)?;
+    let task = find_task(database, user_id, &challenge, task_name)?;
+    if task.active_until.is_some() {
+        return Err(anyhow!("Task {} is already retired.", task_name));
+    }
+    database.set_task_active_until(task.id, today)?;
+    Ok(Response::Reply(format!(
+        "Task {} is retired, today is its last day.",
+        task_name
+    )))
+}
+
+fn find_task(
+    database: &dyn Storage,
+    user_id: &i32,
+    challenge: &Challenge,
+    task_name: &str,
+) -> Result<Task> {
+    database
+        .get_tasks_for_challenge_and_user(challenge.id, *user_id as i64)?
+        .into_iter()
+        .find(|task| task.data.name == task_name)
+        .ok_or_else(|| {
+            anyhow!(
+                "You have no task called {} in {}.",
+                task_name,
+                challenge.data.name
+            )
+        })
+}
+
+fn set_weights_allowed(
+    database: &dyn Storage,
+    user_id: &i32,
+    challenge_name: &str,
+    allowed: bool,
+) -> Result<Response> {
+    let challenge = database.get_challenge_for_user_by_name(user_id, challenge_name)?;
+    if !challenge.can_be_managed_by(*user_id) {
+        return Err(anyhow!(
+            "Only the creator of the challenge can decide about weights."
+        ));
+    }
+    database.set_weights_allowed(challenge.id, allowed)?;
+    Ok(Response::Reply(if allowed {
+        format!("Tasks in {} can have weights now.", challenge_name)
+    } else {
+        format!(
+            "All tasks in {} count the same from now on.",
+            challenge_name
+        )
+    }))
+}
+
+fn pause_user(
+    database: &dyn Storage,
+    user_id: &i32,
+    start: Option<NaiveDate>,
+    end: Option<NaiveDate>,
+) -> Result<Response> {
+    let start = match start {
+        Some(start) => start,
+        None => database.get_today_for_user(user_id)?,
+    };
+    database.pause_user(user_id, start, end)?;
+    Ok(Response::Reply(match end {
+        Some(end) => format!("You are paused from {} until {}.", start, end),
+        None => format!(
+            "You are paused from {} until further notice. Send /resume when you are back.",
+            start
+        ),
+    }))
+}
+
+/// Also brings back users who were marked inactive because their chat refused messages.
+fn resume_user(database: &dyn Storage, user_id: &i32) -> Result<Response> {
+    let today = database.get_today_for_user(user_id)?;
+    let inactive_user = database.get_user(user_id)?.filter(|user| user.inactive);
+    if let Some(user) = &inactive_user {
+        database.set_chat_inactive(user.chat_id, false)?;
+    }
+    if inactive_user.is_none() || database.is_paused(user_id, today)? {
+        database.resume_user(user_id, today)?;
+    }
+    Ok(Response::Reply("Welcome back!".to_owned()))
+}
+
+fn set_timezone(database: &dyn Storage, user_id: &i32, timezone: Option<Tz>) -> Result<Response> {
+    if !database.check_user_signed_up(user_id)? {
+        return Err(anyhow!(
+            "You have not signed up yet. Send a /signup to @deshittify_bot privately"
+        ));
+    }
+    database.set_user_timezone(user_id, timezone)?;
+    Ok(Response::Reply(match timezone {
+        Some(timezone) => format!(
+            "Your timezone is now {}. It is {} there.",
+            timezone,
+            database
+                .clock()
+                .now_in(Some(timezone))
+                .format("%Y-%m-%d %H:%M")
+        ),
+        None => "You are on the time of the server again.".to_owned(),
+    }))
+}
+
+fn show_settings(database: &dyn Storage, user_id: &i32) -> Result<Response> {
+    let user = database.get_user(user_id)?.ok_or_else(|| {
+        anyhow!("You have not signed up yet. Send a /signup to @deshittify_bot privately")
+    })?;
+    let mut lines = vec![
+        match user.timezone {
+            Some(timezone) => format!("Timezone: {}", timezone),
+            None => "Timezone: the time of the server".to_owned(),
+        },
+        format!("Poll: {}", database.get_poll_time(user_id)?.format("%H:%M")),
+    ];
+    for challenge in database.get_challenges_for_user(user_id)? {
+        if !challenge.archived {
+            lines.push(format!(
+                "Updates of {}: {}",
+                challenge.data.name, challenge.update_schedule
+            ));
+        }
+    }
+    Ok(Response::Reply(lines.join("\n")))
+}
+
+fn set_poll_time(
+    database: &dyn Storage,
+    user_id: &i32,
+    poll_time: Option<NaiveTime>,
+) -> Result<Response> {
+    if !database.check_user_signed_up(user_id)? {
+        return Err(anyhow!(
+            "You have not signed up yet. Send a /signup to @deshittify_bot privately"
+        ));
+    }
+    database.set_poll_time(user_id, poll_time)?;
+    Ok(Response::Reply(format!(
+        "You will get your poll at {} from now on.",
+        database.get_poll_time(user_id)?.format("%H:%M")
+    )))
+}
+
+fn set_update_schedule(
+    database: &dyn Storage,
+    user_id: &i32,
+    challenge_name: &str,
+    schedule: UpdateSchedule,
+) -> Result<Response> {
+    let challenge = database.get_challenge_for_user_by_name(user_id, challenge_name)?;
+    if !challenge.can_be_managed_by(*user_id) {
+        return Err(anyhow!(
+            "Only the creator of the challenge can change its updates."
+        ));
+    }
+    database.set_update_schedule(challenge.id, schedule)?;
+    Ok(Response::Reply(match schedule {
+        UpdateSchedule::Off => format!("{} gets no more updates.", challenge_name),
+        _ => format!("Updates of {} are sent {} now.", challenge_name, schedule),
+    }))
+}
+
+fn delete_user(database: &dyn Storage, user_id: &i32) -> Result<Response> {
+    if !database.check_user_signed_up(user_id)? {
+        return Err(anyhow!(
+            "You are not signed up, so nothing is stored about you."
+        ));
+    }
+    database.delete_user(user_id)?;
+    Ok(Response::Reply(
+        "All your data has been deleted. Bye!".to_owned(),
+    ))
+}
+
+fn write_poll_info(database: &dyn Storage, info: &[UserPollDateInfo]) -> Result<Response> {
     database.write_poll_info(info)?;
     Ok(Response::Nothing)
 }
 
+fn record_deliveries(database: &dyn Storage, reports: &[DeliveryReport]) -> Result<Response> {
+    for report in reports.iter() {
+        database.record_delivery(report)?;
+    }
+    Ok(Response::Nothing)
+}
+
 fn modify_user_task_timestamps(
-    database: &Database,
+    database: &dyn Storage,
     poll_id: &str,
-    poll_option_ids: &Vec<i32>,
+    poll_option_ids: &[i32],
 ) -> Result<Response> {
     database.modify_user_task_entries(poll_id, poll_option_ids)?;
-    Ok(Response::Nothing)
+    let user_id = database.get_user_id_from_poll_id(poll_id)?;
+    get_next_amount_prompt(database, &user_id)
+}
+
+/// Asks for the amount of the oldest check-in of a quantitative task which does not have one yet.
+fn get_next_amount_prompt(database: &dyn Storage, user_id: &i32) -> Result<Response> {
+    let pending = match database.get_pending_amounts(user_id)?.into_iter().next() {
+        Some(pending) => pending,
+        None => return Ok(Response::Nothing),
+    };
+    let user = database
+        .get_user(user_id)?
+        .ok_or_else(|| anyhow!("You are not signed up."))?;
+    Ok(Response::AmountPrompt(AmountPromptData {
+        chat_id: user.chat_id,
+        task_name: pending.task.name,
+        unit: pending.task.unit.unwrap_or_default(),
+        date: pending.date,
+    }))
 }
 
-fn send_task_polls(database: &Database) -> Result<Response> {
+fn record_amount(database: &dyn Storage, user_id: &i32, amount: f64) -> Result<Response> {
+    if !amount.is_finite() || amount <= 0.0 {
+        return Err(anyhow!("The amount has to be a positive number."));
+    }
+    let pending = database
+        .get_pending_amounts(user_id)?
+        .into_iter()
+        .next()
+        .ok_or_else(|| anyhow!("There is no check-in waiting for an amount."))?;
+    database.set_check_in_amount(pending.check_in_id, amount)?;
+    match get_next_amount_prompt(database, user_id)? {
+        Response::Nothing => Ok(Response::Reply("Thanks, got it.".to_owned())),
+        prompt => Ok(prompt),
+    }
+}
+
+fn send_task_polls(database: &dyn Storage) -> Result<Response> {
     Ok(Response::TaskPolls(
         database.check_date_and_get_all_user_tasks()?,
     ))
 }
 
-fn send_challenge_updates(database: &Database) -> Result<Response> {
+fn send_challenge_updates(database: &dyn Storage) -> Result<Response> {
     Ok(Response::ChallengeUpdates(
         database.check_date_and_get_challenge_update_data()?,
     ))
 }
 
+fn send_forecast_nudges(database: &dyn Storage) -> Result<Response> {
+    Ok(Response::ForecastNudges(
+        database.check_date_and_get_forecast_nudge_data()?,
+    ))
+}
+
 fn reply(message_text: &str) -> Result<Response> {
     Ok(Response::Reply(message_text.to_string()))
 }
 
 fn signup_user(
-    database: &Database,
+    database: &dyn Storage,
     user_id: &i32,
     chat_id: &i64,
     user_name: &str,
 ) -> Result<Response> {
-    database.signup_user(user_id, chat_id, user_name)?;
+    explain_constraint_violation(database.signup_user(user_id, chat_id, user_name), |_| {
+        "You are already signed up.".to_owned()
+    })?;
     Ok(Response::Reply("Thanks. You signed up.".to_owned()))
 }
 
 fn add_task(
-    database: &Database,
+    database: &dyn Storage,
     user_id: &i32,
     challenge_name: &str,
     task_data: &TaskData,
 ) -> Result<Response> {
-    database.add_task(user_id, challenge_name, task_data)?;
+    check_weight(task_data.weight)?;
+    explain_constraint_violation(
+        database.add_task(user_id, challenge_name, task_data),
+        |violation| match violation {
+            ConstraintViolation::Unique => format!(
+                "You already have a task called {} in {}.",
+                task_data.name, challenge_name
+            ),
+            ConstraintViolation::ForeignKey => {
+                "You have not signed up yet. Send a /signup to @deshittify_bot privately".to_owned()
+            }
+        },
+    )?;
     Ok(Response::Reply(format!(
         "Task {} added. Kaclxokca!",
         task_data.name
@@ -91,12 +607,18 @@ fn add_task(
 }
 
 fn subscribe_to_challenge(
-    database: &Database,
+    database: &dyn Storage,
     user_id: &i32,
     challenge_id: &i32,
     user_name: &str,
 ) -> Result<Response> {
-    let already_subscribed = database.subscribe_to_challenge(user_id, challenge_id)?;
+    let already_subscribed = explain_constraint_violation(
+        database.subscribe_to_challenge(user_id, challenge_id),
+        |violation| match violation {
+            ConstraintViolation::Unique => "You already accepted this challenge.".to_owned(),
+            ConstraintViolation::ForeignKey => "This challenge does not exist anymore.".to_owned(),
+        },
+    )?;
     if !already_subscribed {
         Ok(Response::Reply(format!(
             "{} accepted the challenge! Kaclxokca!",
@@ -107,7 +629,373 @@ fn subscribe_to_challenge(
     }
 }
 
-fn create_new_challenge(database: &Database, challenge_data: &ChallengeData) -> Result<Response> {
+fn create_new_challenge(
+    database: &dyn Storage,
+    challenge_data: &ChallengeData,
+) -> Result<Response> {
     let challenge = database.add_challenge(challenge_data)?;
     Ok(Response::SubscriptionPrompt(challenge))
 }
+
+/// Replaces constraint violations reported by the database with a message the user can understand.
+fn explain_constraint_violation<T>(
+    result: Result<T>,
+    explain: impl FnOnce(&ConstraintViolation) -> String,
+) -> Result<T> {
+    result.map_err(|err| match err.downcast_ref::<ConstraintViolation>() {
+        Some(violation) => anyhow!(explain(violation)),
+        None => err,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{Datelike, Duration, NaiveDate};
+
+    use crate::{
+        action::{Action, DeliveryOutcome, DeliveryReport, UserPollDateInfo},
+        clock::{Clock, FakeClock},
+        database::{
+            challenge_data::ChallengeData, delivery::Delivery, period::Period, scoring::Scoring,
+            storage::Storage, task_data::TaskData,
+        },
+        response::Response,
+        test_utils::{create_challenge, for_each_backend, gym, perform, reply_text, signup, task},
+        time_frame::TimeFrame,
+    };
+
+    fn add_task_for(user_id: i32) -> Action {
+        Action::AddTask(user_id, "c".into(), gym())
+    }
+
+    fn run_signup_subscribe_and_check_in(database: &dyn Storage, clock: &FakeClock) {
+        let today = clock.today();
+        let signup = Action::SignupUser(1, 10, "a".into());
+        reply_text(perform(database, &signup));
+        assert_eq!(
+            reply_text(perform(database, &signup)),
+            "Error: You are already signed up."
+        );
+        let challenge = match perform(
+            database,
+            &Action::CreateNewChallenge(ChallengeData {
+                name: "c".into(),
+                time_frame: TimeFrame::new(today - Duration::days(1), today + Duration::days(30)),
+                owner_id: Some(1),
+                scoring: Scoring::Default,
+            }),
+        ) {
+            Response::SubscriptionPrompt(challenge) => challenge,
+            response => panic!("Expected a subscription prompt, got {:?}", response),
+        };
+        let subscribe = Action::SubscribeToChallenge(1, challenge.id as i32, "a".into());
+        reply_text(perform(database, &subscribe));
+        assert!(matches!(perform(database, &subscribe), Response::Nothing));
+        let add_task = add_task_for(1);
+        reply_text(perform(database, &add_task));
+        assert_eq!(
+            reply_text(perform(database, &add_task)),
+            "Error: You already have a task called gym in c."
+        );
+        // A second user with a task of the same name
+        perform(database, &Action::SignupUser(2, 20, "b".into()));
+        let subscribe = Action::SubscribeToChallenge(2, challenge.id as i32, "b".into());
+        reply_text(perform(database, &subscribe));
+        reply_text(perform(database, &add_task_for(2)));
+        let user_tasks = database.get_user_tasks().unwrap();
+        assert_eq!(user_tasks.data.len(), 2);
+        assert_eq!(user_tasks.data[0].chat_id, 10);
+        assert_eq!(user_tasks.data[0].options.len(), 1);
+        assert_eq!(user_tasks.data[0].options[0].text, "gym");
+        let task_id = user_tasks.data[0].options[0].task_id;
+        let other_task_id = user_tasks.data[1].options[0].task_id;
+        assert_ne!(task_id, other_task_id);
+        perform(
+            database,
+            &Action::WritePollInfo(vec![UserPollDateInfo {
+                user_id: 1,
+                date: today,
+                task_id,
+                poll_id: "poll".into(),
+                task_index: 0,
+            }]),
+        );
+        perform(
+            database,
+            &Action::ModifyUserTaskTimestamps("poll".into(), vec![0]),
+        );
+        assert_eq!(
+            database.get_timestamps_for_task(task_id, 1).unwrap(),
+            vec![today]
+        );
+        assert!(database
+            .get_timestamps_for_task(other_task_id, 2)
+            .unwrap()
+            .is_empty());
+        let update_data = database.get_challenge_update_data().unwrap();
+        assert_eq!(update_data.0.len(), 2);
+        assert_eq!(
+            update_data.0[0].user_performance[0].task_performance.len(),
+            1
+        );
+        check_challenge_csv(database);
+        check_export_and_deletion(database, task_id, other_task_id);
+    }
+
+    #[test]
+    fn signup_subscribe_and_check_in() {
+        for_each_backend(run_signup_subscribe_and_check_in);
+    }
+
+    fn check_challenge_csv(database: &dyn Storage) {
+        let document = match perform(database, &Action::ExportChallengeCsv(1, 30, "c".into())) {
+            Response::Document(document) => document,
+            response => panic!("Expected a document, got {:?}", response),
+        };
+        assert_eq!(document.chat_id, 30);
+        assert_eq!(document.file_name, "c.csv");
+        assert_eq!(
+            reply_text(perform(
+                database,
+                &Action::ExportChallengeCsv(1, 30, "d".into())
+            )),
+            "Error: No challenge with this name found for this user"
+        );
+    }
+
+    fn check_export_and_deletion(database: &dyn Storage, task_id: i64, other_task_id: i64) {
+        let document = match perform(database, &Action::ExportUserData(1)) {
+            Response::Document(document) => document,
+            response => panic!("Expected a document, got {:?}", response),
+        };
+        assert_eq!(document.chat_id, 10);
+        let export: serde_json::Value = serde_json::from_slice(&document.content).unwrap();
+        assert_eq!(export["user"]["data"]["name"], "a");
+        let task = &export["challenges"][0]["tasks"][0];
+        assert_eq!(task["task"]["id"], task_id);
+        assert_eq!(task["check_ins"][0]["done"], true);
+        let today = database.clock().today();
+        perform(
+            database,
+            &Action::RecordDeliveries(
+                [(1, 10), (2, 20)]
+                    .iter()
+                    .map(|(user_id, chat_id)| DeliveryReport {
+                        delivery: Delivery::poll(*user_id, *chat_id, today),
+                        outcome: DeliveryOutcome::Delivered,
+                    })
+                    .collect(),
+            ),
+        );
+        assert_eq!(
+            reply_text(perform(database, &Action::DeleteUser(1))),
+            "All your data has been deleted. Bye!"
+        );
+        // Only what is about the other user is left in the action log
+        let log = database.get_action_log().unwrap();
+        assert_eq!(log.len(), 5);
+        for entry in log {
+            let is_about_other_user = match &entry.action {
+                Action::SignupUser(user_id, ..)
+                | Action::SubscribeToChallenge(user_id, ..)
+                | Action::AddTask(user_id, ..) => *user_id == 2,
+                Action::CreateNewChallenge(challenge_data) => challenge_data.owner_id.is_none(),
+                Action::RecordDeliveries(reports) => {
+                    reports.iter().all(|report| report.delivery.chat_id == 20)
+                }
+                _ => false,
+            };
+            assert!(is_about_other_user, "{:?}", entry);
+        }
+        assert_eq!(
+            database
+                .get_challenge_for_user_by_name(&2, "c")
+                .unwrap()
+                .data
+                .owner_id,
+            None
+        );
+        assert!(database.get_user(&1).unwrap().is_none());
+        assert!(database
+            .get_timestamps_for_task(task_id, 1)
+            .unwrap()
+            .is_empty());
+        assert_eq!(database.get_user_tasks().unwrap().data.len(), 1);
+        assert_eq!(database.get_challenge_users(1).unwrap().len(), 1);
+        assert_eq!(
+            reply_text(perform(database, &Action::ExportUserData(1))),
+            "Error: You are not signed up, so nothing is stored about you."
+        );
+        // The other user is untouched
+        assert!(database.get_user(&2).unwrap().is_some());
+        assert_eq!(
+            database
+                .get_tasks_for_challenge_and_user(1, 2)
+                .unwrap()
+                .first()
+                .map(|task| task.id),
+            Some(other_task_id)
+        );
+    }
+
+    fn run_task_amounts(database: &dyn Storage, clock: &FakeClock) {
+        let today = clock.today();
+        signup(database, &[1]);
+        create_challenge(database, "c", -1, 30);
+        let run = TaskData {
+            unit: Some("km".into()),
+            ..task("run", 20, Period::Week)
+        };
+        reply_text(perform(database, &Action::AddTask(1, "c".into(), run)));
+        let user_tasks = database.get_user_tasks().unwrap();
+        let texts: Vec<&str> = user_tasks.data[0]
+            .options
+            .iter()
+            .map(|option| option.text.as_str())
+            .collect();
+        assert_eq!(texts, vec!["gym", "run (km)"]);
+        let run_id = user_tasks.data[0].options[1].task_id;
+        let info = user_tasks.data[0]
+            .options
+            .iter()
+            .enumerate()
+            .map(|(index, option)| UserPollDateInfo {
+                user_id: 1,
+                date: today,
+                task_id: option.task_id,
+                poll_id: "poll".into(),
+                task_index: index as i32,
+            })
+            .collect();
+        perform(database, &Action::WritePollInfo(info));
+        assert_eq!(
+            reply_text(perform(database, &Action::RecordAmount(1, 5.0))),
+            "Error: There is no check-in waiting for an amount."
+        );
+        match perform(
+            database,
+            &Action::ModifyUserTaskTimestamps("poll".into(), vec![0, 1]),
+        ) {
+            Response::AmountPrompt(prompt) => {
+                assert_eq!(prompt.chat_id, 10);
+                assert_eq!(prompt.task_name, "run");
+                assert_eq!(prompt.unit, "km");
+                assert_eq!(prompt.date, today);
+            }
+            response => panic!("Expected an amount prompt, got {:?}", response),
+        }
+        for amount in [-1.0, 0.0].iter() {
+            assert_eq!(
+                reply_text(perform(database, &Action::RecordAmount(1, *amount))),
+                "Error: The amount has to be a positive number."
+            );
+        }
+        assert_eq!(
+            reply_text(perform(database, &Action::RecordAmount(1, 7.5))),
+            "Thanks, got it."
+        );
+        assert_eq!(
+            database.get_amounts_for_task(run_id, 1).unwrap(),
+            vec![(today, 7.5)]
+        );
+        assert_eq!(
+            database.get_check_ins_for_task(run_id, 1).unwrap()[0].amount,
+            Some(7.5)
+        );
+        let update_data = database.get_challenge_update_data().unwrap();
+        let task_performance = &update_data.0[0].user_performance[0].task_performance;
+        assert_eq!(task_performance[1].amounts, vec![(today, 7.5)]);
+    }
+
+    #[test]
+    fn task_amounts() {
+        for_each_backend(run_task_amounts);
+    }
+
+    /// Goes through a month of polls, checking in on every other day.
+    fn run_simulated_month(database: &dyn Storage, clock: &FakeClock) {
+        let first = NaiveDate::from_ymd(2021, 2, 1);
+        let last = NaiveDate::from_ymd(2021, 2, 28);
+        clock.set(first.and_hms(12, 0, 0));
+        perform(database, &Action::SignupUser(1, 10, "a".into()));
+        let challenge_id = match perform(
+            database,
+            &Action::CreateNewChallenge(ChallengeData {
+                name: "feb".into(),
+                time_frame: TimeFrame::new(first, last),
+                owner_id: Some(1),
+                scoring: Scoring::Default,
+            }),
+        ) {
+            Response::SubscriptionPrompt(challenge) => challenge.id as i32,
+            response => panic!("Expected a subscription prompt, got {:?}", response),
+        };
+        perform(
+            database,
+            &Action::SubscribeToChallenge(1, challenge_id, "a".into()),
+        );
+        let floss = task("floss", 1, Period::Day);
+        reply_text(perform(database, &Action::AddTask(1, "feb".into(), floss)));
+        for day in first.iter_days().take_while(|day| *day <= last) {
+            clock.set(day.and_hms(21, 59, 0));
+            assert!(perform(database, &Action::CheckDateMaybeSendPolls).is_empty());
+            clock.set(day.and_hms(22, 0, 0));
+            let polls = match perform(database, &Action::CheckDateMaybeSendPolls) {
+                Response::TaskPolls(polls) => polls,
+                response => panic!("Expected polls, got {:?}", response),
+            };
+            assert_eq!(polls.data.len(), 1);
+            let poll_id = day.to_string();
+            perform(
+                database,
+                &Action::WritePollInfo(vec![UserPollDateInfo {
+                    user_id: 1,
+                    date: day,
+                    task_id: polls.data[0].options[0].task_id,
+                    poll_id: poll_id.clone(),
+                    task_index: 0,
+                }]),
+            );
+            if day.day() % 2 == 0 {
+                perform(
+                    database,
+                    &Action::ModifyUserTaskTimestamps(poll_id, vec![0]),
+                );
+            }
+            // Only one poll per day
+            assert!(perform(database, &Action::CheckDateMaybeSendPolls).is_empty());
+        }
+        clock.set(NaiveDate::from_ymd(2021, 3, 1).and_hms(12, 0, 0));
+        let update_data = match perform(database, &Action::ShowChallengeStats(1, 10, "feb".into()))
+        {
+            Response::ChallengeUpdates(update_data) => update_data,
+            response => panic!("Expected challenge updates, got {:?}", response),
+        };
+        let user_performance = &update_data.0[0].user_performance[0];
+        assert_eq!(
+            user_performance.get_all_time_average(&update_data.0[0].challenge.data),
+            Some(0.5)
+        );
+        // 2021-02-22 to 2021-02-28, of which the 22nd, 24th, 26th and 28th were done
+        assert_eq!(
+            user_performance.get_weekly_average(clock.today()),
+            Some(4.0 / 7.0)
+        );
+    }
+
+    #[test]
+    fn simulated_month() {
+        for_each_backend(run_simulated_month);
+    }
+
+    #[test]
+    fn add_task_requires_subscription() {
+        for_each_backend(|database, _| {
+            perform(database, &Action::SignupUser(1, 10, "a".into()));
+            assert_eq!(
+                reply_text(perform(database, &Action::AddTask(1, "c".into(), gym()))),
+                "Error: No (active) challenge with this name found for this user"
+            );
+        });
+    }
+}
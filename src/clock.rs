@@ -0,0 +1,152 @@
+use chrono::{
+    DateTime, Duration, FixedOffset, Local, NaiveDate, NaiveDateTime, NaiveTime, Offset, Utc,
+};
+use chrono_tz::Tz;
+use std::sync::Mutex;
+
+/// The source of the current date and time for everything which depends on them.
+pub trait Clock: Send + Sync {
+    fn now_utc(&self) -> DateTime<Utc>;
+
+    /// The wall clock time of the server.
+    fn now(&self) -> NaiveDateTime;
+
+    /// How far the wall clock of the server is ahead of UTC.
+    fn utc_offset(&self) -> FixedOffset;
+
+    fn today(&self) -> NaiveDate {
+        self.now().date()
+    }
+
+    /// Whether the given hour of today has not been reached yet on the server.
+    fn is_before_hour(&self, hour: u32) -> bool {
+        self.is_before_time_in(None, NaiveTime::from_hms(hour, 0, 0))
+    }
+
+    /// The wall clock time in the given timezone, or on the server without one.
+    fn now_in(&self, timezone: Option<Tz>) -> NaiveDateTime {
+        match timezone {
+            Some(timezone) => self.now_utc().with_timezone(&timezone).naive_local(),
+            None => self.now(),
+        }
+    }
+
+    fn today_in(&self, timezone: Option<Tz>) -> NaiveDate {
+        self.now_in(timezone).date()
+    }
+
+    /// Whether the given time of today has not been reached yet in the timezone.
+    /// Compares wall clock times, so that the time stays the same when the
+    /// clocks are changed for daylight saving time.
+    fn is_before_time_in(&self, timezone: Option<Tz>, time: NaiveTime) -> bool {
+        self.now_in(timezone).time() < time
+    }
+}
+
+/// The local time of the machine the bot runs on.
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now_utc(&self) -> DateTime<Utc> {
+        Utc::now()
+    }
+
+    fn now(&self) -> NaiveDateTime {
+        Local::now().naive_local()
+    }
+
+    fn utc_offset(&self) -> FixedOffset {
+        Local::now().offset().fix()
+    }
+}
+
+/// A clock which only moves when told to, for tests and for replaying the action log.
+/// Its server runs on UTC unless told otherwise.
+pub struct FakeClock {
+    now: Mutex<NaiveDateTime>,
+    utc_offset: Mutex<FixedOffset>,
+}
+
+impl FakeClock {
+    pub fn new(now: NaiveDateTime) -> FakeClock {
+        FakeClock {
+            now: Mutex::new(now),
+            utc_offset: Mutex::new(FixedOffset::east(0)),
+        }
+    }
+
+    /// A clock at noon of the given day.
+    pub fn at_date(date: NaiveDate) -> FakeClock {
+        FakeClock::new(date.and_hms(12, 0, 0))
+    }
+
+    pub fn set(&self, now: NaiveDateTime) {
+        *self.now.lock().unwrap() = now;
+    }
+
+    /// Sets the wall clock time of a server which is the given offset ahead of UTC.
+    pub fn set_with_offset(&self, now: NaiveDateTime, utc_offset: FixedOffset) {
+        *self.now.lock().unwrap() = now;
+        *self.utc_offset.lock().unwrap() = utc_offset;
+    }
+
+    pub fn advance(&self, duration: Duration) {
+        *self.now.lock().unwrap() += duration;
+    }
+}
+
+impl Clock for FakeClock {
+    fn now_utc(&self) -> DateTime<Utc> {
+        DateTime::from_utc(self.now() - self.utc_offset(), Utc)
+    }
+
+    fn now(&self) -> NaiveDateTime {
+        *self.now.lock().unwrap()
+    }
+
+    fn utc_offset(&self) -> FixedOffset {
+        *self.utc_offset.lock().unwrap()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{Duration, NaiveDate, NaiveTime};
+    use chrono_tz::{America::Los_Angeles, Europe::Berlin, Pacific::Auckland};
+
+    use super::{Clock, FakeClock};
+
+    #[test]
+    fn fake_clock() {
+        let clock = FakeClock::at_date(NaiveDate::from_ymd(2021, 1, 31));
+        assert!(!clock.is_before_hour(12));
+        assert!(clock.is_before_hour(13));
+        clock.advance(Duration::hours(12));
+        assert_eq!(clock.today(), NaiveDate::from_ymd(2021, 2, 1));
+        assert!(clock.is_before_hour(1));
+    }
+
+    #[test]
+    fn timezones() {
+        let clock = FakeClock::new(NaiveDate::from_ymd(2021, 3, 28).and_hms(10, 30, 0));
+        assert_eq!(
+            clock.today_in(Some(Auckland)),
+            NaiveDate::from_ymd(2021, 3, 28)
+        );
+        assert_eq!(
+            clock.today_in(Some(Los_Angeles)),
+            NaiveDate::from_ymd(2021, 3, 28)
+        );
+        clock.set(NaiveDate::from_ymd(2021, 3, 28).and_hms(11, 30, 0));
+        assert_eq!(
+            clock.today_in(Some(Auckland)),
+            NaiveDate::from_ymd(2021, 3, 29)
+        );
+        // Berlin switched to summer time at 01:00 UTC that day
+        clock.set(NaiveDate::from_ymd(2021, 3, 28).and_hms(0, 30, 0));
+        assert!(clock.is_before_time_in(Some(Berlin), NaiveTime::from_hms(2, 0, 0)));
+        clock.advance(Duration::hours(1));
+        assert!(!clock.is_before_time_in(Some(Berlin), NaiveTime::from_hms(3, 0, 0)));
+        assert!(clock.is_before_time_in(Some(Berlin), NaiveTime::from_hms(3, 30, 1)));
+    }
+}
@@ -1,6 +1,26 @@
+use chrono::Weekday;
+
 pub static API_TOKEN_PATH: &str = "apiToken";
 pub static DEFAULT_DB_PATH: &str = "main.db";
 pub static DATE_CHECK_TIMEOUT_SECS: u64 = 60;
 pub static HOUR_TO_SEND_UPDATE_AT: u32 = 17;
 pub static HOUR_TO_SEND_POLL_AT: u32 = 22;
+/// On this day, everyone gets a forecast of what is left to do this week, at their local time.
+/// None to turn the nudge off.
+pub static FORECAST_NUDGE_WEEKDAY: Option<Weekday> = Some(Weekday::Wed);
+pub static HOUR_TO_SEND_FORECAST_AT: u32 = 17;
 pub static MINUTE_TO_SEND_AT: u32 = 0;
+/// The most options Telegram allows in a single poll.
+pub static MAX_POLL_OPTIONS: usize = 10;
+/// How often a poll or update is tried before it is given up.
+pub static DELIVERY_ATTEMPTS: u32 = 5;
+/// The wait before the first retry of a failed poll or update, doubled for every further one.
+pub static DELIVERY_RETRY_DELAY_SECS: i64 = 60;
+/// How many days back polls which were not sent are caught up on.
+pub static CATCH_UP_DAYS: i64 = 3;
+pub static DEFAULT_BACKUP_DIR: &str = "backups";
+pub static BACKUP_DIR_ENV_VAR: &str = "DESHITTIFY_BACKUP_DIR";
+pub static HOUR_TO_BACKUP_AT: u32 = 4;
+pub static KEEP_DAILY_BACKUPS: usize = 7;
+pub static KEEP_WEEKLY_BACKUPS: usize = 4;
+pub static ADMINS_ENV_VAR: &str = "DESHITTIFY_ADMINS";
@@ -0,0 +1,168 @@
+use std::io::Write;
+
+use anyhow::Result;
+use chrono::NaiveDate;
+use serde::Serialize;
+
+use crate::{
+    database::{challenge::Challenge, storage::Storage},
+    task_handling::{get_done_fraction_with_pauses, get_periods},
+    time_frame::TimeFrame,
+};
+
+/// One row of the check-in matrix of a challenge.
+#[derive(Debug, Serialize)]
+struct CsvRow<'a> {
+    user: &'a str,
+    task: &'a str,
+    date: NaiveDate,
+    due: bool,
+    done: usize,
+    period_start: Option<NaiveDate>,
+    period_end: Option<NaiveDate>,
+    period_fraction: Option<f64>,
+    amount: Option<f64>,
+    paused: bool,
+}
+
+/// Writes the check-in matrix of the challenge as CSV with one row per user, task
+/// and day of the challenge. The done column contains the number of check-ins on that day.
+/// Each row also contains the period of the task the day belongs to and the fraction
+/// of that period which has been done, which is empty if the task was not active or the
+/// user paused during the whole period. On days on which the task is not due, the period
+/// columns are empty. For quantitative tasks, the amount column contains the summed amount of that day.
+pub fn write_challenge_csv(
+    database: &dyn Storage,
+    challenge: &Challenge,
+    writer: impl Write,
+) -> Result<()> {
+    let mut writer = csv::Writer::from_writer(writer);
+    for user in database.get_challenge_users(challenge.id)? {
+        let pauses = database.get_pauses(user.user_id)?;
+        for task in database.get_tasks_for_challenge_and_user(challenge.id, user.user_id)? {
+            let timestamps = database.get_timestamps_for_task(task.id, user.user_id)?;
+            let amounts = database.get_amounts_for_task(task.id, user.user_id)?;
+            let done_amounts = match task.data.unit {
+                Some(_) => amounts.clone(),
+                None => timestamps.iter().map(|date| (*date, 1.0)).collect(),
+            };
+            let active_time_frame = task.get_active_time_frame(&challenge.data.time_frame);
+            let first_day = task.get_first_day(&challenge.data.time_frame);
+            let periods: Vec<(TimeFrame, Option<f64>)> =
+                get_periods(&task.data.period, &challenge.data.time_frame, first_day)
+                    .into_iter()
+                    .map(|period| {
+                        let period_fraction = active_time_frame
+                            .as_ref()
+                            .and_then(|active| period.intersect(active))
+                            .and_then(|active_period| {
+                                get_done_fraction_with_pauses(
+                                    &task.data,
+                                    &done_amounts,
+                                    &active_period,
+                                    first_day,
+                                    &pauses,
+                                    challenge.data.scoring.strategy(),
+                                )
+                            });
+                        (period, period_fraction)
+                    })
+                    .collect();
+            let time_frame = &challenge.data.time_frame;
+            for date in time_frame
+                .start
+                .iter_days()
+                .take_while(|date| *date <= time_frame.end)
+            {
+                let period = periods
+                    .iter()
+                    .find(|(period, _)| period.start <= date && date <= period.end);
+                writer.serialize(CsvRow {
+                    user: &user.name,
+                    task: &task.data.name,
+                    date,
+                    due: period.is_some(),
+                    done: timestamps
+                        .iter()
+                        .filter(|timestamp| **timestamp == date)
+                        .count(),
+                    period_start: period.map(|(period, _)| period.start),
+                    period_end: period.map(|(period, _)| period.end),
+                    period_fraction: period.and_then(|(_, fraction)| *fraction),
+                    amount: task.data.unit.as_ref().map(|_| {
+                        amounts
+                            .iter()
+                            .filter(|(day, _)| *day == date)
+                            .map(|(_, amount)| amount)
+                            .sum()
+                    }),
+                    paused: pauses.iter().any(|pause| pause.contains(date)),
+                })?;
+            }
+        }
+    }
+    writer.flush()?;
+    Ok(())
+}
+
+pub fn get_challenge_csv(database: &dyn Storage, challenge: &Challenge) -> Result<Vec<u8>> {
+    let mut content = vec![];
+    write_challenge_csv(database, challenge, &mut content)?;
+    Ok(content)
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{Datelike, Duration};
+
+    use crate::{
+        clock::Clock,
+        database::period::Period,
+        test_utils::{check_in, create_challenge, for_each_backend, join_challenge, signup, task},
+    };
+
+    use super::get_challenge_csv;
+
+    #[test]
+    fn challenge_csv() {
+        for_each_backend(|database, clock| {
+            let today = clock.today();
+            signup(database, &[1, 2]);
+            let challenge_id = create_challenge(database, "c", -1, 30);
+            join_challenge(database, 2, challenge_id);
+            let gym_id = database
+                .get_tasks_for_challenge_and_user(challenge_id as i64, 1)
+                .unwrap()[0]
+                .id;
+            check_in(database, 1, gym_id, today);
+            let yoga = task("yoga", 1, Period::Weekdays(vec![today.weekday()]));
+            database
+                .insert_task(&1, &challenge_id, &yoga, today - Duration::days(1))
+                .unwrap();
+            let challenge = database.get_challenges_for_user(&1).unwrap().remove(0);
+            let content =
+                String::from_utf8(get_challenge_csv(database, &challenge).unwrap()).unwrap();
+            let mut lines = content.lines();
+            assert_eq!(
+                lines.next().unwrap(),
+                "user,task,date,due,done,period_start,period_end,period_fraction,amount,paused"
+            );
+            // Three tasks for each of the 32 days of the challenge, even on days they are not due
+            let rows: Vec<Vec<&str>> = lines.map(|line| line.split(',').collect()).collect();
+            assert_eq!(rows.len(), 3 * 32);
+            let yoga_rows: Vec<&Vec<&str>> = rows.iter().filter(|row| row[1] == "yoga").collect();
+            assert_eq!(yoga_rows.len(), 32);
+            let due: Vec<&&Vec<&str>> = yoga_rows.iter().filter(|row| row[3] == "true").collect();
+            assert_eq!(due.len(), 5);
+            assert_eq!(due[0][2], today.to_string());
+            assert_eq!(due[0][5], today.to_string());
+            let not_due = yoga_rows.iter().find(|row| row[3] == "false").unwrap();
+            assert_eq!(not_due[5], "");
+            assert_eq!(not_due[7], "");
+            let done: Vec<&Vec<&str>> = rows.iter().filter(|row| row[4] == "1").collect();
+            assert_eq!(done.len(), 1);
+            assert_eq!(done[0][0], "user1");
+            assert_eq!(done[0][2], today.to_string());
+        });
+    }
+}
@@ -0,0 +1,16 @@
+use chrono::{FixedOffset, NaiveDateTime};
+
+use crate::action::{Action, ActionOrigin};
+
+/// An action that went through
+/// [perform_action](crate::action_handling::perform_action), as stored in the action log.
+#[derive(Clone, Debug)]
+pub struct ActionLogEntry {
+    /// The wall clock time of the server.
+    pub timestamp: NaiveDateTime,
+    /// How far the server was ahead of UTC at the time.
+    pub utc_offset: FixedOffset,
+    pub origin: ActionOrigin,
+    pub action: Action,
+    pub response_kind: String,
+}
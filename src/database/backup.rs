@@ -0,0 +1,308 @@
+use anyhow::{anyhow, Context, Result};
+use chrono::{Datelike, NaiveDate, NaiveDateTime};
+use itertools::Itertools;
+use rusqlite::{params, Connection, DatabaseName, OpenFlags};
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use crate::config;
+
+use super::{migrations, storage::Storage, Database};
+
+static BACKUP_PREFIX: &str = "deshittify-";
+static SAFETY_COPY_PREFIX: &str = "pre-restore-";
+static BACKUP_EXTENSION: &str = ".db";
+
+/// Where backups are stored and how many of them are kept.
+#[derive(Clone, Debug)]
+pub struct BackupConfig {
+    pub directory: PathBuf,
+    pub keep_daily: usize,
+    pub keep_weekly: usize,
+}
+
+impl BackupConfig {
+    /// Reads the backup directory from the environment, falling back to the default.
+    pub fn from_env() -> BackupConfig {
+        let directory = std::env::var(config::BACKUP_DIR_ENV_VAR)
+            .unwrap_or_else(|_| config::DEFAULT_BACKUP_DIR.to_owned());
+        BackupConfig {
+            directory: PathBuf::from(directory),
+            keep_daily: config::KEEP_DAILY_BACKUPS,
+            keep_weekly: config::KEEP_WEEKLY_BACKUPS,
+        }
+    }
+
+    fn path_for_date(&self, date: NaiveDate) -> PathBuf {
+        self.directory.join(format!(
+            "{}{}{}",
+            BACKUP_PREFIX,
+            date.format("%Y-%m-%d"),
+            BACKUP_EXTENSION
+        ))
+    }
+
+    fn safety_copy_path(&self, now: NaiveDateTime) -> PathBuf {
+        self.directory.join(format!(
+            "{}{}{}",
+            SAFETY_COPY_PREFIX,
+            now.format("%Y-%m-%d-%H%M%S"),
+            BACKUP_EXTENSION
+        ))
+    }
+
+    /// Returns the copies of the database taken before restoring a backup, newest first.
+    fn list_safety_copies(&self) -> Result<Vec<PathBuf>> {
+        if !self.directory.exists() {
+            return Ok(vec![]);
+        }
+        let mut paths = vec![];
+        for entry in fs::read_dir(&self.directory)? {
+            let entry = entry?;
+            let file_name = entry.file_name();
+            let is_safety_copy = matches!(file_name.to_str(), Some(name)
+                if name.starts_with(SAFETY_COPY_PREFIX) && name.ends_with(BACKUP_EXTENSION));
+            if is_safety_copy {
+                paths.push(entry.path());
+            }
+        }
+        // The timestamps in the names sort like the times themselves
+        paths.sort_by(|path1, path2| path2.cmp(path1));
+        Ok(paths)
+    }
+
+    /// Returns the dates of all backups in the backup directory, newest first.
+    pub fn list_backups(&self) -> Result<Vec<NaiveDate>> {
+        if !self.directory.exists() {
+            return Ok(vec![]);
+        }
+        let mut dates = vec![];
+        for entry in fs::read_dir(&self.directory)? {
+            let file_name = entry?.file_name();
+            if let Some(date) = file_name.to_str().and_then(parse_backup_file_name) {
+                dates.push(date);
+            }
+        }
+        dates.sort_by(|date1, date2| date2.cmp(date1));
+        Ok(dates)
+    }
+}
+
+fn parse_backup_file_name(file_name: &str) -> Option<NaiveDate> {
+    let date = file_name
+        .strip_prefix(BACKUP_PREFIX)?
+        .strip_suffix(BACKUP_EXTENSION)?;
+    NaiveDate::parse_from_str(date, "%Y-%m-%d").ok()
+}
+
+/// Decides which backups survive a rotation: the newest `keep_daily` ones and
+/// the newest backup of each of the last `keep_weekly` weeks.
+/// The dates have to be sorted newest first.
+fn backups_to_keep(dates: &[NaiveDate], keep_daily: usize, keep_weekly: usize) -> Vec<NaiveDate> {
+    let daily = dates.iter().take(keep_daily);
+    let weekly = dates
+        .iter()
+        .group_by(|date| (date.iso_week().year(), date.iso_week().week()))
+        .into_iter()
+        .map(|(_, mut dates_in_week)| dates_in_week.next().unwrap())
+        .take(keep_weekly)
+        .collect::<Vec<_>>();
+    daily.chain(weekly).unique().cloned().collect()
+}
+
+impl Database {
+    /// Writes a consistent copy of the database to the given path while the bot keeps running.
+    pub fn backup_to(&self, path: &Path) -> Result<()> {
+        self.connection
+            .backup(DatabaseName::Main, path, None)
+            .with_context(|| format!("While writing backup to {}", path.display()))
+    }
+
+    /// Takes today's backup unless it already exists and deletes the backups
+    /// which are no longer needed. Returns the path of the new backup, if one was taken.
+    pub fn backup_and_rotate(&self, backup_config: &BackupConfig) -> Result<Option<PathBuf>> {
+        let today = self.clock().today();
+        let path = backup_config.path_for_date(today);
+        if path.exists() {
+            return Ok(None);
+        }
+        fs::create_dir_all(&backup_config.directory).with_context(|| {
+            format!(
+                "While creating backup directory {}",
+                backup_config.directory.display()
+            )
+        })?;
+        self.backup_to(&path)?;
+        let dates = backup_config.list_backups()?;
+        let keep = backups_to_keep(&dates, backup_config.keep_daily, backup_config.keep_weekly);
+        for date in dates.iter().filter(|date| !keep.contains(date)) {
+            let old_path = backup_config.path_for_date(*date);
+            fs::remove_file(&old_path)
+                .with_context(|| format!("While removing old backup {}", old_path.display()))?;
+            log::info!("Removed old backup {}", old_path.display());
+        }
+        Ok(Some(path))
+    }
+
+    /// Replaces the contents of the database with the backup from the given date.
+    /// The backup is checked for integrity and a compatible schema version first
+    /// and the current state is saved next to the other backups before it is overwritten.
+    /// Only the newest `keep_daily` of these safety copies are kept.
+    pub fn restore_backup(&mut self, backup_config: &BackupConfig, date: NaiveDate) -> Result<()> {
+        let path = backup_config.path_for_date(date);
+        if !path.exists() {
+            return Err(anyhow!("There is no backup from {}", date));
+        }
+        validate_backup(&path)
+            .with_context(|| format!("While validating backup {}", path.display()))?;
+        let safety_copy = backup_config.safety_copy_path(self.clock().now());
+        self.backup_to(&safety_copy)?;
+        log::info!("Saved current database to {}", safety_copy.display());
+        for old_path in backup_config
+            .list_safety_copies()?
+            .iter()
+            .skip(backup_config.keep_daily)
+        {
+            fs::remove_file(old_path).with_context(|| {
+                format!("While removing old safety copy {}", old_path.display())
+            })?;
+            log::info!("Removed old safety copy {}", old_path.display());
+        }
+        self.connection
+            .restore(
+                DatabaseName::Main,
+                &path,
+                None::<fn(rusqlite::backup::Progress)>,
+            )
+            .with_context(|| format!("While restoring backup {}", path.display()))?;
+        migrations::migrate(&mut self.connection)?;
+        log::info!("Restored database from {}", path.display());
+        Ok(())
+    }
+}
+
+fn validate_backup(path: &Path) -> Result<()> {
+    let connection = Connection::open_with_flags(path, OpenFlags::SQLITE_OPEN_READ_ONLY)?;
+    let integrity: String =
+        connection.query_row("PRAGMA integrity_check", params![], |row| row.get(0))?;
+    if integrity != "ok" {
+        return Err(anyhow!("Integrity check failed: {}", integrity));
+    }
+    let version = migrations::get_version(&connection)
+        .context("The backup does not contain a schema version")?;
+    if version < 1 || version > migrations::latest_version() {
+        return Err(anyhow!(
+            "Unsupported schema version {} (expected 1 to {})",
+            version,
+            migrations::latest_version()
+        ));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{Duration, NaiveDate};
+    use rusqlite::params;
+    use std::{fs, path::Path, sync::Arc};
+
+    use crate::{
+        clock::FakeClock,
+        database::{storage::Storage, Database},
+    };
+
+    use super::{backups_to_keep, BackupConfig};
+
+    #[test]
+    fn rotation() {
+        // 1970-01-05 is a Monday
+        let dates: Vec<NaiveDate> = (0..30)
+            .rev()
+            .map(|day| NaiveDate::from_ymd(1970, 1, 5) + Duration::days(day))
+            .collect();
+        let keep = backups_to_keep(&dates, 7, 4);
+        let mut expected: Vec<NaiveDate> = dates[..7].to_vec();
+        // The newest backups of the two most recent weeks are already among the daily ones
+        expected.push(NaiveDate::from_ymd(1970, 1, 25));
+        expected.push(NaiveDate::from_ymd(1970, 1, 18));
+        assert_eq!(keep, expected);
+    }
+
+    #[test]
+    fn backup_and_restore() {
+        let directory =
+            std::env::temp_dir().join(format!("deshittify-backup-test-{}", std::process::id()));
+        let backup_config = BackupConfig {
+            directory: directory.clone(),
+            keep_daily: 7,
+            keep_weekly: 4,
+        };
+        let today = NaiveDate::from_ymd(2021, 2, 1);
+        let mut database =
+            Database::with_clock(Path::new(":memory:"), Arc::new(FakeClock::at_date(today)))
+                .unwrap();
+        database.signup_user(&1, &10, "a").unwrap();
+        assert!(database
+            .backup_and_rotate(&backup_config)
+            .unwrap()
+            .is_some());
+        // Only one backup per day
+        assert!(database
+            .backup_and_rotate(&backup_config)
+            .unwrap()
+            .is_none());
+        assert_eq!(backup_config.list_backups().unwrap(), vec![today]);
+        database.signup_user(&2, &20, "b").unwrap();
+        database.restore_backup(&backup_config, today).unwrap();
+        assert!(database.get_user(&1).unwrap().is_some());
+        assert!(database.get_user(&2).unwrap().is_none());
+        assert!(database
+            .restore_backup(&backup_config, today - Duration::days(1))
+            .is_err());
+        // A backup from a newer version of the bot is refused
+        database
+            .connection
+            .execute("UPDATE schema_version SET version = version + 1", params![])
+            .unwrap();
+        database
+            .backup_to(&backup_config.path_for_date(today))
+            .unwrap();
+        assert!(database.restore_backup(&backup_config, today).is_err());
+        fs::remove_dir_all(directory).unwrap();
+    }
+
+    #[test]
+    fn safety_copy_rotation() {
+        let directory = std::env::temp_dir().join(format!(
+            "deshittify-safety-copy-test-{}",
+            std::process::id()
+        ));
+        let backup_config = BackupConfig {
+            directory: directory.clone(),
+            keep_daily: 2,
+            keep_weekly: 4,
+        };
+        let today = NaiveDate::from_ymd(2021, 2, 1);
+        let clock = Arc::new(FakeClock::at_date(today));
+        let mut database = Database::with_clock(Path::new(":memory:"), clock.clone()).unwrap();
+        database.backup_and_rotate(&backup_config).unwrap();
+        let mut restore_times = vec![];
+        for _ in 0..3 {
+            clock.advance(Duration::minutes(1));
+            restore_times.push(database.clock().now());
+            database.restore_backup(&backup_config, today).unwrap();
+        }
+        let expected: Vec<_> = restore_times
+            .iter()
+            .rev()
+            .take(2)
+            .map(|now| backup_config.safety_copy_path(*now))
+            .collect();
+        assert_eq!(backup_config.list_safety_copies().unwrap(), expected);
+        // The regular backups are left alone
+        assert_eq!(backup_config.list_backups().unwrap(), vec![today]);
+        fs::remove_dir_all(directory).unwrap();
+    }
+}
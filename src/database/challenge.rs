@@ -1,7 +1,180 @@
-use crate::database::challenge_data::ChallengeData;
+use crate::database::{
+    challenge_data::ChallengeData, challenge_state::ChallengeState, update_schedule::UpdateSchedule,
+};
+use chrono::NaiveDate;
+use serde::Serialize;
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize)]
 pub struct Challenge {
     pub id: i64,
     pub data: ChallengeData,
+    pub archived: bool,
+    /// Whether the tasks in this challenge can have different weights.
+    pub weights_allowed: bool,
+    pub update_schedule: UpdateSchedule,
+}
+
+impl Challenge {
+    pub fn state(&self, today: NaiveDate) -> ChallengeState {
+        let time_frame = &self.data.time_frame;
+        if self.archived {
+            ChallengeState::Archived
+        } else if today < time_frame.start {
+            ChallengeState::Upcoming
+        } else if today <= time_frame.end {
+            ChallengeState::Active
+        } else {
+            ChallengeState::Finished
+        }
+    }
+
+    /// Challenges without an owner, from before owners were stored or whose owner
+    /// deleted their data, can be managed by all their subscribers.
+    pub fn can_be_managed_by(&self, user_id: i32) -> bool {
+        match self.data.owner_id {
+            Some(owner_id) => owner_id == user_id,
+            None => true,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::Duration;
+
+    use crate::{
+        action::Action,
+        clock::{Clock, FakeClock},
+        database::{
+            challenge_data::ChallengeData, scoring::Scoring, storage::Storage, task_data::TaskData,
+            update_schedule::UpdateSchedule,
+        },
+        response::Response,
+        test_utils::{create_challenge, for_each_backend, gym, perform, reply_text, signup},
+        time_frame::TimeFrame,
+    };
+
+    fn run_challenge_lifecycle(database: &dyn Storage, _clock: &FakeClock) {
+        signup(database, &[1]);
+        create_challenge(database, "finished", -30, -1);
+        create_challenge(database, "active", -1, 30);
+        create_challenge(database, "upcoming", 1, 30);
+        let user_tasks = database.get_user_tasks().unwrap();
+        assert_eq!(user_tasks.data.len(), 1);
+        assert_eq!(user_tasks.data[0].options.len(), 1);
+        let update_data = database.get_challenge_update_data().unwrap();
+        assert_eq!(update_data.0.len(), 1);
+        assert_eq!(update_data.0[0].challenge.data.name, "active");
+        assert_eq!(
+            reply_text(perform(
+                database,
+                &Action::ArchiveChallenge(1, "active".into())
+            )),
+            "Error: Only finished challenges can be archived."
+        );
+        assert_eq!(
+            reply_text(perform(
+                database,
+                &Action::ArchiveChallenge(1, "finished".into())
+            )),
+            "Challenge finished archived."
+        );
+        let list = reply_text(perform(database, &Action::ListChallenges(1)));
+        let states: Vec<&str> = list
+            .lines()
+            .map(|line| line.rsplit(": ").next().unwrap())
+            .collect();
+        assert_eq!(states, vec!["archived", "active", "upcoming"]);
+        // Archived challenges can still be looked at
+        match perform(
+            database,
+            &Action::ShowChallengeStats(1, 30, "finished".into()),
+        ) {
+            Response::ChallengeUpdates(update_data) => {
+                assert_eq!(update_data.0[0].chat_id, 30);
+                assert_eq!(update_data.0[0].user_performance.len(), 1);
+            }
+            response => panic!("Expected challenge updates, got {:?}", response),
+        }
+        match perform(database, &Action::ShowStreaks(1, 30, "active".into())) {
+            Response::Streaks(challenge_performance) => {
+                assert_eq!(challenge_performance.challenge.data.name, "active");
+            }
+            response => panic!("Expected streaks, got {:?}", response),
+        }
+    }
+
+    #[test]
+    fn challenge_lifecycle() {
+        for_each_backend(run_challenge_lifecycle);
+    }
+
+    fn run_last_day_of_challenge(database: &dyn Storage, clock: &FakeClock) {
+        signup(database, &[1]);
+        create_challenge(database, "c", -5, 0);
+        let task_named = |name: &str| TaskData {
+            name: name.into(),
+            ..gym()
+        };
+        assert_eq!(
+            reply_text(perform(
+                database,
+                &Action::AddTask(1, "c".into(), task_named("yoga"))
+            )),
+            "Task yoga added. Kaclxokca!"
+        );
+        clock.advance(Duration::days(1));
+        assert_eq!(
+            reply_text(perform(
+                database,
+                &Action::AddTask(1, "c".into(), task_named("run"))
+            )),
+            "Error: No (active) challenge with this name found for this user"
+        );
+    }
+
+    #[test]
+    fn last_day_of_challenge() {
+        for_each_backend(run_last_day_of_challenge);
+    }
+
+    fn run_ownerless_challenge(database: &dyn Storage, clock: &FakeClock) {
+        let today = clock.today();
+        signup(database, &[1, 2]);
+        let challenge = database
+            .add_challenge(&ChallengeData {
+                name: "c".into(),
+                time_frame: TimeFrame::new(today, today + Duration::days(30)),
+                owner_id: None,
+                scoring: Scoring::Default,
+            })
+            .unwrap();
+        database
+            .subscribe_to_challenge(&2, &(challenge.id as i32))
+            .unwrap();
+        assert_eq!(
+            reply_text(perform(
+                database,
+                &Action::SetWeightsAllowed(2, "c".into(), true)
+            )),
+            "Tasks in c can have weights now."
+        );
+        assert!(reply_text(perform(
+            database,
+            &Action::SetWeightsAllowed(1, "c".into(), false)
+        ))
+        .starts_with("Error: "));
+        assert_eq!(
+            reply_text(perform(
+                database,
+                &Action::SetUpdateSchedule(2, "c".into(), UpdateSchedule::Off)
+            )),
+            "c gets no more updates."
+        );
+    }
+
+    #[test]
+    fn ownerless_challenge() {
+        for_each_backend(run_ownerless_challenge);
+    }
 }
@@ -1,7 +1,14 @@
+use super::scoring::Scoring;
 use crate::time_frame::TimeFrame;
+use serde::{Deserialize, Serialize};
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct ChallengeData {
     pub name: String,
     pub time_frame: TimeFrame,
+    /// The user who created the challenge. Older challenges do not have an owner.
+    #[serde(default)]
+    pub owner_id: Option<i32>,
+    #[serde(default)]
+    pub scoring: Scoring,
 }
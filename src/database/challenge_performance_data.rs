@@ -1,3 +1,5 @@
+use chrono::NaiveDate;
+
 use super::{challenge::Challenge, user_performance_data::UserPerformanceData};
 
 #[derive(Debug)]
@@ -5,4 +7,6 @@ pub struct ChallengePerformanceData {
     pub chat_id: i64,
     pub challenge: Challenge,
     pub user_performance: Vec<UserPerformanceData>,
+    /// The local date of whoever the performance is shown to.
+    pub today: NaiveDate,
 }
@@ -0,0 +1,23 @@
+use serde::Serialize;
+use std::fmt;
+
+/// Where a challenge is in its lifetime. Only active challenges get polls and updates.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+pub enum ChallengeState {
+    Upcoming,
+    Active,
+    Finished,
+    Archived,
+}
+
+impl fmt::Display for ChallengeState {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            ChallengeState::Upcoming => "upcoming",
+            ChallengeState::Active => "active",
+            ChallengeState::Finished => "finished",
+            ChallengeState::Archived => "archived",
+        };
+        write!(f, "{}", name)
+    }
+}
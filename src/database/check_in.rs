@@ -0,0 +1,13 @@
+use chrono::NaiveDate;
+use serde::Serialize;
+
+/// The answer of a user to one poll option.
+#[derive(Debug, Clone, Serialize)]
+pub struct CheckIn {
+    pub task_id: i64,
+    pub date: NaiveDate,
+    pub poll_id: String,
+    pub done: bool,
+    /// The amount done, for quantitative tasks.
+    pub amount: Option<f64>,
+}
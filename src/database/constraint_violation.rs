@@ -0,0 +1,41 @@
+use std::fmt;
+
+// Extended result codes, see https://www.sqlite.org/rescode.html
+const SQLITE_CONSTRAINT_FOREIGNKEY: i32 = 787;
+const SQLITE_CONSTRAINT_PRIMARYKEY: i32 = 1555;
+const SQLITE_CONSTRAINT_UNIQUE: i32 = 2067;
+
+/// Returned by the database whenever a write would violate the integrity of the stored data.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ConstraintViolation {
+    /// The row already exists.
+    Unique,
+    /// The row refers to something that does not exist.
+    ForeignKey,
+}
+
+impl fmt::Display for ConstraintViolation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConstraintViolation::Unique => write!(f, "Entry already exists"),
+            ConstraintViolation::ForeignKey => write!(f, "Entry refers to a missing entry"),
+        }
+    }
+}
+
+impl std::error::Error for ConstraintViolation {}
+
+impl ConstraintViolation {
+    pub fn from_sqlite_error(err: &rusqlite::Error) -> Option<ConstraintViolation> {
+        match err {
+            rusqlite::Error::SqliteFailure(error, _) => match error.extended_code {
+                SQLITE_CONSTRAINT_UNIQUE | SQLITE_CONSTRAINT_PRIMARYKEY => {
+                    Some(ConstraintViolation::Unique)
+                }
+                SQLITE_CONSTRAINT_FOREIGNKEY => Some(ConstraintViolation::ForeignKey),
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+}
@@ -0,0 +1,260 @@
+use chrono::NaiveDate;
+use serde::{Deserialize, Serialize};
+
+use super::delivery_kind::DeliveryKind;
+
+/// A poll, challenge update or forecast nudge for one chat on one day.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Delivery {
+    pub kind: DeliveryKind,
+    /// The user for polls, the challenge for updates and nudges.
+    pub subject_id: i64,
+    pub chat_id: i64,
+    /// The local day of the user, the challenge owner or the recipient of a nudge.
+    pub date: NaiveDate,
+}
+
+impl Delivery {
+    pub fn poll(user_id: i32, chat_id: i64, date: NaiveDate) -> Delivery {
+        Delivery {
+            kind: DeliveryKind::Poll,
+            subject_id: user_id as i64,
+            chat_id,
+            date,
+        }
+    }
+
+    pub fn update(challenge_id: i64, chat_id: i64, date: NaiveDate) -> Delivery {
+        Delivery {
+            kind: DeliveryKind::Update,
+            subject_id: challenge_id,
+            chat_id,
+            date,
+        }
+    }
+
+    pub fn nudge(challenge_id: i64, chat_id: i64, date: NaiveDate) -> Delivery {
+        Delivery {
+            kind: DeliveryKind::Nudge,
+            subject_id: challenge_id,
+            chat_id,
+            date,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{Datelike, Duration, NaiveDate};
+
+    use crate::{
+        action::{Action, DeliveryOutcome, DeliveryReport, UserPollDateInfo},
+        clock::{Clock, FakeClock},
+        config,
+        database::{storage::Storage, update_schedule::UpdateSchedule},
+        response::Response,
+        test_utils::{
+            create_challenge, for_each_backend, join_challenge, perform, reply_text, send_polls,
+            signup,
+        },
+    };
+
+    use super::Delivery;
+
+    fn run_forecast_nudges(database: &dyn Storage, clock: &FakeClock) {
+        let first = NaiveDate::from_ymd(2021, 2, 1);
+        let nudge_day = match config::FORECAST_NUDGE_WEEKDAY {
+            Some(weekday) => (0..7)
+                .map(|days| first + Duration::days(days))
+                .find(|day| day.weekday() == weekday)
+                .unwrap(),
+            None => return,
+        };
+        signup(database, &[1, 2]);
+        perform(
+            database,
+            &Action::SetTimezone(2, Some(chrono_tz::Pacific::Auckland)),
+        );
+        let challenge_id = create_challenge(database, "c", -1, 30);
+        join_challenge(database, 2, challenge_id);
+        // The nudges do not depend on the updates
+        perform(
+            database,
+            &Action::SetUpdateSchedule(1, "c".into(), UpdateSchedule::Off),
+        );
+        let send_nudges = || match perform(database, &Action::CheckDateMaybeSendForecastNudges) {
+            Response::ForecastNudges(nudge_data) => nudge_data
+                .1
+                .iter()
+                .map(|delivery| (delivery.chat_id, delivery.date))
+                .collect::<Vec<_>>(),
+            response => panic!("Expected forecast nudges, got {:?}", response),
+        };
+        // 01:00 of the nudge day in Auckland
+        clock.set(nudge_day.pred().and_hms(12, 0, 0));
+        assert!(send_nudges().is_empty());
+        // 17:00 in Auckland, but still the early morning on the server
+        clock.set(nudge_day.and_hms(4, 0, 0));
+        assert_eq!(send_nudges(), vec![(20, nudge_day)]);
+        assert!(send_nudges().is_empty());
+        clock.set(nudge_day.and_hms(17, 0, 0));
+        assert_eq!(send_nudges(), vec![(10, nudge_day)]);
+        assert!(send_nudges().is_empty());
+        // The next day of the week, in Auckland as well
+        clock.set(nudge_day.succ().and_hms(17, 0, 0));
+        assert!(send_nudges().is_empty());
+    }
+
+    #[test]
+    fn forecast_nudges() {
+        for_each_backend(run_forecast_nudges);
+    }
+
+    fn run_deliveries(database: &dyn Storage, clock: &FakeClock) {
+        let first = NaiveDate::from_ymd(2021, 2, 1);
+        signup(database, &[1, 2, 3]);
+        let challenge_id = create_challenge(database, "c", -1, 30);
+        for user_id in 2..=3 {
+            join_challenge(database, user_id, challenge_id);
+        }
+        let send_updates = |outcome: &dyn Fn(i64) -> DeliveryOutcome| match perform(
+            database,
+            &Action::CheckDateMaybeSendChallengeUpdates,
+        ) {
+            Response::ChallengeUpdates(update_data) => {
+                let reports = update_data
+                    .1
+                    .iter()
+                    .map(|delivery: &Delivery| DeliveryReport {
+                        delivery: delivery.clone(),
+                        outcome: outcome(delivery.chat_id),
+                    })
+                    .collect();
+                perform(database, &Action::RecordDeliveries(reports));
+                update_data
+                    .0
+                    .iter()
+                    .map(|update| update.chat_id)
+                    .collect::<Vec<_>>()
+            }
+            response => panic!("Expected challenge updates, got {:?}", response),
+        };
+        // Each chat gets its own update, a failed one is retried after a while
+        clock.set(first.and_hms(17, 0, 0));
+        let only_20_fails = |chat_id| match chat_id {
+            20 => DeliveryOutcome::Failed,
+            _ => DeliveryOutcome::Delivered,
+        };
+        assert_eq!(send_updates(&only_20_fails), vec![10, 20, 30]);
+        assert!(send_updates(&only_20_fails).is_empty());
+        clock.advance(Duration::seconds(config::DELIVERY_RETRY_DELAY_SECS));
+        assert_eq!(send_updates(&|_| DeliveryOutcome::Delivered), vec![20]);
+        clock.advance(Duration::hours(1));
+        assert!(send_updates(&|_| DeliveryOutcome::Delivered).is_empty());
+        // 2 keeps failing until it runs out of attempts, 3 blocked the bot
+        clock.set(first.and_hms(22, 0, 0));
+        let outcome = |user_id| match user_id {
+            1 => DeliveryOutcome::Delivered,
+            2 => DeliveryOutcome::Failed,
+            _ => DeliveryOutcome::Refused,
+        };
+        assert_eq!(send_polls(database, outcome), vec![1, 2, 3]);
+        let mut attempts = 1;
+        while clock.now() < first.and_hms(23, 59, 0) {
+            clock.advance(Duration::minutes(1));
+            let users = send_polls(database, outcome);
+            assert!(users.is_empty() || users == vec![2]);
+            attempts += users.len();
+        }
+        assert_eq!(attempts, config::DELIVERY_ATTEMPTS as usize);
+        // Running out of attempts does not make a chat inactive, but a refusal does
+        let second = first.succ();
+        clock.set(second.and_hms(17, 0, 0));
+        assert_eq!(send_updates(&|_| DeliveryOutcome::Delivered), vec![10, 20]);
+        // A rejected poll is not tried again
+        clock.set(second.and_hms(22, 0, 0));
+        let outcome = |user_id| match user_id {
+            2 => DeliveryOutcome::Rejected,
+            _ => DeliveryOutcome::Delivered,
+        };
+        assert_eq!(send_polls(database, outcome), vec![1, 2]);
+        clock.advance(Duration::hours(1));
+        assert!(send_polls(database, outcome).is_empty());
+        // Inactive users get nothing until they resume
+        assert_eq!(
+            reply_text(perform(database, &Action::ResumeUser(3))),
+            "Welcome back!"
+        );
+        clock.set(second.succ().and_hms(22, 0, 0));
+        assert_eq!(
+            send_polls(database, |_| DeliveryOutcome::Delivered),
+            vec![1, 2, 3]
+        );
+    }
+
+    #[test]
+    fn deliveries() {
+        for_each_backend(run_deliveries);
+    }
+
+    fn run_catch_up(database: &dyn Storage, clock: &FakeClock) {
+        let first = NaiveDate::from_ymd(2021, 2, 1);
+        signup(database, &[1]);
+        create_challenge(database, "c", -1, 30);
+        let check_polls = || match perform(database, &Action::CheckDateMaybeSendPolls) {
+            Response::TaskPolls(polls) => polls.data,
+            response => panic!("Expected polls, got {:?}", response),
+        };
+        // A new user does not get polls for the days before
+        clock.set(first.and_hms(22, 0, 0));
+        assert_eq!(
+            send_polls(database, |_| DeliveryOutcome::Delivered),
+            vec![1]
+        );
+        // The bot is down in the evening of the second day and back at one in the night
+        let second = first.succ();
+        clock.set(second.succ().and_hms(1, 0, 0));
+        let polls = check_polls();
+        assert_eq!(polls.len(), 1);
+        assert_eq!(polls[0].date, second);
+        assert!(polls[0].late);
+        let task_id = polls[0].options[0].task_id;
+        perform(
+            database,
+            &Action::RecordDeliveries(vec![DeliveryReport {
+                delivery: polls[0].delivery(),
+                outcome: DeliveryOutcome::Delivered,
+            }]),
+        );
+        perform(
+            database,
+            &Action::WritePollInfo(vec![UserPollDateInfo {
+                user_id: 1,
+                date: polls[0].date,
+                task_id,
+                poll_id: "late".into(),
+                task_index: 0,
+            }]),
+        );
+        perform(
+            database,
+            &Action::ModifyUserTaskTimestamps("late".into(), vec![0]),
+        );
+        assert_eq!(
+            database.get_timestamps_for_task(task_id, 1).unwrap(),
+            vec![second]
+        );
+        assert!(check_polls().is_empty());
+        // The poll of the day itself comes as usual
+        clock.set(second.succ().and_hms(22, 0, 0));
+        let polls = check_polls();
+        assert_eq!(polls.len(), 1);
+        assert_eq!(polls[0].date, second.succ());
+        assert!(!polls[0].late);
+    }
+
+    #[test]
+    fn catch_up() {
+        for_each_backend(run_catch_up);
+    }
+}
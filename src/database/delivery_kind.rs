@@ -0,0 +1,32 @@
+use anyhow::anyhow;
+use serde::{Deserialize, Serialize};
+use std::{fmt, str::FromStr};
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum DeliveryKind {
+    Poll,
+    Update,
+    Nudge,
+}
+
+impl FromStr for DeliveryKind {
+    type Err = anyhow::Error;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "poll" => Ok(Self::Poll),
+            "update" => Ok(Self::Update),
+            "nudge" => Ok(Self::Nudge),
+            _ => Err(anyhow!("Wrong delivery kind.")),
+        }
+    }
+}
+
+impl fmt::Display for DeliveryKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DeliveryKind::Poll => write!(f, "poll"),
+            DeliveryKind::Update => write!(f, "update"),
+            DeliveryKind::Nudge => write!(f, "nudge"),
+        }
+    }
+}
@@ -0,0 +1,21 @@
+use chrono::NaiveDateTime;
+
+use crate::config;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct DeliveryStatus {
+    pub attempts: u32,
+    /// When a failed delivery may be tried again.
+    pub next_attempt: NaiveDateTime,
+    pub delivered: bool,
+}
+
+impl DeliveryStatus {
+    pub fn can_attempt(&self, now: NaiveDateTime) -> bool {
+        !self.delivered && !self.attempts_exhausted() && now >= self.next_attempt
+    }
+
+    pub fn attempts_exhausted(&self) -> bool {
+        self.attempts >= config::DELIVERY_ATTEMPTS
+    }
+}
@@ -0,0 +1,767 @@
+use anyhow::{anyhow, Result};
+use chrono::{NaiveDate, NaiveDateTime, NaiveTime};
+use chrono_tz::Tz;
+use std::cell::RefCell;
+use std::sync::Arc;
+
+use crate::{
+    action::UserPollDateInfo,
+    clock::{Clock, SystemClock},
+    config,
+};
+
+use super::{
+    action_log_entry::ActionLogEntry,
+    challenge::Challenge,
+    challenge_data::ChallengeData,
+    check_in::CheckIn,
+    constraint_violation::ConstraintViolation,
+    delivery::Delivery,
+    delivery_status::DeliveryStatus,
+    pause::Pause,
+    pending_amount::PendingAmount,
+    storage::Storage,
+    task::Task,
+    task_data::TaskData,
+    update_schedule::UpdateSchedule,
+    user::{User, UserData},
+    user_task::UserTask,
+};
+
+struct UserRow {
+    user_id: i32,
+    chat_id: i64,
+    name: String,
+    timezone: Option<Tz>,
+    poll_time: Option<NaiveTime>,
+    inactive: bool,
+}
+
+struct TaskRow {
+    user_id: i32,
+    challenge_id: i32,
+    task: Task,
+}
+
+struct UserPollDateRow {
+    id: i64,
+    date: NaiveDate,
+    user_id: i32,
+    poll_id: String,
+    task_id: i64,
+    task_index: i32,
+    done: bool,
+    amount: Option<f64>,
+}
+
+struct PauseRow {
+    user_id: i32,
+    pause: Pause,
+}
+
+#[derive(Default)]
+struct Tables {
+    challenges: Vec<Challenge>,
+    users: Vec<UserRow>,
+    subscriptions: Vec<(i32, i32)>,
+    tasks: Vec<TaskRow>,
+    user_poll_dates: Vec<UserPollDateRow>,
+    deliveries: Vec<(Delivery, DeliveryStatus)>,
+    pauses: Vec<PauseRow>,
+    action_log: Vec<ActionLogEntry>,
+    next_challenge_id: i64,
+    next_task_id: i64,
+    next_user_poll_date_id: i64,
+    next_pause_id: i64,
+}
+
+/// An implementation of [Storage] which keeps everything in memory.
+/// Useful for tests and for running the bot without touching the file system.
+pub struct MemoryDatabase {
+    tables: RefCell<Tables>,
+    clock: Arc<dyn Clock>,
+}
+
+impl MemoryDatabase {
+    pub fn new() -> MemoryDatabase {
+        MemoryDatabase::with_clock(Arc::new(SystemClock))
+    }
+
+    pub fn with_clock(clock: Arc<dyn Clock>) -> MemoryDatabase {
+        MemoryDatabase {
+            tables: RefCell::new(Tables::default()),
+            clock,
+        }
+    }
+}
+
+impl Default for MemoryDatabase {
+    fn default() -> Self {
+        MemoryDatabase::new()
+    }
+}
+
+impl Tables {
+    fn user_exists(&self, user_id: &i32) -> bool {
+        self.users.iter().any(|user| user.user_id == *user_id)
+    }
+
+    fn challenge_exists(&self, challenge_id: &i32) -> bool {
+        self.challenges
+            .iter()
+            .any(|challenge| challenge.id == *challenge_id as i64)
+    }
+}
+
+impl Storage for MemoryDatabase {
+    fn clock(&self) -> &dyn Clock {
+        &*self.clock
+    }
+
+    fn add_challenge(&self, challenge: &ChallengeData) -> Result<Challenge> {
+        let mut tables = self.tables.borrow_mut();
+        tables.next_challenge_id += 1;
+        let challenge = Challenge {
+            id: tables.next_challenge_id,
+            data: challenge.clone(),
+            archived: false,
+            weights_allowed: true,
+            update_schedule: UpdateSchedule::default(),
+        };
+        tables.challenges.push(challenge.clone());
+        Ok(challenge)
+    }
+
+    fn check_user_subscribed_to_challenge(
+        &self,
+        user_id: &i32,
+        challenge_id: &i32,
+    ) -> Result<bool> {
+        Ok(self
+            .tables
+            .borrow()
+            .subscriptions
+            .contains(&(*user_id, *challenge_id)))
+    }
+
+    fn check_user_signed_up(&self, user_id: &i32) -> Result<bool> {
+        Ok(self.tables.borrow().user_exists(user_id))
+    }
+
+    fn insert_subscription(&self, user_id: &i32, challenge_id: &i32) -> Result<()> {
+        let mut tables = self.tables.borrow_mut();
+        if !tables.user_exists(user_id) || !tables.challenge_exists(challenge_id) {
+            return Err(ConstraintViolation::ForeignKey.into());
+        }
+        if tables.subscriptions.contains(&(*user_id, *challenge_id)) {
+            return Err(ConstraintViolation::Unique.into());
+        }
+        tables.subscriptions.push((*user_id, *challenge_id));
+        Ok(())
+    }
+
+    fn signup_user(&self, user_id: &i32, chat_id: &i64, user_name: &str) -> Result<()> {
+        let mut tables = self.tables.borrow_mut();
+        if tables.user_exists(user_id) {
+            return Err(ConstraintViolation::Unique.into());
+        }
+        tables.users.push(UserRow {
+            user_id: *user_id,
+            chat_id: *chat_id,
+            name: user_name.to_owned(),
+            timezone: None,
+            poll_time: None,
+            inactive: false,
+        });
+        Ok(())
+    }
+
+    fn get_active_challenge_id_by_user_id_and_name(
+        &self,
+        user_id: &i32,
+        challenge_name: &str,
+        date: NaiveDate,
+    ) -> Result<i32> {
+        let tables = self.tables.borrow();
+        tables
+            .challenges
+            .iter()
+            .find(|challenge| {
+                challenge.data.name == challenge_name
+                    && challenge.data.time_frame.end >= date
+                    && tables
+                        .subscriptions
+                        .contains(&(*user_id, challenge.id as i32))
+            })
+            .map(|challenge| challenge.id as i32)
+            .ok_or_else(|| anyhow!("No (active) challenge with this name found for this user"))
+    }
+
+    fn insert_task(
+        &self,
+        user_id: &i32,
+        challenge_id: &i32,
+        task_data: &TaskData,
+        active_from: NaiveDate,
+    ) -> Result<()> {
+        let mut tables = self.tables.borrow_mut();
+        if !tables.user_exists(user_id) || !tables.challenge_exists(challenge_id) {
+            return Err(ConstraintViolation::ForeignKey.into());
+        }
+        if tables.tasks.iter().any(|row| {
+            row.user_id == *user_id
+                && row.challenge_id == *challenge_id
+                && row.task.data.name == task_data.name
+        }) {
+            return Err(ConstraintViolation::Unique.into());
+        }
+        tables.next_task_id += 1;
+        let task = Task {
+            id: tables.next_task_id,
+            data: task_data.clone(),
+            active_from,
+            active_until: None,
+        };
+        tables.tasks.push(TaskRow {
+            user_id: *user_id,
+            challenge_id: *challenge_id,
+            task,
+        });
+        Ok(())
+    }
+
+    fn get_all_user_tasks(&self) -> Result<Vec<UserTask>> {
+        let tables = self.tables.borrow();
+        let mut user_tasks: Vec<UserTask> = tables
+            .tasks
+            .iter()
+            .filter_map(|row| {
+                let user = tables
+                    .users
+                    .iter()
+                    .find(|user| user.user_id == row.user_id && !user.inactive)?;
+                let challenge = tables
+                    .challenges
+                    .iter()
+                    .find(|challenge| challenge.id == row.challenge_id as i64)?;
+                Some(UserTask {
+                    user_id: user.user_id,
+                    chat_id: user.chat_id,
+                    timezone: user.timezone,
+                    challenge: challenge.clone(),
+                    task: row.task.clone(),
+                })
+            })
+            .collect();
+        user_tasks.sort_by(|task1, task2| {
+            (task1.chat_id, &task1.task.data.name, task1.task.id).cmp(&(
+                task2.chat_id,
+                &task2.task.data.name,
+                task2.task.id,
+            ))
+        });
+        Ok(user_tasks)
+    }
+
+    fn get_challenges_and_chat_ids(&self) -> Result<Vec<(Challenge, i64)>> {
+        let tables = self.tables.borrow();
+        Ok(tables
+            .subscriptions
+            .iter()
+            .filter_map(|(user_id, challenge_id)| {
+                let challenge = tables
+                    .challenges
+                    .iter()
+                    .find(|challenge| challenge.id == *challenge_id as i64)?;
+                let user = tables
+                    .users
+                    .iter()
+                    .find(|user| user.user_id == *user_id && !user.inactive)?;
+                Some((challenge.clone(), user.chat_id))
+            })
+            .collect())
+    }
+
+    fn get_tasks_for_challenge_and_user(
+        &self,
+        challenge_id: i64,
+        user_id: i64,
+    ) -> Result<Vec<Task>> {
+        Ok(self
+            .tables
+            .borrow()
+            .tasks
+            .iter()
+            .filter(|row| row.challenge_id as i64 == challenge_id && row.user_id as i64 == user_id)
+            .map(|row| row.task.clone())
+            .collect())
+    }
+
+    fn get_challenge_users(&self, challenge_id: i64) -> Result<Vec<UserData>> {
+        let tables = self.tables.borrow();
+        Ok(tables
+            .users
+            .iter()
+            .filter(|user| {
+                tables
+                    .subscriptions
+                    .contains(&(user.user_id, challenge_id as i32))
+            })
+            .map(|user| UserData {
+                user_id: user.user_id as i64,
+                name: user.name.clone(),
+            })
+            .collect())
+    }
+
+    fn get_timestamps_for_task(&self, task_id: i64, user_id: i64) -> Result<Vec<NaiveDate>> {
+        Ok(self
+            .tables
+            .borrow()
+            .user_poll_dates
+            .iter()
+            .filter(|row| row.done && row.task_id == task_id && row.user_id as i64 == user_id)
+            .map(|row| row.date)
+            .collect())
+    }
+
+    fn get_amounts_for_task(&self, task_id: i64, user_id: i64) -> Result<Vec<(NaiveDate, f64)>> {
+        Ok(self
+            .tables
+            .borrow()
+            .user_poll_dates
+            .iter()
+            .filter(|row| row.done && row.task_id == task_id && row.user_id as i64 == user_id)
+            .filter_map(|row| Some((row.date, row.amount?)))
+            .collect())
+    }
+
+    fn get_pending_amounts(&self, user_id: &i32) -> Result<Vec<PendingAmount>> {
+        let tables = self.tables.borrow();
+        let mut pending: Vec<PendingAmount> = tables
+            .user_poll_dates
+            .iter()
+            .filter(|row| row.done && row.amount.is_none() && row.user_id == *user_id)
+            .filter_map(|row| {
+                let task_row = tables
+                    .tasks
+                    .iter()
+                    .find(|task| task.task.id == row.task_id)?;
+                task_row.task.data.unit.as_ref()?;
+                Some(PendingAmount {
+                    check_in_id: row.id,
+                    task: task_row.task.data.clone(),
+                    date: row.date,
+                })
+            })
+            .collect();
+        pending.sort_by_key(|pending_amount| (pending_amount.date, pending_amount.check_in_id));
+        Ok(pending)
+    }
+
+    fn set_check_in_amount(&self, check_in_id: i64, amount: f64) -> Result<()> {
+        for row in self
+            .tables
+            .borrow_mut()
+            .user_poll_dates
+            .iter_mut()
+            .filter(|row| row.id == check_in_id)
+        {
+            row.amount = Some(amount);
+        }
+        Ok(())
+    }
+
+    fn get_delivery_status(&self, delivery: &Delivery) -> Result<Option<DeliveryStatus>> {
+        Ok(self
+            .tables
+            .borrow()
+            .deliveries
+            .iter()
+            .find(|(other, _)| other == delivery)
+            .map(|(_, status)| status.clone()))
+    }
+
+    fn write_delivery_attempt(
+        &self,
+        delivery: &Delivery,
+        next_attempt: NaiveDateTime,
+    ) -> Result<()> {
+        let mut tables = self.tables.borrow_mut();
+        match tables
+            .deliveries
+            .iter_mut()
+            .find(|(other, _)| other == delivery)
+        {
+            Some((_, status)) => {
+                status.attempts += 1;
+                status.next_attempt = next_attempt;
+            }
+            None => tables.deliveries.push((
+                delivery.clone(),
+                DeliveryStatus {
+                    attempts: 1,
+                    next_attempt,
+                    delivered: false,
+                },
+            )),
+        }
+        Ok(())
+    }
+
+    fn mark_delivered(&self, delivery: &Delivery) -> Result<()> {
+        for (_, status) in self
+            .tables
+            .borrow_mut()
+            .deliveries
+            .iter_mut()
+            .filter(|(other, _)| other == delivery)
+        {
+            status.delivered = true;
+        }
+        Ok(())
+    }
+
+    fn give_up_delivery(&self, delivery: &Delivery) -> Result<()> {
+        for (_, status) in self
+            .tables
+            .borrow_mut()
+            .deliveries
+            .iter_mut()
+            .filter(|(other, _)| other == delivery)
+        {
+            status.attempts = config::DELIVERY_ATTEMPTS;
+        }
+        Ok(())
+    }
+
+    fn set_chat_inactive(&self, chat_id: i64, inactive: bool) -> Result<()> {
+        for user in self
+            .tables
+            .borrow_mut()
+            .users
+            .iter_mut()
+            .filter(|user| user.chat_id == chat_id)
+        {
+            user.inactive = inactive;
+        }
+        Ok(())
+    }
+
+    fn set_poll_time(&self, user_id: &i32, poll_time: Option<NaiveTime>) -> Result<()> {
+        if let Some(user) = self
+            .tables
+            .borrow_mut()
+            .users
+            .iter_mut()
+            .find(|user| user.user_id == *user_id)
+        {
+            user.poll_time = poll_time;
+        }
+        Ok(())
+    }
+
+    fn set_update_schedule(&self, challenge_id: i64, schedule: UpdateSchedule) -> Result<()> {
+        for challenge in self
+            .tables
+            .borrow_mut()
+            .challenges
+            .iter_mut()
+            .filter(|challenge| challenge.id == challenge_id)
+        {
+            challenge.update_schedule = schedule;
+        }
+        Ok(())
+    }
+
+    fn set_user_timezone(&self, user_id: &i32, timezone: Option<Tz>) -> Result<()> {
+        if let Some(user) = self
+            .tables
+            .borrow_mut()
+            .users
+            .iter_mut()
+            .find(|user| user.user_id == *user_id)
+        {
+            user.timezone = timezone;
+        }
+        Ok(())
+    }
+
+    fn modify_user_task_entries(&self, poll_id: &str, option_ids: &[i32]) -> Result<()> {
+        for row in self
+            .tables
+            .borrow_mut()
+            .user_poll_dates
+            .iter_mut()
+            .filter(|row| row.poll_id == poll_id)
+        {
+            row.done = option_ids.contains(&row.task_index);
+        }
+        Ok(())
+    }
+
+    fn write_poll_info(&self, info: &[UserPollDateInfo]) -> Result<()> {
+        let mut tables = self.tables.borrow_mut();
+        for user_poll_date_info in info.iter() {
+            tables.next_user_poll_date_id += 1;
+            let id = tables.next_user_poll_date_id;
+            tables.user_poll_dates.push(UserPollDateRow {
+                id,
+                date: user_poll_date_info.date,
+                user_id: user_poll_date_info.user_id,
+                poll_id: user_poll_date_info.poll_id.clone(),
+                task_id: user_poll_date_info.task_id,
+                task_index: user_poll_date_info.task_index,
+                done: false,
+                amount: None,
+            });
+        }
+        Ok(())
+    }
+
+    fn get_sent_task_ids(&self, user_id: &i32, date: NaiveDate) -> Result<Vec<i64>> {
+        Ok(self
+            .tables
+            .borrow()
+            .user_poll_dates
+            .iter()
+            .filter(|row| row.user_id == *user_id && row.date == date)
+            .map(|row| row.task_id)
+            .collect())
+    }
+
+    fn get_date_from_poll_id(&self, poll_id: &str) -> Result<NaiveDate> {
+        self.tables
+            .borrow()
+            .user_poll_dates
+            .iter()
+            .find(|row| row.poll_id == poll_id)
+            .map(|row| row.date)
+            .ok_or_else(|| {
+                anyhow!(
+                    "The poll with id {} is not in the userPoll database",
+                    poll_id
+                )
+            })
+    }
+
+    fn get_user_id_from_poll_id(&self, poll_id: &str) -> Result<i32> {
+        self.tables
+            .borrow()
+            .user_poll_dates
+            .iter()
+            .find(|row| row.poll_id == poll_id)
+            .map(|row| row.user_id)
+            .ok_or_else(|| {
+                anyhow!(
+                    "The poll with id {} is not in the userPoll database",
+                    poll_id
+                )
+            })
+    }
+
+    fn get_user(&self, user_id: &i32) -> Result<Option<User>> {
+        Ok(self
+            .tables
+            .borrow()
+            .users
+            .iter()
+            .find(|user| user.user_id == *user_id)
+            .map(|user| User {
+                chat_id: user.chat_id,
+                data: UserData {
+                    user_id: user.user_id as i64,
+                    name: user.name.clone(),
+                },
+                timezone: user.timezone,
+                poll_time: user.poll_time,
+                inactive: user.inactive,
+            }))
+    }
+
+    fn get_challenges_for_user(&self, user_id: &i32) -> Result<Vec<Challenge>> {
+        let tables = self.tables.borrow();
+        Ok(tables
+            .challenges
+            .iter()
+            .filter(|challenge| {
+                tables
+                    .subscriptions
+                    .contains(&(*user_id, challenge.id as i32))
+            })
+            .cloned()
+            .collect())
+    }
+
+    fn get_check_ins_for_task(&self, task_id: i64, user_id: i64) -> Result<Vec<CheckIn>> {
+        let mut check_ins: Vec<CheckIn> = self
+            .tables
+            .borrow()
+            .user_poll_dates
+            .iter()
+            .filter(|row| row.task_id == task_id && row.user_id as i64 == user_id)
+            .map(|row| CheckIn {
+                task_id: row.task_id,
+                date: row.date,
+                poll_id: row.poll_id.clone(),
+                done: row.done,
+                amount: row.amount,
+            })
+            .collect();
+        check_ins.sort_by_key(|check_in| check_in.date);
+        Ok(check_ins)
+    }
+
+    fn archive_challenge(&self, challenge_id: i64) -> Result<()> {
+        for challenge in self
+            .tables
+            .borrow_mut()
+            .challenges
+            .iter_mut()
+            .filter(|challenge| challenge.id == challenge_id)
+        {
+            challenge.archived = true;
+        }
+        Ok(())
+    }
+
+    fn set_weights_allowed(&self, challenge_id: i64, allowed: bool) -> Result<()> {
+        for challenge in self
+            .tables
+            .borrow_mut()
+            .challenges
+            .iter_mut()
+            .filter(|challenge| challenge.id == challenge_id)
+        {
+            challenge.weights_allowed = allowed;
+        }
+        Ok(())
+    }
+
+    fn set_task_weight(&self, task_id: i64, weight: f64) -> Result<()> {
+        for row in self
+            .tables
+            .borrow_mut()
+            .tasks
+            .iter_mut()
+            .filter(|row| row.task.id == task_id)
+        {
+            row.task.data.weight = weight;
+        }
+        Ok(())
+    }
+
+    fn set_task_active_until(&self, task_id: i64, date: NaiveDate) -> Result<()> {
+        for row in self
+            .tables
+            .borrow_mut()
+            .tasks
+            .iter_mut()
+            .filter(|row| row.task.id == task_id)
+        {
+            row.task.active_until = Some(date);
+        }
+        Ok(())
+    }
+
+    fn insert_pause(&self, user_id: &i32, start: NaiveDate, end: Option<NaiveDate>) -> Result<()> {
+        let mut tables = self.tables.borrow_mut();
+        if !tables.user_exists(user_id) {
+            return Err(ConstraintViolation::ForeignKey.into());
+        }
+        tables.next_pause_id += 1;
+        let pause = Pause {
+            id: tables.next_pause_id,
+            start,
+            end,
+        };
+        tables.pauses.push(PauseRow {
+            user_id: *user_id,
+            pause,
+        });
+        Ok(())
+    }
+
+    fn get_pauses(&self, user_id: i64) -> Result<Vec<Pause>> {
+        let mut pauses: Vec<Pause> = self
+            .tables
+            .borrow()
+            .pauses
+            .iter()
+            .filter(|row| row.user_id as i64 == user_id)
+            .map(|row| row.pause.clone())
+            .collect();
+        pauses.sort_by_key(|pause| (pause.start, pause.id));
+        Ok(pauses)
+    }
+
+    fn set_pause_end(&self, pause_id: i64, end: NaiveDate) -> Result<()> {
+        for row in self
+            .tables
+            .borrow_mut()
+            .pauses
+            .iter_mut()
+            .filter(|row| row.pause.id == pause_id)
+        {
+            row.pause.end = Some(end);
+        }
+        Ok(())
+    }
+
+    fn delete_pause(&self, pause_id: i64) -> Result<()> {
+        self.tables
+            .borrow_mut()
+            .pauses
+            .retain(|row| row.pause.id != pause_id);
+        Ok(())
+    }
+
+    fn write_action_log_entry(&self, entry: &ActionLogEntry) -> Result<()> {
+        self.tables.borrow_mut().action_log.push(entry.clone());
+        Ok(())
+    }
+
+    fn get_action_log(&self) -> Result<Vec<ActionLogEntry>> {
+        Ok(self.tables.borrow().action_log.clone())
+    }
+
+    fn delete_user(&self, user_id: &i32) -> Result<()> {
+        let mut tables = self.tables.borrow_mut();
+        let chat_id = tables
+            .users
+            .iter()
+            .find(|user| user.user_id == *user_id)
+            .map(|user| user.chat_id);
+        let poll_ids: Vec<String> = tables
+            .user_poll_dates
+            .iter()
+            .filter(|row| row.user_id == *user_id)
+            .map(|row| row.poll_id.clone())
+            .collect();
+        let action_log = std::mem::take(&mut tables.action_log);
+        tables.action_log = action_log
+            .into_iter()
+            .filter(|entry| !entry.origin.is_from(*user_id, chat_id))
+            .filter_map(|entry| {
+                let action = entry.action.without_user(*user_id, chat_id, &poll_ids)?;
+                Some(ActionLogEntry { action, ..entry })
+            })
+            .collect();
+        for challenge in tables.challenges.iter_mut() {
+            if challenge.data.owner_id == Some(*user_id) {
+                challenge.data.owner_id = None;
+            }
+        }
+        tables.user_poll_dates.retain(|row| row.user_id != *user_id);
+        tables.tasks.retain(|row| row.user_id != *user_id);
+        tables.pauses.retain(|row| row.user_id != *user_id);
+        tables
+            .deliveries
+            .retain(|(delivery, _)| Some(delivery.chat_id) != chat_id);
+        tables
+            .subscriptions
+            .retain(|(subscribed_user_id, _)| subscribed_user_id != user_id);
+        tables.users.retain(|user| user.user_id != *user_id);
+        Ok(())
+    }
+}
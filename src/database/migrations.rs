@@ -0,0 +1,269 @@
+use anyhow::{anyhow, Context, Result};
+use rusqlite::{params, Connection};
+
+/// The schema migrations in the order in which they have to be applied.
+/// Migration i (counting from 1) brings the database from schema version i-1 to i.
+/// Never change a migration that has already been deployed - add a new one instead.
+static MIGRATIONS: &[&str] = &[
+    include_str!("migrations/001_initial_schema.sql"),
+    include_str!("migrations/002_constraints.sql"),
+    include_str!("migrations/003_check_in_task_ids.sql"),
+    include_str!("migrations/004_challenge_archived.sql"),
+    include_str!("migrations/005_action_log.sql"),
+    include_str!("migrations/006_task_units.sql"),
+    include_str!("migrations/007_task_kind.sql"),
+    include_str!("migrations/008_weights.sql"),
+    include_str!("migrations/009_pauses.sql"),
+    include_str!("migrations/010_task_dates.sql"),
+    include_str!("migrations/011_scoring.sql"),
+    include_str!("migrations/012_timezones.sql"),
+    include_str!("migrations/013_schedules.sql"),
+    include_str!("migrations/014_deliveries.sql"),
+    include_str!("migrations/015_action_log_offsets.sql"),
+];
+
+pub fn latest_version() -> i64 {
+    MIGRATIONS.len() as i64
+}
+
+/// Applies all pending migrations and enables foreign key enforcement afterwards.
+pub fn migrate(connection: &mut Connection) -> Result<()> {
+    migrate_to(connection, latest_version())?;
+    connection.execute_batch("PRAGMA foreign_keys = ON")?;
+    Ok(())
+}
+
+fn migrate_to(connection: &mut Connection, target_version: i64) -> Result<()> {
+    // Migrations rebuild tables, which is only possible without foreign key enforcement.
+    // The pragma is a no-op inside of transactions, so it has to be set here.
+    connection.execute_batch("PRAGMA foreign_keys = OFF")?;
+    connection.execute(
+        "CREATE TABLE IF NOT EXISTS schema_version (version INTEGER NOT NULL)",
+        params![],
+    )?;
+    let current_version = get_version(connection)?;
+    if current_version > latest_version() {
+        return Err(anyhow!(
+            "Database schema version {} is newer than the latest version known to this binary ({})",
+            current_version,
+            latest_version()
+        ));
+    }
+    for (index, migration) in MIGRATIONS
+        .iter()
+        .enumerate()
+        .take(target_version as usize)
+        .skip(current_version as usize)
+    {
+        let version = index as i64 + 1;
+        let transaction = connection.transaction()?;
+        transaction
+            .execute_batch(migration)
+            .with_context(|| format!("While applying migration {}", version))?;
+        check_foreign_keys(&transaction)
+            .with_context(|| format!("While applying migration {}", version))?;
+        transaction.execute("DELETE FROM schema_version", params![])?;
+        transaction.execute(
+            "INSERT INTO schema_version (version) VALUES (?1)",
+            params![version],
+        )?;
+        transaction.commit()?;
+        log::info!("Migrated database to schema version {}", version);
+    }
+    Ok(())
+}
+
+fn check_foreign_keys(connection: &Connection) -> Result<()> {
+    let mut statement = connection.prepare("PRAGMA foreign_key_check")?;
+    let violating_table = statement
+        .query_map(params![], |row| row.get::<_, String>(0))?
+        .next();
+    match violating_table {
+        Some(table) => Err(anyhow!("Foreign key violation in table {}", table?)),
+        None => Ok(()),
+    }
+}
+
+pub fn get_version(connection: &Connection) -> Result<i64> {
+    let mut statement = connection.prepare("SELECT version FROM schema_version")?;
+    let version = statement
+        .query_map(params![], |row| row.get::<_, i64>(0))?
+        .next()
+        .unwrap_or(Ok(0))?;
+    Ok(version)
+}
+
+#[cfg(test)]
+mod tests {
+    use rusqlite::{params, Connection};
+
+    use super::{get_version, latest_version, migrate, migrate_to};
+
+    fn count(connection: &Connection, table: &str) -> i64 {
+        connection
+            .query_row(
+                &format!("SELECT COUNT(*) FROM {}", table),
+                params![],
+                |row| row.get(0),
+            )
+            .unwrap()
+    }
+
+    #[test]
+    fn migrate_fresh_database() {
+        let mut connection = Connection::open_in_memory().unwrap();
+        migrate(&mut connection).unwrap();
+        assert_eq!(get_version(&connection).unwrap(), latest_version());
+        // Running the migrations again is a no-op
+        migrate(&mut connection).unwrap();
+        assert_eq!(get_version(&connection).unwrap(), latest_version());
+        connection
+            .execute(
+                "INSERT INTO user (user_id, chat_id, name) VALUES (?1, ?2, ?3)",
+                params![1, 1, "a"],
+            )
+            .unwrap();
+    }
+
+    #[test]
+    fn migrate_unversioned_database() {
+        // Databases created from the old empty.db have all tables but no schema_version.
+        let mut connection = Connection::open_in_memory().unwrap();
+        connection
+            .execute_batch(include_str!("migrations/001_initial_schema.sql"))
+            .unwrap();
+        connection
+            .execute(
+                "INSERT INTO challenge (name, time_start, time_end) VALUES (?1, ?2, ?3)",
+                params!["c", "1970-01-01", "1970-01-31"],
+            )
+            .unwrap();
+        migrate(&mut connection).unwrap();
+        assert_eq!(get_version(&connection).unwrap(), latest_version());
+        assert_eq!(count(&connection, "challenge"), 1);
+    }
+
+    #[test]
+    fn refuse_newer_database() {
+        let mut connection = Connection::open_in_memory().unwrap();
+        migrate(&mut connection).unwrap();
+        connection
+            .execute(
+                "UPDATE schema_version SET version = ?1",
+                params![latest_version() + 1],
+            )
+            .unwrap();
+        assert!(migrate(&mut connection).is_err());
+    }
+
+    #[test]
+    fn deduplicate_existing_data() {
+        let mut connection = Connection::open_in_memory().unwrap();
+        migrate_to(&mut connection, 1).unwrap();
+        connection
+            .execute_batch(
+                "INSERT INTO challenge (id, name, time_start, time_end) VALUES (1, 'c', '1970-01-01', '1970-01-31');
+                INSERT INTO user (user_id, chat_id, name) VALUES (1, 1, 'a');
+                INSERT INTO user (user_id, chat_id, name) VALUES (1, 1, 'a');
+                INSERT INTO user (user_id, chat_id, name) VALUES (2, 2, 'b');
+                INSERT INTO userChallenge (user_id, challenge_id) VALUES (1, 1);
+                INSERT INTO userChallenge (user_id, challenge_id) VALUES (1, 1);
+                INSERT INTO userChallenge (user_id, challenge_id) VALUES (3, 1);
+                INSERT INTO userChallenge (user_id, challenge_id) VALUES (2, 2);
+                INSERT INTO task (challenge_id, user_id, name, count, period) VALUES (1, 1, 'gym', 1, 'w');
+                INSERT INTO task (challenge_id, user_id, name, count, period) VALUES (1, 1, 'gym', 2, 'w');
+                INSERT INTO task (challenge_id, user_id, name, count, period) VALUES (1, 2, 'gym', 1, 'w');
+                INSERT INTO task (challenge_id, user_id, name, count, period) VALUES (2, 1, 'read', 1, 'w');",
+            )
+            .unwrap();
+        migrate(&mut connection).unwrap();
+        assert_eq!(count(&connection, "user"), 2);
+        assert_eq!(count(&connection, "userChallenge"), 1);
+        assert_eq!(count(&connection, "task"), 2);
+        let gym_count: i32 = connection
+            .query_row(
+                "SELECT count FROM task WHERE user_id = 1 AND name = 'gym'",
+                params![],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(gym_count, 1);
+        assert!(connection
+            .execute(
+                "INSERT INTO user (user_id, chat_id, name) VALUES (1, 1, 'a')",
+                params![]
+            )
+            .is_err());
+        assert!(connection
+            .execute(
+                "INSERT INTO userChallenge (user_id, challenge_id) VALUES (2, 5)",
+                params![]
+            )
+            .is_err());
+    }
+
+    #[test]
+    fn map_check_ins_onto_task_ids() {
+        let mut connection = Connection::open_in_memory().unwrap();
+        migrate_to(&mut connection, 2).unwrap();
+        connection
+            .execute_batch(
+                "INSERT INTO challenge (id, name, time_start, time_end) VALUES (1, 'c', '1970-01-01', '1970-01-31');
+                INSERT INTO challenge (id, name, time_start, time_end) VALUES (2, 'd', '1970-01-01', '1970-01-31');
+                INSERT INTO user (user_id, chat_id, name) VALUES (1, 1, 'a');
+                INSERT INTO user (user_id, chat_id, name) VALUES (2, 2, 'b');
+                INSERT INTO task (id, challenge_id, user_id, name, count, period) VALUES (1, 1, 1, 'gym', 1, 'w');
+                INSERT INTO task (id, challenge_id, user_id, name, count, period) VALUES (2, 2, 1, 'gym', 1, 'w');
+                INSERT INTO task (id, challenge_id, user_id, name, count, period) VALUES (3, 1, 2, 'gym', 1, 'w');
+                INSERT INTO userPollDate (date, user_id, poll_id, task_id, task_index, done) VALUES ('1970-01-01', 1, 'p1', 'gym', 0, 1);
+                INSERT INTO userPollDate (date, user_id, poll_id, task_id, task_index, done) VALUES ('1970-01-01', 2, 'p2', 'gym', 0, 0);
+                INSERT INTO userPollDate (date, user_id, poll_id, task_id, task_index, done) VALUES ('1970-01-01', 2, 'p2', 'deleted', 1, 1);",
+            )
+            .unwrap();
+        migrate(&mut connection).unwrap();
+        let rows: Vec<(i32, i64, bool)> = connection
+            .prepare("SELECT user_id, task_id, done FROM userPollDate ORDER BY id")
+            .unwrap()
+            .query_map(params![], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)))
+            .unwrap()
+            .collect::<rusqlite::Result<_>>()
+            .unwrap();
+        assert_eq!(rows, vec![(1, 1, true), (1, 2, true), (2, 3, false)]);
+    }
+
+    #[test]
+    fn move_send_dates_into_deliveries() {
+        let mut connection = Connection::open_in_memory().unwrap();
+        migrate_to(&mut connection, 13).unwrap();
+        connection
+            .execute_batch(
+                "INSERT INTO challenge (id, name, time_start, time_end) VALUES (1, 'c', '1970-01-01', '1970-01-31');
+                INSERT INTO user (user_id, chat_id, name) VALUES (1, 10, 'a');
+                INSERT INTO user (user_id, chat_id, name) VALUES (2, 20, 'b');
+                INSERT INTO userChallenge (user_id, challenge_id) VALUES (1, 1);
+                INSERT INTO pollSendDate (date) VALUES ('1970-01-01');
+                INSERT INTO pollSendDate (date, user_id) VALUES ('1970-01-02', 2);
+                INSERT INTO challengeUpdateSendDate (date, challenge_id) VALUES ('1970-01-02', 1);",
+            )
+            .unwrap();
+        migrate(&mut connection).unwrap();
+        let rows: Vec<(String, i64, i64, String, bool)> = connection
+            .prepare("SELECT kind, subject_id, chat_id, date, delivered FROM delivery ORDER BY kind, date, chat_id")
+            .unwrap()
+            .query_map(params![], |row| {
+                Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?, row.get(4)?))
+            })
+            .unwrap()
+            .collect::<rusqlite::Result<_>>()
+            .unwrap();
+        assert_eq!(
+            rows,
+            vec![
+                ("poll".into(), 1, 10, "1970-01-01".into(), true),
+                ("poll".into(), 2, 20, "1970-01-01".into(), true),
+                ("poll".into(), 2, 20, "1970-01-02".into(), true),
+                ("update".into(), 1, 10, "1970-01-02".into(), true),
+            ]
+        );
+    }
+}
@@ -0,0 +1,46 @@
+CREATE TABLE IF NOT EXISTS "challenge" (
+	"id"	INTEGER,
+	"name"	TEXT,
+	"time_start"	DATETIME,
+	"time_end"	DATETIME,
+	PRIMARY KEY("id" AUTOINCREMENT)
+);
+CREATE TABLE IF NOT EXISTS "userChallenge" (
+	"user_id"	INTEGER,
+	"challenge_id"	INTEGER
+);
+CREATE TABLE IF NOT EXISTS "task" (
+	"id"	INTEGER,
+	"challenge_id"	INTEGER,
+	"user_id"	INTEGER,
+	"name"	TEXT,
+	"count"	INTEGER,
+	"period"	TEXT,
+	PRIMARY KEY("id" AUTOINCREMENT)
+);
+CREATE TABLE IF NOT EXISTS "userPollDate" (
+	"id"	INTEGER,
+	"date"	DATETIME,
+	"user_id"	INTEGER,
+	"poll_id"	TEXT,
+	"task_id"	TEXT,
+	"task_index"	INTEGER,
+	"done"	INTEGER DEFAULT 0,
+	PRIMARY KEY("id" AUTOINCREMENT)
+);
+CREATE TABLE IF NOT EXISTS "pollSendDate" (
+	"id"	INTEGER,
+	"date"	DATETIME,
+	PRIMARY KEY("id" AUTOINCREMENT)
+);
+CREATE TABLE IF NOT EXISTS "challengeUpdateSendDate" (
+	"id"	INTEGER,
+	"date"	DATETIME
+);
+CREATE TABLE IF NOT EXISTS "user" (
+	"id"	INTEGER,
+	"user_id"	INTEGER,
+	"chat_id"	INTEGER,
+	"name"	TEXT,
+	PRIMARY KEY("id" AUTOINCREMENT)
+);
@@ -0,0 +1,43 @@
+-- Keep the first signup of every telegram user and drop the duplicates
+CREATE TABLE "user_new" (
+	"id"	INTEGER,
+	"user_id"	INTEGER NOT NULL UNIQUE,
+	"chat_id"	INTEGER NOT NULL,
+	"name"	TEXT,
+	PRIMARY KEY("id" AUTOINCREMENT)
+);
+INSERT INTO user_new (id, user_id, chat_id, name)
+	SELECT id, user_id, chat_id, name FROM user
+	WHERE id IN (SELECT MIN(id) FROM user WHERE user_id IS NOT NULL AND chat_id IS NOT NULL GROUP BY user_id);
+DROP TABLE user;
+ALTER TABLE user_new RENAME TO user;
+
+-- One subscription per user and challenge, only for users and challenges that exist
+CREATE TABLE "userChallenge_new" (
+	"user_id"	INTEGER NOT NULL REFERENCES user(user_id),
+	"challenge_id"	INTEGER NOT NULL REFERENCES challenge(id),
+	PRIMARY KEY("user_id", "challenge_id")
+);
+INSERT INTO userChallenge_new (user_id, challenge_id)
+	SELECT DISTINCT user_id, challenge_id FROM userChallenge
+	WHERE user_id IN (SELECT user_id FROM user) AND challenge_id IN (SELECT id FROM challenge);
+DROP TABLE userChallenge;
+ALTER TABLE userChallenge_new RENAME TO userChallenge;
+
+-- Task names are unique per user and challenge. Of duplicated tasks the oldest one is kept.
+CREATE TABLE "task_new" (
+	"id"	INTEGER,
+	"challenge_id"	INTEGER NOT NULL REFERENCES challenge(id),
+	"user_id"	INTEGER NOT NULL REFERENCES user(user_id),
+	"name"	TEXT NOT NULL,
+	"count"	INTEGER,
+	"period"	TEXT,
+	PRIMARY KEY("id" AUTOINCREMENT),
+	UNIQUE("user_id", "challenge_id", "name")
+);
+INSERT INTO task_new (id, challenge_id, user_id, name, count, period)
+	SELECT id, challenge_id, user_id, name, count, period FROM task
+	WHERE id IN (SELECT MIN(id) FROM task WHERE name IS NOT NULL GROUP BY user_id, challenge_id, name)
+	AND user_id IN (SELECT user_id FROM user) AND challenge_id IN (SELECT id FROM challenge);
+DROP TABLE task;
+ALTER TABLE task_new RENAME TO task;
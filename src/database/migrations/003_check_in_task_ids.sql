@@ -0,0 +1,25 @@
+-- Check-ins used to store the task name in task_id, which mixed up the check-ins
+-- of all tasks sharing a name. They now reference task.id.
+CREATE TABLE "userPollDate_new" (
+	"id"	INTEGER,
+	"date"	DATETIME NOT NULL,
+	"user_id"	INTEGER NOT NULL REFERENCES user(user_id),
+	"poll_id"	TEXT NOT NULL,
+	"task_id"	INTEGER NOT NULL REFERENCES task(id),
+	"task_index"	INTEGER NOT NULL,
+	"done"	INTEGER NOT NULL DEFAULT 0,
+	PRIMARY KEY("id" AUTOINCREMENT)
+);
+-- An old check-in is mapped onto every task of the same user with that name,
+-- since that is what the poll option stood for. Tasks in other challenges only
+-- count check-ins within their own challenge, so this does not inflate any scores.
+-- Check-ins whose task does not exist anymore are dropped.
+INSERT INTO userPollDate_new (date, user_id, poll_id, task_id, task_index, done)
+	SELECT userPollDate.date, userPollDate.user_id, userPollDate.poll_id, task.id, userPollDate.task_index, COALESCE(userPollDate.done, 0)
+	FROM userPollDate JOIN task ON task.user_id = userPollDate.user_id AND task.name = userPollDate.task_id
+	WHERE userPollDate.date IS NOT NULL AND userPollDate.poll_id IS NOT NULL AND userPollDate.task_index IS NOT NULL
+	ORDER BY userPollDate.id, task.id;
+DROP TABLE userPollDate;
+ALTER TABLE userPollDate_new RENAME TO userPollDate;
+CREATE INDEX userPollDate_poll_id ON userPollDate (poll_id);
+CREATE INDEX userPollDate_task_id ON userPollDate (task_id, user_id);
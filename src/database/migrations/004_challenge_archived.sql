@@ -0,0 +1,2 @@
+-- Finished challenges can be archived by their participants.
+ALTER TABLE challenge ADD COLUMN archived INTEGER NOT NULL DEFAULT 0;
@@ -0,0 +1,12 @@
+-- Every action performed by the bot, so that the state can be rebuilt by replaying them.
+-- The action is stored as json.
+CREATE TABLE "actionLog" (
+	"id"	INTEGER,
+	"timestamp"	DATETIME NOT NULL,
+	"user_id"	INTEGER,
+	"chat_id"	INTEGER,
+	"action"	TEXT NOT NULL,
+	"response_kind"	TEXT NOT NULL,
+	PRIMARY KEY("id" AUTOINCREMENT)
+);
+CREATE INDEX "actionLog_user_id" ON "actionLog" ("user_id");
@@ -0,0 +1,4 @@
+-- Quantitative tasks have a unit, their count is the target amount per period.
+-- The amount of a check-in is asked for after the poll has been answered.
+ALTER TABLE task ADD COLUMN unit TEXT;
+ALTER TABLE userPollDate ADD COLUMN amount REAL;
@@ -0,0 +1,2 @@
+-- Tasks can be things to avoid, in which case the count is the number of allowed slips.
+ALTER TABLE task ADD COLUMN kind TEXT NOT NULL DEFAULT 'positive';
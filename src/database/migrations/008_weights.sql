@@ -0,0 +1,5 @@
+-- Tasks can count more or less than others in the overall percentage of a user.
+-- The creator of a challenge owns it and decides whether weights are allowed in it.
+ALTER TABLE task ADD COLUMN weight REAL NOT NULL DEFAULT 1;
+ALTER TABLE challenge ADD COLUMN owner_id INTEGER;
+ALTER TABLE challenge ADD COLUMN weights_allowed INTEGER NOT NULL DEFAULT 1;
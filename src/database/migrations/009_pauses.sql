@@ -0,0 +1,10 @@
+-- Users can pause for a while, e.g. during holidays or when they are sick.
+-- A pause without an end lasts until the user resumes.
+CREATE TABLE "pause" (
+	"id"	INTEGER,
+	"user_id"	INTEGER NOT NULL REFERENCES user(user_id),
+	"start_date"	DATE NOT NULL,
+	"end_date"	DATE,
+	PRIMARY KEY("id" AUTOINCREMENT)
+);
+CREATE INDEX "pause_user_id" ON "pause" ("user_id");
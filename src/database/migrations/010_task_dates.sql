@@ -0,0 +1,5 @@
+-- Tasks are only scored from the day they were added until the day they were retired.
+-- Existing tasks count from the start of their challenge.
+ALTER TABLE task ADD COLUMN active_from DATE;
+UPDATE task SET active_from = (SELECT time_start FROM challenge WHERE challenge.id = task.challenge_id);
+ALTER TABLE task ADD COLUMN active_until DATE;
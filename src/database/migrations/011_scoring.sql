@@ -0,0 +1,2 @@
+-- The scoring strategy of a challenge, see the Scoring enum.
+ALTER TABLE challenge ADD COLUMN scoring TEXT NOT NULL DEFAULT 'default';
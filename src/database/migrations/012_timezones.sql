@@ -0,0 +1,6 @@
+-- The IANA timezone of a user, such as Europe/Berlin. Without one, the time of the server is used.
+ALTER TABLE user ADD COLUMN timezone TEXT;
+-- Polls and updates are sent at the local time of each user and challenge owner,
+-- so whether they went out already is tracked per user and per challenge.
+ALTER TABLE pollSendDate ADD COLUMN user_id INTEGER;
+ALTER TABLE challengeUpdateSendDate ADD COLUMN challenge_id INTEGER;
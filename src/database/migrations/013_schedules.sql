@@ -0,0 +1,4 @@
+-- The time at which a user gets their poll. Without one, the poll comes at the usual time.
+ALTER TABLE user ADD COLUMN poll_time TEXT;
+-- When the update of a challenge is sent, see the UpdateSchedule enum. Without one, it is sent daily at the usual time.
+ALTER TABLE challenge ADD COLUMN update_schedule TEXT;
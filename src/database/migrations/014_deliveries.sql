@@ -0,0 +1,29 @@
+-- Polls and updates are tracked per chat and day, so that a failed send can be retried
+-- without sending the same message to everyone else twice.
+CREATE TABLE "delivery" (
+	"id"	INTEGER,
+	"kind"	TEXT NOT NULL,
+	"subject_id"	INTEGER NOT NULL,
+	"chat_id"	INTEGER NOT NULL,
+	"date"	DATE NOT NULL,
+	"attempts"	INTEGER NOT NULL DEFAULT 0,
+	"next_attempt"	DATETIME NOT NULL,
+	"delivered"	BOOL NOT NULL DEFAULT 0,
+	PRIMARY KEY("id" AUTOINCREMENT),
+	UNIQUE("kind", "subject_id", "chat_id", "date")
+);
+CREATE INDEX "delivery_chat_id" ON "delivery" ("chat_id");
+-- Users whose chat keeps refusing messages get none until they resume.
+ALTER TABLE user ADD COLUMN inactive BOOL NOT NULL DEFAULT 0;
+-- Send dates from before timezones have no user or challenge and count for everyone.
+INSERT OR IGNORE INTO delivery (kind, subject_id, chat_id, date, attempts, next_attempt, delivered)
+	SELECT 'poll', user.user_id, user.chat_id, pollSendDate.date, 1, datetime(pollSendDate.date), 1
+	FROM pollSendDate, user
+	WHERE pollSendDate.user_id IS NULL OR pollSendDate.user_id = user.user_id;
+INSERT OR IGNORE INTO delivery (kind, subject_id, chat_id, date, attempts, next_attempt, delivered)
+	SELECT 'update', userChallenge.challenge_id, user.chat_id, challengeUpdateSendDate.date, 1, datetime(challengeUpdateSendDate.date), 1
+	FROM challengeUpdateSendDate, userChallenge, user
+	WHERE user.user_id = userChallenge.user_id
+	AND (challengeUpdateSendDate.challenge_id IS NULL OR challengeUpdateSendDate.challenge_id = userChallenge.challenge_id);
+DROP TABLE pollSendDate;
+DROP TABLE challengeUpdateSendDate;
@@ -0,0 +1,4 @@
+-- The timestamps of the action log are the wall clock time of the server. Its offset from UTC
+-- is needed as well to replay actions in the timezones of the users at the right time.
+-- Entries from before are taken to be logged on UTC.
+ALTER TABLE actionLog ADD COLUMN utc_offset INTEGER NOT NULL DEFAULT 0;
@@ -1,52 +1,96 @@
+pub mod action_log_entry;
+pub mod backup;
 pub mod challenge;
 pub mod challenge_data;
 pub mod challenge_performance_data;
+pub mod challenge_state;
+pub mod check_in;
+pub mod constraint_violation;
+pub mod delivery;
+pub mod delivery_kind;
+pub mod delivery_status;
+pub mod memory;
+pub mod migrations;
+pub mod pause;
+pub mod pending_amount;
 pub mod period;
+pub mod scoring;
+pub mod storage;
 pub mod task;
 pub mod task_data;
+pub mod task_kind;
 pub mod task_performance_data;
+pub mod update_schedule;
 pub mod user;
+pub mod user_export;
 pub mod user_performance_data;
+pub mod user_task;
 
 use anyhow::{anyhow, Context, Result};
-use chrono::{Local, NaiveDate, NaiveTime};
-use itertools::Itertools;
+use chrono::{FixedOffset, NaiveDate, NaiveDateTime, NaiveTime};
+use chrono_tz::Tz;
 use rusqlite::{params, Connection};
 use std::path::Path;
 use std::str::FromStr;
+use std::sync::Arc;
 
 use crate::{
-    action::UserPollDateInfo,
+    action::{Action, ActionOrigin, UserPollDateInfo},
+    clock::{Clock, SystemClock},
     config,
-    response::{ChallengeUpdateData, PollData, UserTaskData},
     time_frame::TimeFrame,
 };
 
+use self::{action_log_entry::ActionLogEntry, constraint_violation::ConstraintViolation};
 use self::{
-    challenge::Challenge, challenge_performance_data::ChallengePerformanceData,
-    task_data::TaskData, task_performance_data::TaskPerformanceData, user::UserData,
-    user_performance_data::UserPerformanceData,
+    challenge::Challenge,
+    check_in::CheckIn,
+    delivery::Delivery,
+    delivery_status::DeliveryStatus,
+    pause::Pause,
+    pending_amount::PendingAmount,
+    scoring::Scoring,
+    task_data::TaskData,
+    task_kind::TaskKind,
+    update_schedule::UpdateSchedule,
+    user::{User, UserData},
+    user_task::UserTask,
 };
-use self::{challenge_data::ChallengeData, period::Period, task::Task};
+use self::{challenge_data::ChallengeData, period::Period, storage::Storage, task::Task};
 
+/// The sqlite implementation of [Storage].
 pub struct Database {
     connection: Connection,
+    clock: Arc<dyn Clock>,
 }
 
 impl Database {
-    pub fn new(path: &Path) -> Database {
-        Database {
-            connection: Connection::open(&path).unwrap(),
-        }
+    pub fn new(path: &Path) -> Result<Database> {
+        Database::with_clock(path, Arc::new(SystemClock))
+    }
+
+    pub fn with_clock(path: &Path, clock: Arc<dyn Clock>) -> Result<Database> {
+        let mut connection = Connection::open(path)
+            .with_context(|| format!("While opening database at {}", path.display()))?;
+        migrations::migrate(&mut connection)?;
+        Ok(Database { connection, clock })
     }
+}
 
-    pub fn add_challenge(&self, challenge: &ChallengeData) -> Result<Challenge> {
+impl Storage for Database {
+    fn clock(&self) -> &dyn Clock {
+        &*self.clock
+    }
+
+    fn add_challenge(&self, challenge: &ChallengeData) -> Result<Challenge> {
         self.connection.execute(
-            "INSERT INTO challenge (name, time_start, time_end) VALUES (?1, ?2, ?3)",
+            "INSERT INTO challenge (name, time_start, time_end, owner_id, scoring) VALUES (?1, ?2, ?3, ?4, ?5)",
             params![
                 challenge.name,
                 challenge.time_frame.start,
-                challenge.time_frame.end
+                challenge.time_frame.end,
+                challenge.owner_id,
+                challenge.scoring.to_string(),
             ],
         )?;
 
@@ -55,17 +99,20 @@ impl Database {
             .connection
             .prepare("SELECT id FROM challenge WHERE rowid = ?1")?;
         let challenge_id = statement
-            .query_map(params![id], |row| Ok(row.get(0)?))?
+            .query_map(params![id], |row| row.get(0))?
             .next()
             .unwrap()?;
 
         Ok(Challenge {
             id: challenge_id,
             data: challenge.clone(),
+            archived: false,
+            weights_allowed: true,
+            update_schedule: UpdateSchedule::default(),
         })
     }
 
-    pub fn check_user_subscribed_to_challenge(
+    fn check_user_subscribed_to_challenge(
         &self,
         user_id: &i32,
         challenge_id: &i32,
@@ -78,141 +125,121 @@ impl Database {
             .context("")
     }
 
-    pub fn check_user_signed_up(&self, user_id: &i32) -> Result<bool> {
+    fn check_user_signed_up(&self, user_id: &i32) -> Result<bool> {
         let mut statement = self
             .connection
             .prepare("SELECT user_id FROM user WHERE user_id = ?1")?;
         statement.exists(params![user_id,]).context("")
     }
 
-    pub fn get_challenge_id_from_name(&self, _challenge_name: &str) -> Result<i32> {
-        todo!()
-    }
-
-    pub fn subscribe_to_challenge(&self, user_id: &i32, challenge_id: &i32) -> Result<bool> {
-        let user_already_signed_up = self.check_user_signed_up(user_id)?;
-        if !user_already_signed_up {
-            return Err(anyhow!(
-                "You have not signed up yet. Send a /signup to @deshittify_bot privately"
-            ));
-        }
-        let user_already_subscribed =
-            self.check_user_subscribed_to_challenge(user_id, challenge_id)?;
-        if !user_already_subscribed {
-            self.connection.execute(
+    fn insert_subscription(&self, user_id: &i32, challenge_id: &i32) -> Result<()> {
+        self.connection
+            .execute(
                 "INSERT INTO userChallenge (user_id, challenge_id) VALUES (?1, ?2)",
                 params![user_id, challenge_id,],
-            )?;
-        }
-        Ok(user_already_subscribed)
+            )
+            .map_err(convert_error)?;
+        Ok(())
     }
 
-    pub fn signup_user(&self, user_id: &i32, chat_id: &i64, user_name: &str) -> Result<()> {
+    fn signup_user(&self, user_id: &i32, chat_id: &i64, user_name: &str) -> Result<()> {
         self.connection
             .execute(
                 "INSERT INTO user (user_id, chat_id, name) VALUES (?1, ?2, ?3)",
                 params![user_id, chat_id, user_name,],
             )
+            .map_err(convert_error)
             .context("While inserting user into table")
             .map(|_| ())
     }
 
-    pub fn add_task(
-        &self,
-        user_id: &i32,
-        challenge_name: &str,
-        task_data: &TaskData,
-    ) -> Result<()> {
-        let challenge_id = self.get_challenge_id_by_user_id_and_name(user_id, challenge_name)?;
-        self.connection.execute(
-            "INSERT INTO task (user_id, challenge_id, name, count, period) VALUES (?1, ?2, ?3, ?4, ?5)",
-            params![
-                user_id,
-                challenge_id,
-                task_data.name,
-                task_data.count,
-                task_data.period.to_string(),
-            ],
-        )?;
-        Ok(())
-    }
-
-    fn get_challenge_id_by_user_id_and_name(
+    fn get_active_challenge_id_by_user_id_and_name(
         &self,
         user_id: &i32,
         challenge_name: &str,
+        date: NaiveDate,
     ) -> Result<i32> {
-        let today = Local::today().to_string();
         let mut statement = self
             .connection
-            .prepare("SELECT challenge.id FROM challenge, userChallenge WHERE challenge.id = userChallenge.challenge_id AND userChallenge.user_id = ?1 AND challenge.name = ?2 AND challenge.time_end > ?3")?;
+            .prepare("SELECT challenge.id FROM challenge, userChallenge WHERE challenge.id = userChallenge.challenge_id AND userChallenge.user_id = ?1 AND challenge.name = ?2 AND challenge.time_end >= ?3")?;
         let challenge_id = statement
-            .query_map(params![user_id, challenge_name, today], |row| {
-                Ok(row.get(0)?)
-            })?
+            .query_map(params![user_id, challenge_name, date], |row| row.get(0))?
             .next()
-            .ok_or(anyhow!(
-                "No (active) challenge with this name found for this user"
-            ))??;
+            .ok_or_else(|| anyhow!("No (active) challenge with this name found for this user"))??;
         Ok(challenge_id)
     }
 
-    pub fn check_date_and_get_all_user_tasks(&self) -> Result<UserTaskData> {
-        if self.poll_already_sent_today()? || self.too_early(config::HOUR_TO_SEND_POLL_AT) {
-            return Ok(UserTaskData { data: vec![] });
-        }
-        self.write_poll_send_date()?;
-        self.get_user_tasks()
-    }
-
-    pub fn check_date_and_get_challenge_update_data(&self) -> Result<ChallengeUpdateData> {
-        if self.challenge_update_already_sent_today()?
-            || self.too_early(config::HOUR_TO_SEND_UPDATE_AT)
-        {
-            return Ok(ChallengeUpdateData(vec![]));
-        }
-        self.write_challenge_update_send_date()?;
-        self.get_challenge_update_data()
-    }
-
-    pub fn too_early(&self, hour: u32) -> bool {
-        let datetime_now = Local::now().naive_local();
-        let datetime_to_send_at = Local::today()
-            .naive_local()
-            .and_time(NaiveTime::from_hms(hour, 0, 0));
-        datetime_now < datetime_to_send_at
+    fn insert_task(
+        &self,
+        user_id: &i32,
+        challenge_id: &i32,
+        task_data: &TaskData,
+        active_from: NaiveDate,
+    ) -> Result<()> {
+        self.connection
+            .execute(
+                "INSERT INTO task (user_id, challenge_id, name, count, period, unit, kind, weight, active_from) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)",
+                params![
+                    user_id,
+                    challenge_id,
+                    task_data.name,
+                    task_data.count,
+                    task_data.period.to_string(),
+                    task_data.unit,
+                    task_data.kind.to_string(),
+                    task_data.weight,
+                    active_from,
+                ],
+            )
+            .map_err(convert_error)?;
+        Ok(())
     }
 
-    pub fn get_user_tasks(&self) -> Result<UserTaskData> {
+    fn get_all_user_tasks(&self) -> Result<Vec<UserTask>> {
         let mut statement = self.connection.prepare(
-            "SELECT user.user_id, user.chat_id, task.name FROM user, task WHERE user.user_id = task.user_id GROUP BY user.chat_id, task.name ORDER BY user.chat_id, task.name",
+            "SELECT user.user_id, user.chat_id, challenge.id, challenge.name, challenge.time_start, challenge.time_end, challenge.archived, task.id, task.name, task.count, task.period, task.unit, task.kind, challenge.owner_id, challenge.weights_allowed, task.weight, task.active_from, task.active_until, challenge.scoring, user.timezone, challenge.update_schedule FROM user, task, challenge WHERE user.user_id = task.user_id AND challenge.id = task.challenge_id AND NOT user.inactive ORDER BY user.chat_id, task.name, task.id",
         )?;
-        let mb_chat_ids_with_task_names = statement.query_map(params![], |row| {
-            Ok((
-                row.get::<_, i32>(0)?,
-                row.get::<_, i64>(1)?,
-                row.get::<_, String>(2)?,
-            ))
+        let result = statement.query_map(params![], |row| {
+            Ok(UserTask {
+                user_id: row.get::<_, i32>(0)?,
+                chat_id: row.get::<_, i64>(1)?,
+                timezone: parse_timezone(row.get::<_, Option<String>>(19)?),
+                challenge: Challenge {
+                    id: row.get::<_, i64>(2)?,
+                    data: ChallengeData {
+                        name: row.get::<_, String>(3)?,
+                        time_frame: TimeFrame {
+                            start: row.get::<_, NaiveDate>(4)?,
+                            end: row.get::<_, NaiveDate>(5)?,
+                        },
+                        owner_id: row.get::<_, Option<i32>>(13)?,
+                        scoring: Scoring::from_str(&row.get::<_, String>(18)?).unwrap(),
+                    },
+                    archived: row.get::<_, bool>(6)?,
+                    weights_allowed: row.get::<_, bool>(14)?,
+                    update_schedule: parse_update_schedule(row.get::<_, Option<String>>(20)?),
+                },
+                task: Task {
+                    id: row.get::<_, i64>(7)?,
+                    data: TaskData {
+                        name: row.get::<_, String>(8)?,
+                        count: row.get::<_, i32>(9)?,
+                        period: Period::from_str(&row.get::<_, String>(10)?).unwrap(),
+                        unit: row.get::<_, Option<String>>(11)?,
+                        kind: TaskKind::from_str(&row.get::<_, String>(12)?).unwrap(),
+                        weight: row.get::<_, f64>(15)?,
+                    },
+                    active_from: row.get::<_, NaiveDate>(16)?,
+                    active_until: row.get::<_, Option<NaiveDate>>(17)?,
+                },
+            })
         })?;
-        let chat_ids_with_task_names: Vec<(i32, i64, String)> =
-            mb_chat_ids_with_task_names.collect::<rusqlite::Result<Vec<(i32, i64, String)>>>()?;
-        let mut data_grouped = UserTaskData { data: vec![] };
-        for (key, group) in &chat_ids_with_task_names
-            .into_iter()
-            .group_by(|(user_id, chat_id, _)| (*user_id, *chat_id))
-        {
-            data_grouped.data.push(PollData {
-                chat_id: key.1,
-                task_names: group.map(|(_, _, name)| name).collect(),
-                user_id: key.0,
-            });
-        }
-        Ok(data_grouped)
+        result.collect::<rusqlite::Result<_>>().context("")
     }
 
-    pub fn get_challenges_and_chat_ids(&self) -> rusqlite::Result<Vec<(Challenge, i64)>> {
+    fn get_challenges_and_chat_ids(&self) -> Result<Vec<(Challenge, i64)>> {
         let mut statement = self.connection.prepare(
-            "SELECT challenge.id, challenge.name, challenge.time_start, challenge.time_end, user.chat_id FROM challenge, user, userChallenge WHERE user.user_id = userChallenge.user_id AND challenge.id = userChallenge.challenge_id",
+            "SELECT challenge.id, challenge.name, challenge.time_start, challenge.time_end, challenge.archived, user.chat_id, challenge.owner_id, challenge.weights_allowed, challenge.scoring, challenge.update_schedule FROM challenge, user, userChallenge WHERE user.user_id = userChallenge.user_id AND challenge.id = userChallenge.challenge_id AND NOT user.inactive",
         )?;
         let challenges_result = statement.query_map(params![], |row| {
             Ok((
@@ -224,22 +251,29 @@ impl Database {
                             start: row.get::<_, NaiveDate>(2)?,
                             end: row.get::<_, NaiveDate>(3)?,
                         },
+                        owner_id: row.get::<_, Option<i32>>(6)?,
+                        scoring: Scoring::from_str(&row.get::<_, String>(8)?).unwrap(),
                     },
+                    archived: row.get::<_, bool>(4)?,
+                    weights_allowed: row.get::<_, bool>(7)?,
+                    update_schedule: parse_update_schedule(row.get::<_, Option<String>>(9)?),
                 },
-                row.get::<_, i64>(4)?,
+                row.get::<_, i64>(5)?,
             ))
         })?;
-        challenges_result.collect()
+        challenges_result
+            .collect::<rusqlite::Result<_>>()
+            .context("")
     }
 
-    pub fn get_tasks_for_challenge_and_user(
+    fn get_tasks_for_challenge_and_user(
         &self,
         challenge_id: i64,
         user_id: i64,
-    ) -> rusqlite::Result<Vec<Task>> {
+    ) -> Result<Vec<Task>> {
         let mut statement = self
             .connection
-            .prepare("SELECT task.id, task.name, task.count, task.period FROM task WHERE task.challenge_id = ?1 AND task.user_id = ?2")?;
+            .prepare("SELECT task.id, task.name, task.count, task.period, task.unit, task.kind, task.weight, task.active_from, task.active_until FROM task WHERE task.challenge_id = ?1 AND task.user_id = ?2")?;
         let result = statement.query_map(params![challenge_id, user_id], |row| {
             Ok(Task {
                 id: row.get::<_, i64>(0)?,
@@ -247,13 +281,18 @@ impl Database {
                     name: row.get::<_, String>(1)?,
                     count: row.get::<_, i32>(2)?,
                     period: Period::from_str(&row.get::<_, String>(3)?).unwrap(),
+                    unit: row.get::<_, Option<String>>(4)?,
+                    kind: TaskKind::from_str(&row.get::<_, String>(5)?).unwrap(),
+                    weight: row.get::<_, f64>(6)?,
                 },
+                active_from: row.get::<_, NaiveDate>(7)?,
+                active_until: row.get::<_, Option<NaiveDate>>(8)?,
             })
         })?;
-        result.collect()
+        result.collect::<rusqlite::Result<_>>().context("")
     }
 
-    pub fn get_challenge_users(&self, challenge_id: i64) -> rusqlite::Result<Vec<UserData>> {
+    fn get_challenge_users(&self, challenge_id: i64) -> Result<Vec<UserData>> {
         let mut statement = self
             .connection
             .prepare("SELECT user.user_id, user.name FROM user, userChallenge WHERE userChallenge.user_id = user.user_id AND userChallenge.challenge_id = ?1")?;
@@ -263,91 +302,160 @@ impl Database {
                 name: row.get::<_, String>(1)?,
             })
         })?;
-        result.collect()
+        result.collect::<rusqlite::Result<_>>().context("")
     }
 
-    pub fn get_timestamps_for_task(&self, task_name: &str) -> rusqlite::Result<Vec<NaiveDate>> {
+    fn get_timestamps_for_task(&self, task_id: i64, user_id: i64) -> Result<Vec<NaiveDate>> {
         let mut statement = self
             .connection
-            .prepare("SELECT userPollDate.date FROM userPollDate WHERE userPollDate.done = 1 AND userPollDate.task_id = ?1")?;
+            .prepare("SELECT userPollDate.date FROM userPollDate WHERE userPollDate.done = 1 AND userPollDate.task_id = ?1 AND userPollDate.user_id = ?2")?;
         let result =
-            statement.query_map(params![task_name], |row| Ok(row.get::<_, NaiveDate>(0)?))?;
-        result.collect()
+            statement.query_map(params![task_id, user_id], |row| row.get::<_, NaiveDate>(0))?;
+        result.collect::<rusqlite::Result<_>>().context("")
     }
 
-    pub fn get_task_performance(
-        &self,
-        challenge: &Challenge,
-        user_id: i64,
-    ) -> Result<Vec<TaskPerformanceData>> {
-        let tasks = self.get_tasks_for_challenge_and_user(challenge.id, user_id)?;
-        tasks
-            .iter()
-            .map(move |task| {
-                Ok(TaskPerformanceData {
-                    task: task.data.clone(),
-                    timestamps: self.get_timestamps_for_task(&task.data.name)?,
-                })
+    fn get_amounts_for_task(&self, task_id: i64, user_id: i64) -> Result<Vec<(NaiveDate, f64)>> {
+        let mut statement = self
+            .connection
+            .prepare("SELECT date, amount FROM userPollDate WHERE done = 1 AND amount IS NOT NULL AND task_id = ?1 AND user_id = ?2")?;
+        let result = statement.query_map(params![task_id, user_id], |row| {
+            Ok((row.get::<_, NaiveDate>(0)?, row.get::<_, f64>(1)?))
+        })?;
+        result.collect::<rusqlite::Result<_>>().context("")
+    }
+
+    fn get_pending_amounts(&self, user_id: &i32) -> Result<Vec<PendingAmount>> {
+        let mut statement = self.connection.prepare(
+            "SELECT userPollDate.id, userPollDate.date, task.name, task.count, task.period, task.unit, task.kind, task.weight FROM userPollDate, task WHERE userPollDate.task_id = task.id AND userPollDate.user_id = ?1 AND userPollDate.done = 1 AND userPollDate.amount IS NULL AND task.unit IS NOT NULL ORDER BY userPollDate.date, userPollDate.id",
+        )?;
+        let result = statement.query_map(params![user_id], |row| {
+            Ok(PendingAmount {
+                check_in_id: row.get::<_, i64>(0)?,
+                date: row.get::<_, NaiveDate>(1)?,
+                task: TaskData {
+                    name: row.get::<_, String>(2)?,
+                    count: row.get::<_, i32>(3)?,
+                    period: Period::from_str(&row.get::<_, String>(4)?).unwrap(),
+                    unit: row.get::<_, Option<String>>(5)?,
+                    kind: TaskKind::from_str(&row.get::<_, String>(6)?).unwrap(),
+                    weight: row.get::<_, f64>(7)?,
+                },
             })
-            .collect()
-    }
-
-    pub fn get_challenge_update_data(&self) -> Result<ChallengeUpdateData> {
-        let challenges_and_chat_ids = self.get_challenges_and_chat_ids()?;
-        let mut challenge_performance_data = vec![];
-        for (challenge, chat_id) in challenges_and_chat_ids.iter() {
-            let mut user_performance = vec![];
-            for user in self.get_challenge_users(challenge.id)? {
-                let task_performance = self.get_task_performance(&challenge, user.user_id)?;
-                user_performance.push(UserPerformanceData {
-                    user,
-                    task_performance,
-                });
-            }
-            challenge_performance_data.push(ChallengePerformanceData {
-                challenge: challenge.clone(),
-                chat_id: *chat_id,
-                user_performance,
-            });
-        }
-        Ok(ChallengeUpdateData(challenge_performance_data))
+        })?;
+        result.collect::<rusqlite::Result<_>>().context("")
     }
 
-    pub fn write_poll_send_date(&self) -> Result<()> {
-        let date_today = Local::today().naive_local();
+    fn set_check_in_amount(&self, check_in_id: i64, amount: f64) -> Result<()> {
         self.connection.execute(
-            "INSERT INTO pollSendDate (date) VALUES (?1)",
-            params![date_today],
+            "UPDATE userPollDate SET amount = ?1 WHERE id = ?2",
+            params![amount, check_in_id],
         )?;
         Ok(())
     }
 
-    pub fn write_challenge_update_send_date(&self) -> Result<()> {
-        let date_today = Local::today().naive_local();
+    fn get_delivery_status(&self, delivery: &Delivery) -> Result<Option<DeliveryStatus>> {
+        let mut statement = self.connection.prepare(
+            "SELECT attempts, next_attempt, delivered FROM delivery WHERE kind = ?1 AND subject_id = ?2 AND chat_id = ?3 AND date = ?4",
+        )?;
+        let result = statement
+            .query_map(
+                params![
+                    delivery.kind.to_string(),
+                    delivery.subject_id,
+                    delivery.chat_id,
+                    delivery.date
+                ],
+                |row| {
+                    Ok(DeliveryStatus {
+                        attempts: row.get::<_, u32>(0)?,
+                        next_attempt: row.get::<_, NaiveDateTime>(1)?,
+                        delivered: row.get::<_, bool>(2)?,
+                    })
+                },
+            )?
+            .next();
+        result.transpose().context("")
+    }
+
+    fn write_delivery_attempt(
+        &self,
+        delivery: &Delivery,
+        next_attempt: NaiveDateTime,
+    ) -> Result<()> {
         self.connection.execute(
-            "INSERT INTO challengeUpdateSendDate (date) VALUES (?1)",
-            params![date_today],
+            "INSERT INTO delivery (kind, subject_id, chat_id, date, attempts, next_attempt) VALUES (?1, ?2, ?3, ?4, 1, ?5)
+            ON CONFLICT (kind, subject_id, chat_id, date) DO UPDATE SET attempts = attempts + 1, next_attempt = ?5",
+            params![
+                delivery.kind.to_string(),
+                delivery.subject_id,
+                delivery.chat_id,
+                delivery.date,
+                next_attempt
+            ],
         )?;
         Ok(())
     }
 
-    pub fn poll_already_sent_today(&self) -> Result<bool> {
-        let date_today = Local::today().naive_local();
-        let mut statement = self
-            .connection
-            .prepare("SELECT id FROM pollSendDate WHERE date = ?1")?;
-        statement.exists(params![date_today,]).context("")
+    fn mark_delivered(&self, delivery: &Delivery) -> Result<()> {
+        self.connection.execute(
+            "UPDATE delivery SET delivered = 1 WHERE kind = ?1 AND subject_id = ?2 AND chat_id = ?3 AND date = ?4",
+            params![
+                delivery.kind.to_string(),
+                delivery.subject_id,
+                delivery.chat_id,
+                delivery.date
+            ],
+        )?;
+        Ok(())
     }
 
-    pub fn challenge_update_already_sent_today(&self) -> Result<bool> {
-        let date_today = Local::today().naive_local();
-        let mut statement = self
-            .connection
-            .prepare("SELECT id FROM challengeUpdateSendDate WHERE date = ?1")?;
-        statement.exists(params![date_today,]).context("")
+    fn give_up_delivery(&self, delivery: &Delivery) -> Result<()> {
+        self.connection.execute(
+            "UPDATE delivery SET attempts = ?5 WHERE kind = ?1 AND subject_id = ?2 AND chat_id = ?3 AND date = ?4",
+            params![
+                delivery.kind.to_string(),
+                delivery.subject_id,
+                delivery.chat_id,
+                delivery.date,
+                config::DELIVERY_ATTEMPTS
+            ],
+        )?;
+        Ok(())
+    }
+
+    fn set_chat_inactive(&self, chat_id: i64, inactive: bool) -> Result<()> {
+        self.connection.execute(
+            "UPDATE user SET inactive = ?1 WHERE chat_id = ?2",
+            params![inactive, chat_id],
+        )?;
+        Ok(())
+    }
+
+    fn set_poll_time(&self, user_id: &i32, poll_time: Option<NaiveTime>) -> Result<()> {
+        self.connection.execute(
+            "UPDATE user SET poll_time = ?1 WHERE user_id = ?2",
+            params![poll_time, user_id],
+        )?;
+        Ok(())
+    }
+
+    fn set_update_schedule(&self, challenge_id: i64, schedule: UpdateSchedule) -> Result<()> {
+        self.connection.execute(
+            "UPDATE challenge SET update_schedule = ?1 WHERE id = ?2",
+            params![schedule.to_string(), challenge_id],
+        )?;
+        Ok(())
+    }
+
+    fn set_user_timezone(&self, user_id: &i32, timezone: Option<Tz>) -> Result<()> {
+        self.connection.execute(
+            "UPDATE user SET timezone = ?1 WHERE user_id = ?2",
+            params![timezone.map(|timezone| timezone.name()), user_id],
+        )?;
+        Ok(())
     }
 
-    pub fn modify_user_task_entries(&self, poll_id: &str, option_ids: &Vec<i32>) -> Result<()> {
+    fn modify_user_task_entries(&self, poll_id: &str, option_ids: &[i32]) -> Result<()> {
         // Set everything to done=false first, because we're lazy af
         self.connection.execute(
             "UPDATE userPollDate SET done = 0 WHERE poll_id = ?1",
@@ -363,7 +471,7 @@ impl Database {
         Ok(())
     }
 
-    pub fn write_poll_info(&self, info: &Vec<UserPollDateInfo>) -> Result<()> {
+    fn write_poll_info(&self, info: &[UserPollDateInfo]) -> Result<()> {
         for user_poll_date_info in info.iter() {
             self.connection.execute(
                 "INSERT INTO userPollDate (date, user_id, poll_id, task_id, task_index, done) VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
@@ -373,17 +481,308 @@ impl Database {
         Ok(())
     }
 
-    pub fn get_date_from_poll_id(&self, poll_id: &str) -> Result<NaiveDate> {
+    fn get_sent_task_ids(&self, user_id: &i32, date: NaiveDate) -> Result<Vec<i64>> {
         let mut statement = self
             .connection
-            .prepare("SELECT date FROM pollDate WHERE poll_id = ?1")?;
+            .prepare("SELECT task_id FROM userPollDate WHERE user_id = ?1 AND date = ?2")?;
+        let result = statement.query_map(params![user_id, date], |row| row.get::<_, i64>(0))?;
+        result.collect::<rusqlite::Result<_>>().context("")
+    }
+
+    fn get_date_from_poll_id(&self, poll_id: &str) -> Result<NaiveDate> {
+        let mut statement = self
+            .connection
+            .prepare("SELECT date FROM userPollDate WHERE poll_id = ?1")?;
         let result: rusqlite::Result<NaiveDate> = statement
             .query_map(params![poll_id,], |row| row.get::<_, NaiveDate>(0))?
             .next()
-            .ok_or(anyhow!(
-                "The poll with id {} is not in the userPoll database",
-                poll_id
-            ))?;
+            .ok_or_else(|| {
+                anyhow!(
+                    "The poll with id {} is not in the userPoll database",
+                    poll_id
+                )
+            })?;
+        result.context("")
+    }
+
+    fn get_user_id_from_poll_id(&self, poll_id: &str) -> Result<i32> {
+        let mut statement = self
+            .connection
+            .prepare("SELECT user_id FROM userPollDate WHERE poll_id = ?1")?;
+        let result: rusqlite::Result<i32> = statement
+            .query_map(params![poll_id,], |row| row.get::<_, i32>(0))?
+            .next()
+            .ok_or_else(|| {
+                anyhow!(
+                    "The poll with id {} is not in the userPoll database",
+                    poll_id
+                )
+            })?;
         result.context("")
     }
+
+    fn get_user(&self, user_id: &i32) -> Result<Option<User>> {
+        let mut statement = self.connection.prepare(
+            "SELECT user_id, chat_id, name, timezone, poll_time, inactive FROM user WHERE user_id = ?1",
+        )?;
+        let result = statement
+            .query_map(params![user_id], |row| {
+                Ok(User {
+                    chat_id: row.get::<_, i64>(1)?,
+                    data: UserData {
+                        user_id: row.get::<_, i64>(0)?,
+                        name: row.get::<_, String>(2)?,
+                    },
+                    timezone: parse_timezone(row.get::<_, Option<String>>(3)?),
+                    poll_time: row.get::<_, Option<NaiveTime>>(4)?,
+                    inactive: row.get::<_, bool>(5)?,
+                })
+            })?
+            .next();
+        result.transpose().context("")
+    }
+
+    fn get_challenges_for_user(&self, user_id: &i32) -> Result<Vec<Challenge>> {
+        let mut statement = self.connection.prepare(
+            "SELECT challenge.id, challenge.name, challenge.time_start, challenge.time_end, challenge.archived, challenge.owner_id, challenge.weights_allowed, challenge.scoring, challenge.update_schedule FROM challenge, userChallenge WHERE challenge.id = userChallenge.challenge_id AND userChallenge.user_id = ?1 ORDER BY challenge.id",
+        )?;
+        let result = statement.query_map(params![user_id], |row| {
+            Ok(Challenge {
+                id: row.get::<_, i64>(0)?,
+                data: ChallengeData {
+                    name: row.get::<_, String>(1)?,
+                    time_frame: TimeFrame {
+                        start: row.get::<_, NaiveDate>(2)?,
+                        end: row.get::<_, NaiveDate>(3)?,
+                    },
+                    owner_id: row.get::<_, Option<i32>>(5)?,
+                    scoring: Scoring::from_str(&row.get::<_, String>(7)?).unwrap(),
+                },
+                archived: row.get::<_, bool>(4)?,
+                weights_allowed: row.get::<_, bool>(6)?,
+                update_schedule: parse_update_schedule(row.get::<_, Option<String>>(8)?),
+            })
+        })?;
+        result.collect::<rusqlite::Result<_>>().context("")
+    }
+
+    fn get_check_ins_for_task(&self, task_id: i64, user_id: i64) -> Result<Vec<CheckIn>> {
+        let mut statement = self.connection.prepare(
+            "SELECT task_id, date, poll_id, done, amount FROM userPollDate WHERE task_id = ?1 AND user_id = ?2 ORDER BY date, id",
+        )?;
+        let result = statement.query_map(params![task_id, user_id], |row| {
+            Ok(CheckIn {
+                task_id: row.get::<_, i64>(0)?,
+                date: row.get::<_, NaiveDate>(1)?,
+                poll_id: row.get::<_, String>(2)?,
+                done: row.get::<_, bool>(3)?,
+                amount: row.get::<_, Option<f64>>(4)?,
+            })
+        })?;
+        result.collect::<rusqlite::Result<_>>().context("")
+    }
+
+    fn archive_challenge(&self, challenge_id: i64) -> Result<()> {
+        self.connection.execute(
+            "UPDATE challenge SET archived = 1 WHERE id = ?1",
+            params![challenge_id],
+        )?;
+        Ok(())
+    }
+
+    fn set_weights_allowed(&self, challenge_id: i64, allowed: bool) -> Result<()> {
+        self.connection.execute(
+            "UPDATE challenge SET weights_allowed = ?1 WHERE id = ?2",
+            params![allowed, challenge_id],
+        )?;
+        Ok(())
+    }
+
+    fn set_task_weight(&self, task_id: i64, weight: f64) -> Result<()> {
+        self.connection.execute(
+            "UPDATE task SET weight = ?1 WHERE id = ?2",
+            params![weight, task_id],
+        )?;
+        Ok(())
+    }
+
+    fn set_task_active_until(&self, task_id: i64, date: NaiveDate) -> Result<()> {
+        self.connection.execute(
+            "UPDATE task SET active_until = ?1 WHERE id = ?2",
+            params![date, task_id],
+        )?;
+        Ok(())
+    }
+
+    fn insert_pause(&self, user_id: &i32, start: NaiveDate, end: Option<NaiveDate>) -> Result<()> {
+        self.connection
+            .execute(
+                "INSERT INTO pause (user_id, start_date, end_date) VALUES (?1, ?2, ?3)",
+                params![user_id, start, end],
+            )
+            .map_err(convert_error)?;
+        Ok(())
+    }
+
+    fn get_pauses(&self, user_id: i64) -> Result<Vec<Pause>> {
+        let mut statement = self.connection.prepare(
+            "SELECT id, start_date, end_date FROM pause WHERE user_id = ?1 ORDER BY start_date, id",
+        )?;
+        let pauses = statement
+            .query_map(params![user_id], |row| {
+                Ok(Pause {
+                    id: row.get(0)?,
+                    start: row.get(1)?,
+                    end: row.get(2)?,
+                })
+            })?
+            .collect::<rusqlite::Result<Vec<Pause>>>()?;
+        Ok(pauses)
+    }
+
+    fn set_pause_end(&self, pause_id: i64, end: NaiveDate) -> Result<()> {
+        self.connection.execute(
+            "UPDATE pause SET end_date = ?1 WHERE id = ?2",
+            params![end, pause_id],
+        )?;
+        Ok(())
+    }
+
+    fn delete_pause(&self, pause_id: i64) -> Result<()> {
+        self.connection
+            .execute("DELETE FROM pause WHERE id = ?1", params![pause_id])?;
+        Ok(())
+    }
+
+    fn write_action_log_entry(&self, entry: &ActionLogEntry) -> Result<()> {
+        self.connection.execute(
+            "INSERT INTO actionLog (timestamp, utc_offset, user_id, chat_id, action, response_kind) VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+            params![
+                entry.timestamp,
+                entry.utc_offset.local_minus_utc(),
+                entry.origin.user_id,
+                entry.origin.chat_id,
+                serde_json::to_string(&entry.action)?,
+                entry.response_kind,
+            ],
+        )?;
+        Ok(())
+    }
+
+    fn get_action_log(&self) -> Result<Vec<ActionLogEntry>> {
+        let mut statement = self.connection.prepare(
+            "SELECT timestamp, utc_offset, user_id, chat_id, action, response_kind FROM actionLog ORDER BY id",
+        )?;
+        let rows = statement.query_map(params![], |row| {
+            Ok((
+                row.get::<_, NaiveDateTime>(0)?,
+                row.get::<_, i32>(1)?,
+                ActionOrigin {
+                    user_id: row.get::<_, Option<i32>>(2)?,
+                    chat_id: row.get::<_, Option<i64>>(3)?,
+                },
+                row.get::<_, String>(4)?,
+                row.get::<_, String>(5)?,
+            ))
+        })?;
+        rows.map(|row| {
+            let (timestamp, utc_offset, origin, action, response_kind) = row?;
+            Ok(ActionLogEntry {
+                timestamp,
+                utc_offset: FixedOffset::east_opt(utc_offset)
+                    .ok_or_else(|| anyhow!("Invalid offset from UTC: {}", utc_offset))?,
+                origin,
+                action: serde_json::from_str(&action)
+                    .with_context(|| format!("While reading logged action {}", action))?,
+                response_kind,
+            })
+        })
+        .collect()
+    }
+
+    fn delete_user(&self, user_id: &i32) -> Result<()> {
+        let transaction = self.connection.unchecked_transaction()?;
+        let chat_id = self.get_user(user_id)?.map(|user| user.chat_id);
+        let poll_ids = transaction
+            .prepare("SELECT DISTINCT poll_id FROM userPollDate WHERE user_id = ?1")?
+            .query_map(params![user_id], |row| row.get::<_, String>(0))?
+            .collect::<rusqlite::Result<Vec<String>>>()?;
+        let entries = transaction
+            .prepare("SELECT id, user_id, chat_id, action FROM actionLog")?
+            .query_map(params![], |row| {
+                Ok((
+                    row.get::<_, i64>(0)?,
+                    ActionOrigin {
+                        user_id: row.get::<_, Option<i32>>(1)?,
+                        chat_id: row.get::<_, Option<i64>>(2)?,
+                    },
+                    row.get::<_, String>(3)?,
+                ))
+            })?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        for (id, origin, action) in entries {
+            let scrubbed = if origin.is_from(*user_id, chat_id) {
+                None
+            } else {
+                serde_json::from_str::<Action>(&action)?
+                    .without_user(*user_id, chat_id, &poll_ids)
+                    .map(|action| serde_json::to_string(&action))
+                    .transpose()?
+            };
+            match scrubbed {
+                None => {
+                    transaction.execute("DELETE FROM actionLog WHERE id = ?1", params![id])?;
+                }
+                Some(scrubbed) if scrubbed != action => {
+                    transaction.execute(
+                        "UPDATE actionLog SET action = ?1 WHERE id = ?2",
+                        params![scrubbed, id],
+                    )?;
+                }
+                Some(_) => {}
+            }
+        }
+        transaction.execute(
+            "UPDATE challenge SET owner_id = NULL WHERE owner_id = ?1",
+            params![user_id],
+        )?;
+        transaction.execute(
+            "DELETE FROM userPollDate WHERE user_id = ?1",
+            params![user_id],
+        )?;
+        transaction.execute("DELETE FROM task WHERE user_id = ?1", params![user_id])?;
+        transaction.execute("DELETE FROM pause WHERE user_id = ?1", params![user_id])?;
+        transaction.execute(
+            "DELETE FROM delivery WHERE chat_id IN (SELECT chat_id FROM user WHERE user_id = ?1)",
+            params![user_id],
+        )?;
+        transaction.execute(
+            "DELETE FROM userChallenge WHERE user_id = ?1",
+            params![user_id],
+        )?;
+        transaction.execute("DELETE FROM user WHERE user_id = ?1", params![user_id])?;
+        transaction.commit()?;
+        Ok(())
+    }
+}
+
+/// Timezones are stored by name. A name which chrono-tz does not know (anymore) falls back to the time of the server.
+fn parse_timezone(name: Option<String>) -> Option<Tz> {
+    name?.parse().ok()
+}
+
+/// Challenges without a schedule get the default one.
+fn parse_update_schedule(schedule: Option<String>) -> UpdateSchedule {
+    schedule
+        .and_then(|schedule| schedule.parse().ok())
+        .unwrap_or_default()
+}
+
+/// Turns constraint violations into a [ConstraintViolation] so that callers
+/// can react to them without knowing about sqlite.
+fn convert_error(err: rusqlite::Error) -> anyhow::Error {
+    match ConstraintViolation::from_sqlite_error(&err) {
+        Some(violation) => violation.into(),
+        None => err.into(),
+    }
 }
@@ -0,0 +1,133 @@
+use chrono::NaiveDate;
+use serde::Serialize;
+
+/// Days on which a user does not take part in their challenges.
+#[derive(Debug, Clone, Serialize)]
+pub struct Pause {
+    pub id: i64,
+    pub start: NaiveDate,
+    /// The last day of the pause, or None if the user pauses until further notice.
+    pub end: Option<NaiveDate>,
+}
+
+impl Pause {
+    pub fn contains(&self, date: NaiveDate) -> bool {
+        self.start <= date && self.end.map(|end| date <= end).unwrap_or(true)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::Duration;
+
+    use crate::{
+        action::Action,
+        clock::{Clock, FakeClock},
+        database::storage::Storage,
+        response::Response,
+        task_handling::get_done_fraction,
+        test_utils::{create_challenge, for_each_backend, gym, perform, reply_text, signup},
+        time_frame::TimeFrame,
+    };
+
+    fn run_pauses(database: &dyn Storage, clock: &FakeClock) {
+        let today = clock.today();
+        assert_eq!(
+            reply_text(perform(database, &Action::PauseUser(1, None, None))),
+            "Error: You have not signed up yet. Send a /signup to @deshittify_bot privately"
+        );
+        signup(database, &[1]);
+        create_challenge(database, "c", -10, 30);
+        assert_eq!(
+            reply_text(perform(
+                database,
+                &Action::PauseUser(1, Some(today), Some(today - Duration::days(1)))
+            )),
+            "Error: A pause cannot end before it starts."
+        );
+        assert_eq!(
+            reply_text(perform(
+                database,
+                &Action::PauseUser(1, Some(today - Duration::days(2)), None)
+            )),
+            "Error: A pause can start yesterday at the earliest."
+        );
+        // A pause planned a few days ago
+        let (start, end) = (today - Duration::days(5), today - Duration::days(3));
+        clock.set(start.and_hms(12, 0, 0));
+        assert_eq!(
+            reply_text(perform(
+                database,
+                &Action::PauseUser(1, Some(start), Some(end))
+            )),
+            format!("You are paused from {} until {}.", start, end)
+        );
+        clock.set(today.and_hms(12, 0, 0));
+        assert_eq!(database.get_user_tasks().unwrap().data.len(), 1);
+        assert_eq!(
+            reply_text(perform(database, &Action::ResumeUser(1))),
+            "Error: You are not paused."
+        );
+        assert_eq!(
+            reply_text(perform(database, &Action::PauseUser(1, None, None))),
+            format!(
+                "You are paused from {} until further notice. Send /resume when you are back.",
+                today
+            )
+        );
+        // No polls during the pause, and today neither counts for nor against the user
+        assert!(database.get_user_tasks().unwrap().data.is_empty());
+        let update_data = database.get_challenge_update_data().unwrap();
+        let user_performance = &update_data.0[0].user_performance[0];
+        assert!(user_performance.get_pause(today).is_some());
+        assert!(user_performance
+            .get_pause(end + Duration::days(1))
+            .is_none());
+        // Only the two days between the pauses count
+        assert_eq!(
+            user_performance.task_performance[0]
+                .get_done_fraction(&TimeFrame::new(start, today), &user_performance.pauses),
+            Some(get_done_fraction(
+                &gym(),
+                &[],
+                &TimeFrame::new(end + Duration::days(1), today - Duration::days(1))
+            ))
+        );
+        assert_eq!(
+            reply_text(perform(database, &Action::ResumeUser(1))),
+            "Welcome back!"
+        );
+        assert_eq!(database.get_user_tasks().unwrap().data.len(), 1);
+        // The pause which started today is gone, one which started earlier ends yesterday
+        assert_eq!(database.get_pauses(1).unwrap().len(), 1);
+        perform(
+            database,
+            &Action::PauseUser(1, Some(today - Duration::days(1)), None),
+        );
+        perform(database, &Action::ResumeUser(1));
+        let pauses = database.get_pauses(1).unwrap();
+        assert_eq!(pauses.len(), 2);
+        assert_eq!(pauses[1].end, Some(today - Duration::days(1)));
+        // A week spent paused is not scored at all
+        perform(database, &Action::PauseUser(1, None, None));
+        clock.advance(Duration::days(8));
+        let update_data = database.get_challenge_update_data().unwrap();
+        assert_eq!(
+            update_data.0[0].user_performance[0].get_weekly_average(clock.today()),
+            None
+        );
+        let document = match perform(database, &Action::ExportUserData(1)) {
+            Response::Document(document) => document,
+            response => panic!("Expected a document, got {:?}", response),
+        };
+        let export: serde_json::Value = serde_json::from_slice(&document.content).unwrap();
+        assert_eq!(export["pauses"][0]["start"], start.to_string());
+        perform(database, &Action::DeleteUser(1));
+        assert!(database.get_pauses(1).unwrap().is_empty());
+    }
+
+    #[test]
+    fn pauses() {
+        for_each_backend(run_pauses);
+    }
+}
@@ -0,0 +1,11 @@
+use chrono::NaiveDate;
+
+use super::task_data::TaskData;
+
+/// A check-in of a quantitative task for which the user has not told us the amount yet.
+#[derive(Debug, Clone)]
+pub struct PendingAmount {
+    pub check_in_id: i64,
+    pub task: TaskData,
+    pub date: NaiveDate,
+}
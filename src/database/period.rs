@@ -1,12 +1,18 @@
 use anyhow::anyhow;
+use chrono::{Datelike, NaiveDate, Weekday};
+use serde::{Deserialize, Serialize};
 use std::str::FromStr;
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum Period {
     Day,
     Week,
     Month,
     OneTime,
+    /// Due every n days, counted from the first day of the task.
+    EveryNDays(u32),
+    /// Due on the given days of the week.
+    Weekdays(Vec<Weekday>),
 }
 
 impl FromStr for Period {
@@ -22,19 +28,157 @@ impl FromStr for Period {
             "o" => Ok(Self::OneTime),
             "once" => Ok(Self::OneTime),
             "onetime" => Ok(Self::OneTime),
-            _ => Err(anyhow!("Wrong period specifier.")),
+            "weekend" | "weekends" => Ok(Self::Weekdays(vec![Weekday::Sat, Weekday::Sun])),
+            "weekdays" | "workdays" => Ok(Self::Weekdays(vec![
+                Weekday::Mon,
+                Weekday::Tue,
+                Weekday::Wed,
+                Weekday::Thu,
+                Weekday::Fri,
+            ])),
+            _ => parse_every_n_days(s)
+                .or_else(|| parse_weekdays(s))
+                .ok_or_else(|| anyhow!("Wrong period specifier.")),
         }
     }
 }
 
+/// Parses "3d", "3days" or "every3d".
+fn parse_every_n_days(s: &str) -> Option<Period> {
+    let s = s.strip_prefix("every").unwrap_or(s);
+    let n = s
+        .strip_suffix("days")
+        .or_else(|| s.strip_suffix('d'))?
+        .parse::<u32>()
+        .ok()?;
+    match n {
+        0 => None,
+        1 => Some(Period::Day),
+        _ => Some(Period::EveryNDays(n)),
+    }
+}
+
+/// Parses lists of weekdays such as "mon,wed,fri" or "tue/thu".
+fn parse_weekdays(s: &str) -> Option<Period> {
+    let mut weekdays = s
+        .split(&[',', '/'][..])
+        .map(|day| day.parse::<Weekday>().ok())
+        .collect::<Option<Vec<Weekday>>>()?;
+    weekdays.sort_by_key(|day| day.num_days_from_monday());
+    weekdays.dedup();
+    Some(Period::Weekdays(weekdays))
+}
+
 impl Period {
     pub fn to_string(&self) -> String {
         match self {
-            Period::Day => "d",
-            Period::Week => "w",
-            Period::Month => "m",
-            Period::OneTime => "o",
+            Period::Day => "d".to_owned(),
+            Period::Week => "w".to_owned(),
+            Period::Month => "m".to_owned(),
+            Period::OneTime => "o".to_owned(),
+            Period::EveryNDays(n) => format!("{}d", n),
+            Period::Weekdays(weekdays) => weekdays
+                .iter()
+                .map(|day| day.to_string().to_lowercase())
+                .collect::<Vec<String>>()
+                .join(","),
+        }
+    }
+
+    /// Whether the task has to be done on the given day. Tasks which are not
+    /// tied to specific days are due every day. The first day is the one from which
+    /// the task counts, so that a task due every n days is due on it.
+    pub fn is_due(&self, date: NaiveDate, first_day: NaiveDate) -> bool {
+        match self {
+            Period::EveryNDays(n) => (date - first_day).num_days().rem_euclid(*n as i64) == 0,
+            Period::Weekdays(weekdays) => weekdays.contains(&date.weekday()),
+            _ => true,
+        }
+    }
+
+    /// How to refer to the period which is currently running.
+    pub fn current_period_name(&self) -> &'static str {
+        match self {
+            Period::Week => "this week",
+            Period::Month => "this month",
+            Period::OneTime => "so far",
+            Period::Day | Period::EveryNDays(_) | Period::Weekdays(_) => "today",
+        }
+    }
+
+    /// What a single period is called, for numbers of periods such as streaks.
+    pub fn unit_name(&self, number: usize) -> &'static str {
+        match (self, number) {
+            (Period::Day, 1) => "day",
+            (Period::Day, _) => "days",
+            (Period::Week, 1) => "week",
+            (Period::Week, _) => "weeks",
+            (Period::Month, 1) => "month",
+            (Period::Month, _) => "months",
+            (_, 1) => "time",
+            (_, _) => "times",
+        }
+    }
+
+    /// Whether the task is scored day by day, in which case it can have
+    /// more than one check-in per day.
+    pub fn is_per_day(&self) -> bool {
+        matches!(
+            self,
+            Period::Day | Period::EveryNDays(_) | Period::Weekdays(_)
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{NaiveDate, Weekday};
+
+    use super::Period;
+
+    fn parse(s: &str) -> String {
+        s.parse::<Period>().unwrap().to_string()
+    }
+
+    #[test]
+    fn parse_periods() {
+        assert_eq!(parse("week"), "w");
+        assert_eq!(parse("3d"), "3d");
+        assert_eq!(parse("every3days"), "3d");
+        assert_eq!(parse("1d"), "d");
+        assert_eq!(parse("fri/mon,wed"), "mon,wed,fri");
+        assert_eq!(parse("weekends"), "sat,sun");
+        assert_eq!(parse("weekdays"), "mon,tue,wed,thu,fri");
+        assert!("0d".parse::<Period>().is_err());
+        assert!("mon,funday".parse::<Period>().is_err());
+        // The stored representation can be read back
+        for s in &["3d", "mon,wed,fri", "sat,sun", "d"] {
+            assert_eq!(parse(s), *s);
         }
-        .to_owned()
+    }
+
+    #[test]
+    fn due_days() {
+        let monday = NaiveDate::from_ymd(1970, 1, 5);
+        let weekends = Period::Weekdays(vec![Weekday::Sat, Weekday::Sun]);
+        let due: Vec<bool> = monday
+            .iter_days()
+            .take(7)
+            .map(|day| weekends.is_due(day, monday))
+            .collect();
+        assert_eq!(due, vec![false, false, false, false, false, true, true]);
+        let every_three_days = Period::EveryNDays(3);
+        let due_count = monday
+            .iter_days()
+            .take(30)
+            .filter(|day| every_three_days.is_due(*day, monday))
+            .count();
+        assert_eq!(due_count, 10);
+        // Counted from the first day, whichever day that is
+        let wednesday = NaiveDate::from_ymd(1970, 1, 7);
+        assert!(every_three_days.is_due(wednesday, wednesday));
+        assert!(!every_three_days.is_due(wednesday, monday));
+        assert!(every_three_days.is_due(wednesday + chrono::Duration::days(3), wednesday));
+        assert!(Period::Week.is_due(monday, wednesday));
     }
 }
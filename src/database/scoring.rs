@@ -0,0 +1,114 @@
+use anyhow::anyhow;
+use serde::{Deserialize, Serialize};
+use std::{fmt, str::FromStr};
+
+use crate::scoring_strategy::{
+    AllOrNothingScoring, DefaultScoring, OverachievementBonusScoring, RawRatioScoring,
+    ScoringStrategy,
+};
+
+/// The scoring strategy of a challenge, chosen when it is created.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub enum Scoring {
+    #[default]
+    Default,
+    RawRatio,
+    OverachievementBonus,
+    AllOrNothing,
+}
+
+impl Scoring {
+    pub fn strategy(&self) -> &'static dyn ScoringStrategy {
+        match self {
+            Scoring::Default => &DefaultScoring,
+            Scoring::RawRatio => &RawRatioScoring,
+            Scoring::OverachievementBonus => &OverachievementBonusScoring,
+            Scoring::AllOrNothing => &AllOrNothingScoring,
+        }
+    }
+}
+
+impl FromStr for Scoring {
+    type Err = anyhow::Error;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "default" => Ok(Self::Default),
+            "ratio" => Ok(Self::RawRatio),
+            "bonus" => Ok(Self::OverachievementBonus),
+            "allornothing" | "all-or-nothing" => Ok(Self::AllOrNothing),
+            _ => Err(anyhow!(
+                "Wrong scoring, use default, ratio, bonus or allornothing."
+            )),
+        }
+    }
+}
+
+impl fmt::Display for Scoring {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Scoring::Default => write!(f, "default"),
+            Scoring::RawRatio => write!(f, "ratio"),
+            Scoring::OverachievementBonus => write!(f, "bonus"),
+            Scoring::AllOrNothing => write!(f, "allornothing"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::Duration;
+
+    use crate::{
+        action::Action,
+        clock::{Clock, FakeClock},
+        database::{challenge_data::ChallengeData, storage::Storage},
+        test_utils::{create_challenge, for_each_backend, gym, perform, signup},
+        time_frame::TimeFrame,
+    };
+
+    use super::Scoring;
+
+    fn run_challenge_scoring(database: &dyn Storage, clock: &FakeClock) {
+        let today = clock.today();
+        signup(database, &[1]);
+        let challenge_id = create_challenge(database, "default", -10, 30);
+        perform(
+            database,
+            &Action::CreateNewChallenge(ChallengeData {
+                name: "strict".into(),
+                time_frame: TimeFrame::new(today - Duration::days(10), today + Duration::days(30)),
+                owner_id: Some(1),
+                scoring: Scoring::AllOrNothing,
+            }),
+        );
+        database
+            .subscribe_to_challenge(&1, &(challenge_id + 1))
+            .unwrap();
+        database
+            .insert_task(&1, &(challenge_id + 1), &gym(), today)
+            .unwrap();
+        let scorings: Vec<Scoring> = database
+            .get_challenges_for_user(&1)
+            .unwrap()
+            .iter()
+            .map(|challenge| challenge.data.scoring)
+            .collect();
+        assert_eq!(scorings, vec![Scoring::Default, Scoring::AllOrNothing]);
+        // The tasks of the strict challenge are scored all or nothing
+        let update_data = database.get_challenge_update_data().unwrap();
+        let strict = update_data
+            .0
+            .iter()
+            .find(|performance| performance.challenge.data.name == "strict")
+            .unwrap();
+        assert_eq!(
+            strict.user_performance[0].task_performance[0].scoring,
+            Scoring::AllOrNothing
+        );
+    }
+
+    #[test]
+    fn challenge_scoring() {
+        for_each_backend(run_challenge_scoring);
+    }
+}
@@ -0,0 +1,732 @@
+use anyhow::{anyhow, Result};
+use chrono::{Datelike, Duration, NaiveDate, NaiveDateTime, NaiveTime};
+use chrono_tz::Tz;
+use itertools::Itertools;
+
+use crate::{
+    action::{DeliveryOutcome, DeliveryReport, UserPollDateInfo},
+    clock::Clock,
+    config,
+    response::{ChallengeUpdateData, PollData, PollOption, UserTaskData},
+};
+
+use super::{
+    action_log_entry::ActionLogEntry,
+    challenge::Challenge,
+    challenge_data::ChallengeData,
+    challenge_performance_data::ChallengePerformanceData,
+    challenge_state::ChallengeState,
+    check_in::CheckIn,
+    delivery::Delivery,
+    delivery_status::DeliveryStatus,
+    pause::Pause,
+    pending_amount::PendingAmount,
+    task::Task,
+    task_data::TaskData,
+    task_kind::TaskKind,
+    task_performance_data::TaskPerformanceData,
+    update_schedule::UpdateSchedule,
+    user::User,
+    user::UserData,
+    user_export::{ChallengeExport, TaskExport, UserExport},
+    user_performance_data::UserPerformanceData,
+    user_task::UserTask,
+};
+
+/// Everything the bot needs to store and look up.
+/// Implementors only provide the basic queries, the logic on top of them
+/// lives in the provided methods so that all backends behave the same.
+/// Writes which would break the integrity of the data fail with a
+/// [ConstraintViolation](super::constraint_violation::ConstraintViolation).
+pub trait Storage {
+    /// The clock which decides what today is.
+    fn clock(&self) -> &dyn Clock;
+
+    fn add_challenge(&self, challenge: &ChallengeData) -> Result<Challenge>;
+
+    fn check_user_subscribed_to_challenge(&self, user_id: &i32, challenge_id: &i32)
+        -> Result<bool>;
+
+    fn check_user_signed_up(&self, user_id: &i32) -> Result<bool>;
+
+    fn insert_subscription(&self, user_id: &i32, challenge_id: &i32) -> Result<()>;
+
+    fn signup_user(&self, user_id: &i32, chat_id: &i64, user_name: &str) -> Result<()>;
+
+    /// Returns the id of the challenge with the given name that the user is
+    /// subscribed to and which ends after the given date.
+    fn get_active_challenge_id_by_user_id_and_name(
+        &self,
+        user_id: &i32,
+        challenge_name: &str,
+        date: NaiveDate,
+    ) -> Result<i32>;
+
+    /// Adds a task which is active from the given day on.
+    fn insert_task(
+        &self,
+        user_id: &i32,
+        challenge_id: &i32,
+        task_data: &TaskData,
+        active_from: NaiveDate,
+    ) -> Result<()>;
+
+    /// Returns all tasks together with the user they belong to,
+    /// ordered by chat id and task name. Inactive users are left out.
+    fn get_all_user_tasks(&self) -> Result<Vec<UserTask>>;
+
+    /// Returns every challenge once for each subscriber, with the chat of the subscriber.
+    /// Inactive users are left out.
+    fn get_challenges_and_chat_ids(&self) -> Result<Vec<(Challenge, i64)>>;
+
+    fn get_tasks_for_challenge_and_user(
+        &self,
+        challenge_id: i64,
+        user_id: i64,
+    ) -> Result<Vec<Task>>;
+
+    fn get_challenge_users(&self, challenge_id: i64) -> Result<Vec<UserData>>;
+
+    fn get_timestamps_for_task(&self, task_id: i64, user_id: i64) -> Result<Vec<NaiveDate>>;
+
+    /// Returns the dates and amounts of the check-ins of a quantitative task
+    /// for which the amount is known.
+    fn get_amounts_for_task(&self, task_id: i64, user_id: i64) -> Result<Vec<(NaiveDate, f64)>>;
+
+    /// Returns the check-ins of quantitative tasks which are still missing
+    /// their amount, oldest first.
+    fn get_pending_amounts(&self, user_id: &i32) -> Result<Vec<PendingAmount>>;
+
+    fn set_check_in_amount(&self, check_in_id: i64, amount: f64) -> Result<()>;
+
+    /// None if the delivery was never attempted.
+    fn get_delivery_status(&self, delivery: &Delivery) -> Result<Option<DeliveryStatus>>;
+
+    /// Counts one more attempt of the delivery, which may be tried again at next_attempt.
+    fn write_delivery_attempt(
+        &self,
+        delivery: &Delivery,
+        next_attempt: NaiveDateTime,
+    ) -> Result<()>;
+
+    fn mark_delivered(&self, delivery: &Delivery) -> Result<()>;
+
+    /// Uses up the remaining attempts of the delivery, so that it is not tried again.
+    fn give_up_delivery(&self, delivery: &Delivery) -> Result<()>;
+
+    /// Inactive chats get no polls and updates.
+    fn set_chat_inactive(&self, chat_id: i64, inactive: bool) -> Result<()>;
+
+    /// Sets the IANA timezone of the user. None goes back to the time of the server.
+    fn set_user_timezone(&self, user_id: &i32, timezone: Option<Tz>) -> Result<()>;
+
+    /// Sets the local time the user gets their poll at. None goes back to the usual time.
+    fn set_poll_time(&self, user_id: &i32, poll_time: Option<NaiveTime>) -> Result<()>;
+
+    fn set_update_schedule(&self, challenge_id: i64, schedule: UpdateSchedule) -> Result<()>;
+
+    fn modify_user_task_entries(&self, poll_id: &str, option_ids: &[i32]) -> Result<()>;
+
+    fn write_poll_info(&self, info: &[UserPollDateInfo]) -> Result<()>;
+
+    /// The task of every poll option which was sent to the user for the date.
+    fn get_sent_task_ids(&self, user_id: &i32, date: NaiveDate) -> Result<Vec<i64>>;
+
+    fn get_date_from_poll_id(&self, poll_id: &str) -> Result<NaiveDate>;
+
+    fn get_user_id_from_poll_id(&self, poll_id: &str) -> Result<i32>;
+
+    fn get_user(&self, user_id: &i32) -> Result<Option<User>>;
+
+    /// Returns all challenges the user is subscribed to.
+    fn get_challenges_for_user(&self, user_id: &i32) -> Result<Vec<Challenge>>;
+
+    fn get_check_ins_for_task(&self, task_id: i64, user_id: i64) -> Result<Vec<CheckIn>>;
+
+    fn archive_challenge(&self, challenge_id: i64) -> Result<()>;
+
+    fn set_weights_allowed(&self, challenge_id: i64, allowed: bool) -> Result<()>;
+
+    fn set_task_weight(&self, task_id: i64, weight: f64) -> Result<()>;
+
+    /// Retires the task after the given day.
+    fn set_task_active_until(&self, task_id: i64, date: NaiveDate) -> Result<()>;
+
+    fn insert_pause(&self, user_id: &i32, start: NaiveDate, end: Option<NaiveDate>) -> Result<()>;
+
+    /// Returns the pauses of the user, ordered by their start.
+    fn get_pauses(&self, user_id: i64) -> Result<Vec<Pause>>;
+
+    fn set_pause_end(&self, pause_id: i64, end: NaiveDate) -> Result<()>;
+
+    fn delete_pause(&self, pause_id: i64) -> Result<()>;
+
+    fn write_action_log_entry(&self, entry: &ActionLogEntry) -> Result<()>;
+
+    /// Returns the action log, oldest entry first.
+    fn get_action_log(&self) -> Result<Vec<ActionLogEntry>>;
+
+    /// Removes the user along with their subscriptions, tasks, check-ins, pauses
+    /// and everything about them in the action log, see [Action::without_user].
+    /// Challenges they created lose their owner.
+    fn delete_user(&self, user_id: &i32) -> Result<()>;
+
+    fn get_user_export(&self, user_id: &i32) -> Result<UserExport> {
+        let user = self
+            .get_user(user_id)?
+            .ok_or_else(|| anyhow!("You are not signed up, so nothing is stored about you."))?;
+        let mut challenges = vec![];
+        for challenge in self.get_challenges_for_user(user_id)? {
+            let mut tasks = vec![];
+            for task in self.get_tasks_for_challenge_and_user(challenge.id, *user_id as i64)? {
+                let check_ins = self.get_check_ins_for_task(task.id, *user_id as i64)?;
+                tasks.push(TaskExport { task, check_ins });
+            }
+            challenges.push(ChallengeExport { challenge, tasks });
+        }
+        let pauses = self.get_pauses(*user_id as i64)?;
+        Ok(UserExport {
+            user,
+            challenges,
+            pauses,
+        })
+    }
+
+    /// Returns the most recent challenge with the given name that the user is subscribed to,
+    /// regardless of whether it is still running.
+    fn get_challenge_for_user_by_name(
+        &self,
+        user_id: &i32,
+        challenge_name: &str,
+    ) -> Result<Challenge> {
+        self.get_challenges_for_user(user_id)?
+            .into_iter()
+            .filter(|challenge| challenge.data.name == challenge_name)
+            .max_by_key(|challenge| challenge.id)
+            .ok_or_else(|| anyhow!("No challenge with this name found for this user"))
+    }
+
+    fn subscribe_to_challenge(&self, user_id: &i32, challenge_id: &i32) -> Result<bool> {
+        let user_already_signed_up = self.check_user_signed_up(user_id)?;
+        if !user_already_signed_up {
+            return Err(anyhow!(
+                "You have not signed up yet. Send a /signup to @deshittify_bot privately"
+            ));
+        }
+        let user_already_subscribed =
+            self.check_user_subscribed_to_challenge(user_id, challenge_id)?;
+        if !user_already_subscribed {
+            self.insert_subscription(user_id, challenge_id)?;
+        }
+        Ok(user_already_subscribed)
+    }
+
+    fn add_task(&self, user_id: &i32, challenge_name: &str, task_data: &TaskData) -> Result<()> {
+        let today = self.get_today_for_user(user_id)?;
+        let challenge_id =
+            self.get_active_challenge_id_by_user_id_and_name(user_id, challenge_name, today)?;
+        if task_data.weight != 1.0 {
+            let weights_allowed = self
+                .get_challenges_for_user(user_id)?
+                .iter()
+                .any(|challenge| challenge.id == challenge_id as i64 && challenge.weights_allowed);
+            if !weights_allowed {
+                return Err(anyhow!("Weights are not allowed in {}.", challenge_name));
+            }
+        }
+        self.insert_task(user_id, &challenge_id, task_data, today)
+    }
+
+    fn pause_user(&self, user_id: &i32, start: NaiveDate, end: Option<NaiveDate>) -> Result<()> {
+        if !self.check_user_signed_up(user_id)? {
+            return Err(anyhow!(
+                "You have not signed up yet. Send a /signup to @deshittify_bot privately"
+            ));
+        }
+        if end.map(|end| end < start).unwrap_or(false) {
+            return Err(anyhow!("A pause cannot end before it starts."));
+        }
+        // Days which were already scored should stay as they are.
+        if start < self.get_today_for_user(user_id)?.pred() {
+            return Err(anyhow!("A pause can start yesterday at the earliest."));
+        }
+        self.insert_pause(user_id, start, end)
+    }
+
+    /// Ends the pause the user is in on the given day. That day is not paused anymore.
+    fn resume_user(&self, user_id: &i32, today: NaiveDate) -> Result<()> {
+        let pauses: Vec<Pause> = self
+            .get_pauses(*user_id as i64)?
+            .into_iter()
+            .filter(|pause| pause.contains(today))
+            .collect();
+        if pauses.is_empty() {
+            return Err(anyhow!("You are not paused."));
+        }
+        for pause in pauses {
+            if pause.start == today {
+                self.delete_pause(pause.id)?;
+            } else {
+                self.set_pause_end(pause.id, today.pred())?;
+            }
+        }
+        Ok(())
+    }
+
+    fn is_paused(&self, user_id: &i32, date: NaiveDate) -> Result<bool> {
+        Ok(self
+            .get_pauses(*user_id as i64)?
+            .iter()
+            .any(|pause| pause.contains(date)))
+    }
+
+    /// The timezone of the user, None if they did not set one or are not signed up.
+    fn get_timezone(&self, user_id: &i32) -> Result<Option<Tz>> {
+        Ok(self.get_user(user_id)?.and_then(|user| user.timezone))
+    }
+
+    /// The local time the user gets their poll at.
+    fn get_poll_time(&self, user_id: &i32) -> Result<NaiveTime> {
+        Ok(self
+            .get_user(user_id)?
+            .and_then(|user| user.poll_time)
+            .unwrap_or_else(|| {
+                NaiveTime::from_hms(config::HOUR_TO_SEND_POLL_AT, config::MINUTE_TO_SEND_AT, 0)
+            }))
+    }
+
+    /// The current date where the user lives.
+    fn get_today_for_user(&self, user_id: &i32) -> Result<NaiveDate> {
+        Ok(self.clock().today_in(self.get_timezone(user_id)?))
+    }
+
+    /// Returns the tasks of all active challenges which are due today, grouped into one poll per user.
+    /// Today is the local date of each user.
+    /// Tasks which have to be done several times a day get one option per repetition.
+    /// Users who are paused do not get a poll, neither do retired tasks.
+    fn get_user_tasks(&self) -> Result<UserTaskData> {
+        self.get_user_tasks_on(0)
+    }
+
+    /// Like [get_user_tasks](Storage::get_user_tasks), but for the given number of days ago.
+    fn get_user_tasks_on(&self, days_ago: i64) -> Result<UserTaskData> {
+        let mut user_tasks = vec![];
+        for user_task in self.get_all_user_tasks()? {
+            let date = self.clock().today_in(user_task.timezone) - Duration::days(days_ago);
+            if user_task.challenge.state(date) == ChallengeState::Active
+                && user_task.task.data.period.is_due(
+                    date,
+                    user_task
+                        .task
+                        .get_first_day(&user_task.challenge.data.time_frame),
+                )
+                && user_task.task.is_active(date)
+                && !self.is_paused(&user_task.user_id, date)?
+            {
+                user_tasks.push(user_task);
+            }
+        }
+        let mut data_grouped = UserTaskData { data: vec![] };
+        for ((user_id, chat_id, timezone), group) in &user_tasks
+            .into_iter()
+            .group_by(|user_task| (user_task.user_id, user_task.chat_id, user_task.timezone))
+        {
+            let group: Vec<UserTask> = group.collect();
+            let options = group
+                .iter()
+                .flat_map(|user_task| {
+                    let name_is_ambiguous = group
+                        .iter()
+                        .filter(|other| other.task.data.name == user_task.task.data.name)
+                        .count()
+                        > 1;
+                    let text = if name_is_ambiguous {
+                        format!(
+                            "{} ({})",
+                            user_task.task.data.name, user_task.challenge.data.name
+                        )
+                    } else {
+                        user_task.task.data.name.clone()
+                    };
+                    let task = &user_task.task.data;
+                    let text = match (&task.unit, task.kind) {
+                        (Some(unit), TaskKind::Positive) => format!("{} ({})", text, unit),
+                        (Some(unit), TaskKind::Negative) => {
+                            format!("{} (slipped, {})", text, unit)
+                        }
+                        (None, TaskKind::Positive) => text,
+                        (None, TaskKind::Negative) => format!("{} (slipped)", text),
+                    };
+                    // The amount of quantitative tasks is asked for separately.
+                    // Things to avoid get one more option than allowed, so that going over
+                    // the budget can be recorded.
+                    let repetitions = match (task.period.is_per_day(), &task.unit, task.kind) {
+                        (true, None, TaskKind::Positive) => task.count.max(1),
+                        (true, None, TaskKind::Negative) => task.count + 1,
+                        _ => 1,
+                    };
+                    (1..=repetitions).map(move |repetition| PollOption {
+                        task_id: user_task.task.id,
+                        text: if repetitions > 1 {
+                            format!("{} ({}/{})", text, repetition, repetitions)
+                        } else {
+                            text.clone()
+                        },
+                    })
+                })
+                .collect();
+            data_grouped.data.push(PollData {
+                chat_id,
+                user_id,
+                timezone,
+                date: self.clock().today_in(timezone) - Duration::days(days_ago),
+                late: days_ago > 0,
+                options,
+            });
+        }
+        Ok(data_grouped)
+    }
+
+    /// Returns the polls of the users whose poll time has come and who did not get one today yet,
+    /// including those whose poll failed before and is due for a retry.
+    /// Polls of the last few days which were missed, e.g. because the bot was down, come first.
+    fn check_date_and_get_all_user_tasks(&self) -> Result<UserTaskData> {
+        let mut due = vec![];
+        for days_ago in (1..=config::CATCH_UP_DAYS).rev() {
+            for poll in self.get_user_tasks_on(days_ago)?.data {
+                if self.is_catch_up_due(&poll)? && self.claim_delivery(&poll.delivery())? {
+                    due.push(poll);
+                }
+            }
+        }
+        for poll in self.get_user_tasks()?.data {
+            if !self
+                .clock()
+                .is_before_time_in(poll.timezone, self.get_poll_time(&poll.user_id)?)
+                && self.claim_delivery(&poll.delivery())?
+            {
+                due.push(poll);
+            }
+        }
+        for poll in due.iter_mut() {
+            self.remove_sent_options(poll)?;
+        }
+        Ok(UserTaskData { data: due })
+    }
+
+    /// Leaves out the options which were sent already, so that retrying a poll of which
+    /// only some parts went out does not send the other parts twice.
+    /// The options of a task are sent in order, so the first ones of each task are left out.
+    fn remove_sent_options(&self, poll: &mut PollData) -> Result<()> {
+        let mut sent_task_ids = self.get_sent_task_ids(&poll.user_id, poll.date)?;
+        poll.options.retain(|option| {
+            match sent_task_ids.iter().position(|id| *id == option.task_id) {
+                Some(index) => {
+                    sent_task_ids.swap_remove(index);
+                    false
+                }
+                None => true,
+            }
+        });
+        Ok(())
+    }
+
+    /// Returns the updates of the active challenges which are due, at the local time of their owners.
+    /// Each subscriber gets their own delivery, so one failing chat does not hold up the others.
+    fn check_date_and_get_challenge_update_data(&self) -> Result<ChallengeUpdateData> {
+        let mut challenge_performance_data = vec![];
+        let mut deliveries = vec![];
+        for (challenge, chat_id) in self.get_challenges_and_chat_ids()?.iter() {
+            if let Some(today) = self.get_challenge_update_due_date(challenge)? {
+                let delivery = Delivery::update(challenge.id, *chat_id, today);
+                if self.claim_delivery(&delivery)? {
+                    let recipient_today = self.get_today_for_subscriber(challenge.id, *chat_id)?;
+                    challenge_performance_data.push(self.get_challenge_performance(
+                        challenge,
+                        *chat_id,
+                        recipient_today,
+                    )?);
+                    deliveries.push(delivery);
+                }
+            }
+        }
+        Ok(ChallengeUpdateData(challenge_performance_data, deliveries))
+    }
+
+    /// Returns the forecasts of the active challenges for the subscribers on whose local
+    /// day of the week the nudge is sent, once their local time to send it has come.
+    /// This does not depend on the update schedule of the challenge.
+    fn check_date_and_get_forecast_nudge_data(&self) -> Result<ChallengeUpdateData> {
+        let mut challenge_performance_data = vec![];
+        let mut deliveries = vec![];
+        for (challenge, chat_id) in self.get_challenges_and_chat_ids()?.iter() {
+            let timezone = self.get_subscriber_timezone(challenge.id, *chat_id)?;
+            let today = self.clock().today_in(timezone);
+            if config::FORECAST_NUDGE_WEEKDAY != Some(today.weekday())
+                || challenge.state(today) != ChallengeState::Active
+                || self.clock().is_before_time_in(
+                    timezone,
+                    NaiveTime::from_hms(
+                        config::HOUR_TO_SEND_FORECAST_AT,
+                        config::MINUTE_TO_SEND_AT,
+                        0,
+                    ),
+                )
+            {
+                continue;
+            }
+            let delivery = Delivery::nudge(challenge.id, *chat_id, today);
+            if self.claim_delivery(&delivery)? {
+                challenge_performance_data
+                    .push(self.get_challenge_performance(challenge, *chat_id, today)?);
+                deliveries.push(delivery);
+            }
+        }
+        Ok(ChallengeUpdateData(challenge_performance_data, deliveries))
+    }
+
+    /// The timezone of the subscriber of the challenge who gets their messages in the chat.
+    fn get_subscriber_timezone(&self, challenge_id: i64, chat_id: i64) -> Result<Option<Tz>> {
+        for user_data in self.get_challenge_users(challenge_id)? {
+            if let Some(user) = self.get_user(&(user_data.user_id as i32))? {
+                if user.chat_id == chat_id {
+                    return Ok(user.timezone);
+                }
+            }
+        }
+        Ok(None)
+    }
+
+    /// The current date of the subscriber of the challenge who gets their messages in the chat.
+    fn get_today_for_subscriber(&self, challenge_id: i64, chat_id: i64) -> Result<NaiveDate> {
+        Ok(self
+            .clock()
+            .today_in(self.get_subscriber_timezone(challenge_id, chat_id)?))
+    }
+
+    /// Whether the poll of a past day should be caught up on, which is the case if the user
+    /// got a poll in the days before it. Whether it is sent is then up to its delivery.
+    /// Users who did not get any poll for a while, such as new or inactive ones, are not sent old polls.
+    fn is_catch_up_due(&self, poll: &PollData) -> Result<bool> {
+        for days_before in 1..=config::CATCH_UP_DAYS {
+            let date = poll.date - Duration::days(days_before);
+            let earlier = Delivery::poll(poll.user_id, poll.chat_id, date);
+            if let Some(DeliveryStatus {
+                delivered: true, ..
+            }) = self.get_delivery_status(&earlier)?
+            {
+                return Ok(true);
+            }
+        }
+        Ok(false)
+    }
+
+    /// Counts an attempt of the delivery if it was not delivered yet and a retry is due.
+    /// Returns whether it should be sent now.
+    /// The retry is scheduled right away, so that a send which never reports back is tried again as well.
+    fn claim_delivery(&self, delivery: &Delivery) -> Result<bool> {
+        let now = self.clock().now();
+        let attempts = match self.get_delivery_status(delivery)? {
+            Some(status) if !status.can_attempt(now) => return Ok(false),
+            Some(status) => status.attempts,
+            None => 0,
+        };
+        let delay = config::DELIVERY_RETRY_DELAY_SECS * 2i64.pow(attempts);
+        self.write_delivery_attempt(delivery, now + Duration::seconds(delay))?;
+        Ok(true)
+    }
+
+    /// Marks the chat of a delivery inactive when it refuses messages.
+    /// Rejected deliveries are not tried again, failed ones until they run out of attempts.
+    fn record_delivery(&self, report: &DeliveryReport) -> Result<()> {
+        let delivery = &report.delivery;
+        match report.outcome {
+            DeliveryOutcome::Delivered => self.mark_delivered(delivery),
+            DeliveryOutcome::Refused => self.set_chat_inactive(delivery.chat_id, true),
+            DeliveryOutcome::Rejected => self.give_up_delivery(delivery),
+            DeliveryOutcome::Failed => Ok(()),
+        }
+    }
+
+    /// The local date of the owner of the challenge, if its update for that date is due
+    /// according to the schedule of the challenge.
+    fn get_challenge_update_due_date(&self, challenge: &Challenge) -> Result<Option<NaiveDate>> {
+        let timezone = match challenge.data.owner_id {
+            Some(owner_id) => self.get_timezone(&owner_id)?,
+            None => None,
+        };
+        let today = self.clock().today_in(timezone);
+        if challenge.state(today) != ChallengeState::Active
+            || !challenge
+                .update_schedule
+                .is_due(self.clock().now_in(timezone))
+        {
+            return Ok(None);
+        }
+        Ok(Some(today))
+    }
+
+    /// Returns the check-ins of the tasks of the user, together with the part of the
+    /// challenge in which each task is active, so that a task added late or retired
+    /// early is only scored over its own days.
+    fn get_task_performance(
+        &self,
+        challenge: &Challenge,
+        user_id: i64,
+    ) -> Result<Vec<TaskPerformanceData>> {
+        let tasks = self.get_tasks_for_challenge_and_user(challenge.id, user_id)?;
+        tasks
+            .iter()
+            .map(move |task| {
+                let amounts = match task.data.unit {
+                    Some(_) => self.get_amounts_for_task(task.id, user_id)?,
+                    None => vec![],
+                };
+                let mut task_data = task.data.clone();
+                // Weights which were set before the owner forbade them do not count anymore
+                if !challenge.weights_allowed {
+                    task_data.weight = 1.0;
+                }
+                Ok(TaskPerformanceData {
+                    task: task_data,
+                    active_time_frame: task.get_active_time_frame(&challenge.data.time_frame),
+                    scoring: challenge.data.scoring,
+                    timestamps: self.get_timestamps_for_task(task.id, user_id)?,
+                    amounts,
+                })
+            })
+            .collect()
+    }
+
+    /// `today` is the local date of whoever the performance is shown to.
+    fn get_challenge_performance(
+        &self,
+        challenge: &Challenge,
+        chat_id: i64,
+        today: NaiveDate,
+    ) -> Result<ChallengePerformanceData> {
+        let mut user_performance = vec![];
+        for user in self.get_challenge_users(challenge.id)? {
+            let task_performance = self.get_task_performance(challenge, user.user_id)?;
+            let pauses = self.get_pauses(user.user_id)?;
+            user_performance.push(UserPerformanceData {
+                user,
+                task_performance,
+                pauses,
+            });
+        }
+        Ok(ChallengePerformanceData {
+            challenge: challenge.clone(),
+            chat_id,
+            user_performance,
+            today,
+        })
+    }
+
+    /// Returns the performance data of all active challenges.
+    fn get_challenge_update_data(&self) -> Result<ChallengeUpdateData> {
+        let today = self.clock().today();
+        let mut challenge_performance_data = vec![];
+        for (challenge, chat_id) in self.get_challenges_and_chat_ids()?.iter() {
+            if challenge.state(today) == ChallengeState::Active {
+                let recipient_today = self.get_today_for_subscriber(challenge.id, *chat_id)?;
+                challenge_performance_data.push(self.get_challenge_performance(
+                    challenge,
+                    *chat_id,
+                    recipient_today,
+                )?);
+            }
+        }
+        Ok(ChallengeUpdateData(challenge_performance_data, vec![]))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::Datelike;
+
+    use crate::{
+        action::{Action, UserPollDateInfo},
+        clock::{Clock, FakeClock},
+        database::{period::Period, task_data::TaskData, task_kind::TaskKind},
+        test_utils::{create_challenge, for_each_backend, perform, reply_text, signup, task},
+    };
+
+    use super::Storage;
+
+    fn run_daily_task_repetitions(database: &dyn Storage, clock: &FakeClock) {
+        let today = clock.today();
+        signup(database, &[1]);
+        create_challenge(database, "c", -1, 30);
+        let water = task("water", 2, Period::Day);
+        reply_text(perform(database, &Action::AddTask(1, "c".into(), water)));
+        // Not asked for on days on which it is not due
+        let yoga = task("yoga", 1, Period::Weekdays(vec![today.succ().weekday()]));
+        reply_text(perform(database, &Action::AddTask(1, "c".into(), yoga)));
+        let user_tasks = database.get_user_tasks().unwrap();
+        let texts: Vec<&str> = user_tasks.data[0]
+            .options
+            .iter()
+            .map(|option| option.text.as_str())
+            .collect();
+        assert_eq!(texts, vec!["gym", "water (1/2)", "water (2/2)"]);
+        let water_id = user_tasks.data[0].options[1].task_id;
+        let info = user_tasks.data[0]
+            .options
+            .iter()
+            .enumerate()
+            .map(|(index, option)| UserPollDateInfo {
+                user_id: 1,
+                date: today,
+                task_id: option.task_id,
+                poll_id: "poll".into(),
+                task_index: index as i32,
+            })
+            .collect();
+        perform(database, &Action::WritePollInfo(info));
+        perform(
+            database,
+            &Action::ModifyUserTaskTimestamps("poll".into(), vec![1, 2]),
+        );
+        assert_eq!(
+            database.get_timestamps_for_task(water_id, 1).unwrap(),
+            vec![today, today]
+        );
+        let update_data = database.get_challenge_update_data().unwrap();
+        assert_eq!(
+            update_data.0[0].user_performance[0].task_performance.len(),
+            3
+        );
+        // Things to avoid get an extra option for going over the budget
+        let sugar = TaskData {
+            kind: TaskKind::Negative,
+            ..task("sugar", 1, Period::Day)
+        };
+        reply_text(perform(database, &Action::AddTask(1, "c".into(), sugar)));
+        let user_tasks = database.get_user_tasks().unwrap();
+        let texts: Vec<&str> = user_tasks.data[0]
+            .options
+            .iter()
+            .skip(1)
+            .take(2)
+            .map(|option| option.text.as_str())
+            .collect();
+        assert_eq!(
+            texts,
+            vec!["sugar (slipped) (1/2)", "sugar (slipped) (2/2)"]
+        );
+        // Every few days, starting on the day it is added
+        let stretch = task("stretch", 1, Period::EveryNDays(3));
+        reply_text(perform(database, &Action::AddTask(1, "c".into(), stretch)));
+        let user_tasks = database.get_user_tasks().unwrap();
+        assert!(user_tasks.data[0]
+            .options
+            .iter()
+            .any(|option| option.text == "stretch"));
+    }
+
+    #[test]
+    fn daily_task_repetitions() {
+        for_each_backend(run_daily_task_repetitions);
+    }
+}
@@ -1,7 +1,32 @@
 use super::task_data::TaskData;
+use crate::time_frame::TimeFrame;
+use chrono::NaiveDate;
+use serde::Serialize;
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Serialize)]
 pub struct Task {
     pub id: i64,
     pub data: TaskData,
+    /// The day the task was added.
+    pub active_from: NaiveDate,
+    /// The last day of a retired task.
+    pub active_until: Option<NaiveDate>,
+}
+
+impl Task {
+    pub fn is_active(&self, date: NaiveDate) -> bool {
+        self.active_from <= date && self.active_until.map(|end| date <= end).unwrap_or(true)
+    }
+
+    /// The day from which the task counts in the time frame, which is
+    /// where the days of a task due every n days are counted from.
+    pub fn get_first_day(&self, time_frame: &TimeFrame) -> NaiveDate {
+        self.active_from.max(time_frame.start)
+    }
+
+    /// The part of the time frame in which the task is active, if any.
+    pub fn get_active_time_frame(&self, time_frame: &TimeFrame) -> Option<TimeFrame> {
+        let end = self.active_until.unwrap_or(time_frame.end);
+        time_frame.intersect(&TimeFrame::new(self.active_from, end))
+    }
 }
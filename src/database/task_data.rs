@@ -1,8 +1,227 @@
-use super::period::Period;
+use super::{period::Period, task_kind::TaskKind};
+use anyhow::anyhow;
+use serde::{Deserialize, Serialize};
+use std::str::FromStr;
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct TaskData {
     pub name: String,
     pub count: i32,
     pub period: Period,
+    /// Quantitative tasks have a unit, such as km or pages. For them, the count
+    /// is the amount which should be reached per period instead of a number of check-ins.
+    #[serde(default)]
+    pub unit: Option<String>,
+    #[serde(default)]
+    pub kind: TaskKind,
+    /// How much the task counts in the overall percentage of the user, relative to their other tasks.
+    #[serde(default = "default_weight")]
+    pub weight: f64,
+}
+
+fn default_weight() -> f64 {
+    1.0
+}
+
+/// The count of a task as given to /addtask: either a number of check-ins
+/// such as "3" or an amount with a unit such as "20km".
+/// A leading "max" makes it something to avoid, e.g. "max2" or "max5drinks".
+#[derive(Debug, PartialEq)]
+pub struct TaskTarget {
+    pub count: i32,
+    pub unit: Option<String>,
+    pub kind: TaskKind,
+}
+
+impl FromStr for TaskTarget {
+    type Err = anyhow::Error;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (s, kind) = match s.strip_prefix("max") {
+            Some(rest) => (rest, TaskKind::Negative),
+            None => (s, TaskKind::Positive),
+        };
+        let split = s.find(|c: char| !c.is_ascii_digit()).unwrap_or(s.len());
+        let (count, unit) = s.split_at(split);
+        let count = count
+            .parse::<i32>()
+            .map_err(|_| anyhow!("Wrong count, expected something like 3 or 20km."))?;
+        let unit = unit.trim();
+        Ok(TaskTarget {
+            count,
+            unit: if unit.is_empty() {
+                None
+            } else {
+                Some(unit.to_owned())
+            },
+            kind,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::Duration;
+
+    use crate::{
+        action::{Action, UserPollDateInfo},
+        clock::{Clock, FakeClock},
+        database::{period::Period, storage::Storage, task_kind::TaskKind},
+        task_handling::get_done_fraction,
+        test_utils::{create_challenge, for_each_backend, gym, perform, reply_text, signup, task},
+        time_frame::TimeFrame,
+    };
+
+    use super::{TaskData, TaskTarget};
+
+    #[test]
+    fn parse_task_targets() {
+        assert_eq!(
+            "3".parse::<TaskTarget>().unwrap(),
+            TaskTarget {
+                count: 3,
+                unit: None,
+                kind: TaskKind::Positive,
+            }
+        );
+        assert_eq!(
+            "150pages".parse::<TaskTarget>().unwrap(),
+            TaskTarget {
+                count: 150,
+                unit: Some("pages".into()),
+                kind: TaskKind::Positive,
+            }
+        );
+        assert_eq!(
+            "max2".parse::<TaskTarget>().unwrap(),
+            TaskTarget {
+                count: 2,
+                unit: None,
+                kind: TaskKind::Negative,
+            }
+        );
+        assert!("km".parse::<TaskTarget>().is_err());
+    }
+
+    fn run_task_weights(database: &dyn Storage, clock: &FakeClock) {
+        let today = clock.today();
+        signup(database, &[1, 2]);
+        let challenge_id = create_challenge(database, "c", -10, 30);
+        database.subscribe_to_challenge(&2, &challenge_id).unwrap();
+        let floss = TaskData {
+            weight: 0.0,
+            ..task("floss", 1, Period::Day)
+        };
+        assert_eq!(
+            reply_text(perform(
+                database,
+                &Action::AddTask(1, "c".into(), floss.clone())
+            )),
+            "Error: The weight has to be a positive number."
+        );
+        let floss = TaskData {
+            weight: 3.0,
+            ..floss
+        };
+        // A task added today does not count for the days before
+        let stretch = TaskData {
+            name: "stretch".into(),
+            ..floss.clone()
+        };
+        assert_eq!(
+            reply_text(perform(database, &Action::AddTask(1, "c".into(), stretch))),
+            "Task stretch added. Kaclxokca!"
+        );
+        // Floss was added when the challenge started
+        database
+            .insert_task(&1, &challenge_id, &floss, today - Duration::days(10))
+            .unwrap();
+        // Floss every day of the last week, never go to the gym
+        let tasks = database
+            .get_tasks_for_challenge_and_user(challenge_id as i64, 1)
+            .unwrap();
+        let floss_id = tasks
+            .iter()
+            .find(|task| task.data.name == "floss")
+            .unwrap()
+            .id;
+        let info: Vec<UserPollDateInfo> = (1..=7)
+            .map(|days_ago| UserPollDateInfo {
+                user_id: 1,
+                date: today - Duration::days(days_ago),
+                task_id: floss_id,
+                poll_id: format!("poll{}", days_ago),
+                task_index: 0,
+            })
+            .collect();
+        perform(database, &Action::WritePollInfo(info));
+        for days_ago in 1..=7 {
+            perform(
+                database,
+                &Action::ModifyUserTaskTimestamps(format!("poll{}", days_ago), vec![0]),
+            );
+        }
+        let weekly_average = |database: &dyn Storage| {
+            database.get_challenge_update_data().unwrap().0[0].user_performance[0]
+                .get_weekly_average(today)
+        };
+        // Depending on the weekday, the gym might not be due in the partial weeks
+        let gym_fraction = get_done_fraction(
+            &gym(),
+            &[],
+            &TimeFrame::new(today - Duration::days(7), today - Duration::days(1)),
+        );
+        assert_eq!(weekly_average(database), Some((3.0 + gym_fraction) / 4.0));
+        assert_eq!(
+            reply_text(perform(
+                database,
+                &Action::SetTaskWeight(1, "c".into(), "floss".into(), 1.0)
+            )),
+            "Task floss now has a weight of 1."
+        );
+        assert_eq!(weekly_average(database), Some((1.0 + gym_fraction) / 2.0));
+        assert_eq!(
+            reply_text(perform(
+                database,
+                &Action::SetTaskWeight(1, "c".into(), "run".into(), 1.0)
+            )),
+            "Error: You have no task called run in c."
+        );
+        assert_eq!(
+            reply_text(perform(
+                database,
+                &Action::SetWeightsAllowed(2, "c".into(), false)
+            )),
+            "Error: Only the creator of the challenge can decide about weights."
+        );
+        perform(
+            database,
+            &Action::SetTaskWeight(1, "c".into(), "floss".into(), 3.0),
+        );
+        reply_text(perform(
+            database,
+            &Action::SetWeightsAllowed(1, "c".into(), false),
+        ));
+        // Existing weights are ignored and no new ones can be set
+        assert_eq!(weekly_average(database), Some((1.0 + gym_fraction) / 2.0));
+        assert_eq!(
+            reply_text(perform(
+                database,
+                &Action::SetTaskWeight(1, "c".into(), "floss".into(), 2.0)
+            )),
+            "Error: Weights are not allowed in c."
+        );
+        let read = TaskData {
+            weight: 2.0,
+            ..task("read", 1, Period::Week)
+        };
+        assert_eq!(
+            reply_text(perform(database, &Action::AddTask(1, "c".into(), read))),
+            "Error: Weights are not allowed in c."
+        );
+    }
+
+    #[test]
+    fn task_weights() {
+        for_each_backend(run_task_weights);
+    }
 }
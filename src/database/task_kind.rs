@@ -0,0 +1,33 @@
+use anyhow::anyhow;
+use serde::{Deserialize, Serialize};
+use std::{fmt, str::FromStr};
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub enum TaskKind {
+    /// Something to do. The count is how often (or how much) it should be done per period.
+    #[default]
+    Positive,
+    /// Something to avoid. Check-ins are slips and the count is the number
+    /// of slips (or the amount) which is still fine per period.
+    Negative,
+}
+
+impl FromStr for TaskKind {
+    type Err = anyhow::Error;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "positive" => Ok(Self::Positive),
+            "negative" => Ok(Self::Negative),
+            _ => Err(anyhow!("Wrong task kind.")),
+        }
+    }
+}
+
+impl fmt::Display for TaskKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TaskKind::Positive => write!(f, "positive"),
+            TaskKind::Negative => write!(f, "negative"),
+        }
+    }
+}
@@ -1,9 +1,154 @@
 use chrono::NaiveDate;
 
-use super::task_data::TaskData;
+use crate::{
+    task_handling::{get_done_fraction_with_pauses, get_forecast, get_streak, Forecast, Streak},
+    time_frame::TimeFrame,
+};
+
+use super::{pause::Pause, scoring::Scoring, task_data::TaskData};
 
 #[derive(Debug)]
 pub struct TaskPerformanceData {
     pub task: TaskData,
+    /// The part of the challenge between adding and retiring the task.
+    /// None if the task was retired before the challenge started.
+    pub active_time_frame: Option<TimeFrame>,
+    /// The scoring strategy of the challenge.
+    pub scoring: Scoring,
     pub timestamps: Vec<NaiveDate>,
+    /// The amounts of the check-ins, only filled for quantitative tasks.
+    pub amounts: Vec<(NaiveDate, f64)>,
+}
+
+impl TaskPerformanceData {
+    /// The done fraction in the part of the time frame in which the task is active,
+    /// scored by the strategy of the challenge and leaving out the days on which the user paused.
+    /// None if the task is not active or the user paused during the whole time frame.
+    pub fn get_done_fraction(&self, time_frame: &TimeFrame, pauses: &[Pause]) -> Option<f64> {
+        let time_frame = self.get_active_part(time_frame)?;
+        get_done_fraction_with_pauses(
+            &self.task,
+            &self.get_done_amounts(),
+            &time_frame,
+            self.get_first_day()?,
+            pauses,
+            self.scoring.strategy(),
+        )
+    }
+
+    pub fn get_streak(&self, time_frame: &TimeFrame, pauses: &[Pause], today: NaiveDate) -> Streak {
+        match (self.get_active_part(time_frame), self.get_first_day()) {
+            (Some(time_frame), Some(first_day)) => get_streak(
+                &self.task,
+                &self.get_done_amounts(),
+                &time_frame,
+                first_day,
+                pauses,
+                today,
+            ),
+            _ => Streak::default(),
+        }
+    }
+
+    /// How the task is doing in its current period. None if the task is not active
+    /// today or not due today.
+    pub fn get_forecast(&self, time_frame: &TimeFrame, today: NaiveDate) -> Option<Forecast> {
+        let time_frame = self.get_active_part(time_frame)?;
+        get_forecast(
+            &self.task,
+            &self.get_done_amounts(),
+            &time_frame,
+            self.get_first_day()?,
+            today,
+        )
+    }
+
+    fn get_active_part(&self, time_frame: &TimeFrame) -> Option<TimeFrame> {
+        time_frame.intersect(self.active_time_frame.as_ref()?)
+    }
+
+    /// The day the task started to count in the challenge.
+    fn get_first_day(&self) -> Option<NaiveDate> {
+        Some(self.active_time_frame.as_ref()?.start)
+    }
+
+    /// The check-ins with their amounts. Check-ins of tasks without a unit count as one.
+    fn get_done_amounts(&self) -> Vec<(NaiveDate, f64)> {
+        match self.task.unit {
+            Some(_) => self.amounts.clone(),
+            None => self
+                .timestamps
+                .iter()
+                .map(|timestamp| (*timestamp, 1.0))
+                .collect(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::Datelike;
+
+    use crate::{
+        action::{Action, UserPollDateInfo},
+        clock::{Clock, FakeClock},
+        database::storage::Storage,
+        response::Response,
+        test_utils::{
+            create_challenge, for_each_backend, join_challenge, perform, reply_text, signup,
+        },
+    };
+
+    fn run_forecast(database: &dyn Storage, clock: &FakeClock) {
+        let today = clock.today();
+        signup(database, &[1, 2]);
+        let challenge_id = create_challenge(database, "c", -20, 30);
+        join_challenge(database, 2, challenge_id);
+        let gym_id = database
+            .get_tasks_for_challenge_and_user(challenge_id as i64, 1)
+            .unwrap()[0]
+            .id;
+        perform(
+            database,
+            &Action::WritePollInfo(vec![UserPollDateInfo {
+                user_id: 1,
+                date: today,
+                task_id: gym_id,
+                poll_id: "poll".into(),
+                task_index: 0,
+            }]),
+        );
+        perform(
+            database,
+            &Action::ModifyUserTaskTimestamps("poll".into(), vec![0]),
+        );
+        let challenges = match perform(database, &Action::ShowForecast(1, 10, None)) {
+            Response::Forecast(challenges) => challenges,
+            response => panic!("Expected a forecast, got {:?}", response),
+        };
+        assert_eq!(challenges.len(), 1);
+        // Only the tasks of the user who asked
+        assert_eq!(challenges[0].user_performance.len(), 1);
+        let forecast = challenges[0].user_performance[0].task_performance[0]
+            .get_forecast(&challenges[0].challenge.data.time_frame, today)
+            .unwrap();
+        assert_eq!(forecast.done, 1.0);
+        assert_eq!(forecast.remaining(), 2.0);
+        assert_eq!(
+            forecast.remaining_days,
+            7 - today.weekday().num_days_from_monday() as i64
+        );
+        assert_eq!(
+            reply_text(perform(
+                database,
+                &Action::ShowForecast(1, 10, Some("d".into()))
+            )),
+            "Error: No challenge with this name found for this user"
+        );
+    }
+
+    #[test]
+    fn forecast() {
+        for_each_backend(run_forecast);
+    }
 }
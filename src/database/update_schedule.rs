@@ -0,0 +1,196 @@
+use anyhow::anyhow;
+use chrono::{Datelike, NaiveDateTime, NaiveTime, Weekday};
+use serde::{Deserialize, Serialize};
+use std::{fmt, str::FromStr};
+
+use crate::config;
+
+/// When the update of a challenge is sent, in the local time of its owner.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum UpdateSchedule {
+    Daily(NaiveTime),
+    /// A weekly digest on the given day.
+    Weekly(Weekday, NaiveTime),
+    Off,
+}
+
+impl Default for UpdateSchedule {
+    fn default() -> Self {
+        UpdateSchedule::Daily(default_update_time())
+    }
+}
+
+fn default_update_time() -> NaiveTime {
+    NaiveTime::from_hms(config::HOUR_TO_SEND_UPDATE_AT, config::MINUTE_TO_SEND_AT, 0)
+}
+
+impl UpdateSchedule {
+    /// Whether the update of the day is due at the given wall clock time.
+    pub fn is_due(&self, now: NaiveDateTime) -> bool {
+        match self {
+            UpdateSchedule::Daily(time) => now.time() >= *time,
+            UpdateSchedule::Weekly(weekday, time) => {
+                now.weekday() == *weekday && now.time() >= *time
+            }
+            UpdateSchedule::Off => false,
+        }
+    }
+}
+
+/// Parses a time of day such as "17:00".
+pub fn parse_time(s: &str) -> anyhow::Result<NaiveTime> {
+    NaiveTime::parse_from_str(s, "%H:%M")
+        .map_err(|_| anyhow!("Wrong time {}, expected something like 17:00.", s))
+}
+
+/// Parses "off", "daily 18:00" or "weekly sun 18:00". Without a time, the update
+/// is sent at the usual time.
+impl FromStr for UpdateSchedule {
+    type Err = anyhow::Error;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let args: Vec<&str> = s.split_whitespace().collect();
+        let time = |arg: Option<&&str>| match arg {
+            Some(time) => parse_time(time),
+            None => Ok(default_update_time()),
+        };
+        match args.as_slice() {
+            ["off"] => Ok(UpdateSchedule::Off),
+            ["daily"] | ["daily", _] => Ok(UpdateSchedule::Daily(time(args.get(1))?)),
+            ["weekly", weekday] | ["weekly", weekday, _] => Ok(UpdateSchedule::Weekly(
+                weekday.parse().map_err(|_| {
+                    anyhow!("Wrong weekday {}, expected something like sun.", weekday)
+                })?,
+                time(args.get(2))?,
+            )),
+            _ => Err(anyhow!(
+                "Wrong schedule, use off, daily 17:00 or weekly sun 17:00."
+            )),
+        }
+    }
+}
+
+impl fmt::Display for UpdateSchedule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            UpdateSchedule::Daily(time) => write!(f, "daily {}", time.format("%H:%M")),
+            UpdateSchedule::Weekly(weekday, time) => write!(
+                f,
+                "weekly {} {}",
+                weekday.to_string().to_lowercase(),
+                time.format("%H:%M")
+            ),
+            UpdateSchedule::Off => write!(f, "off"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{Duration, NaiveDate, NaiveTime, Weekday};
+
+    use crate::{
+        action::{Action, DeliveryOutcome},
+        clock::FakeClock,
+        database::storage::Storage,
+        response::Response,
+        test_utils::{
+            create_challenge, for_each_backend, join_challenge, perform, reply_text, send_polls,
+            signup,
+        },
+    };
+
+    use super::UpdateSchedule;
+
+    #[test]
+    fn parse_and_check_schedules() {
+        let sunday_digest = "weekly sun 18:30".parse::<UpdateSchedule>().unwrap();
+        assert_eq!(
+            sunday_digest,
+            UpdateSchedule::Weekly(Weekday::Sun, NaiveTime::from_hms(18, 30, 0))
+        );
+        assert_eq!(sunday_digest.to_string(), "weekly sun 18:30");
+        assert_eq!(
+            "daily".parse::<UpdateSchedule>().unwrap(),
+            UpdateSchedule::default()
+        );
+        assert_eq!(
+            "off".parse::<UpdateSchedule>().unwrap(),
+            UpdateSchedule::Off
+        );
+        assert!("daily 25:00".parse::<UpdateSchedule>().is_err());
+        assert!("monthly".parse::<UpdateSchedule>().is_err());
+        // 2021-01-03 is a Sunday
+        let sunday = NaiveDate::from_ymd(2021, 1, 3);
+        assert!(!sunday_digest.is_due(sunday.and_hms(18, 29, 0)));
+        assert!(sunday_digest.is_due(sunday.and_hms(18, 30, 0)));
+        assert!(!sunday_digest.is_due(sunday.succ().and_hms(18, 30, 0)));
+        assert!(!UpdateSchedule::Off.is_due(sunday.and_hms(23, 0, 0)));
+    }
+
+    fn run_settings(database: &dyn Storage, clock: &FakeClock) {
+        // A Monday
+        let first = NaiveDate::from_ymd(2021, 2, 1);
+        signup(database, &[1, 2]);
+        let challenge_id = create_challenge(database, "c", -1, 30);
+        join_challenge(database, 2, challenge_id);
+        let sunday_digest = "weekly sun 18:00".parse::<UpdateSchedule>().unwrap();
+        assert_eq!(
+            reply_text(perform(
+                database,
+                &Action::SetUpdateSchedule(2, "c".into(), sunday_digest)
+            )),
+            "Error: Only the creator of the challenge can change its updates."
+        );
+        assert_eq!(
+            reply_text(perform(
+                database,
+                &Action::SetUpdateSchedule(1, "c".into(), sunday_digest)
+            )),
+            "Updates of c are sent weekly sun 18:00 now."
+        );
+        assert_eq!(
+            reply_text(perform(
+                database,
+                &Action::SetPollTime(2, Some(NaiveTime::from_hms(23, 30, 0)))
+            )),
+            "You will get your poll at 23:30 from now on."
+        );
+        assert_eq!(
+            reply_text(perform(database, &Action::ShowSettings(2))),
+            "Timezone: the time of the server\nPoll: 23:30\nUpdates of c: weekly sun 18:00"
+        );
+        let delivered = |_| DeliveryOutcome::Delivered;
+        clock.set(first.and_hms(22, 0, 0));
+        assert_eq!(send_polls(database, delivered), vec![1]);
+        clock.set(first.and_hms(23, 30, 0));
+        assert_eq!(send_polls(database, delivered), vec![2]);
+        // No daily update anymore, only on sunday
+        for day in 0..6 {
+            clock.set((first + Duration::days(day)).and_hms(23, 0, 0));
+            assert!(perform(database, &Action::CheckDateMaybeSendChallengeUpdates).is_empty());
+        }
+        let sunday = first + Duration::days(6);
+        clock.set(sunday.and_hms(17, 59, 0));
+        assert!(perform(database, &Action::CheckDateMaybeSendChallengeUpdates).is_empty());
+        clock.set(sunday.and_hms(18, 0, 0));
+        match perform(database, &Action::CheckDateMaybeSendChallengeUpdates) {
+            Response::ChallengeUpdates(update_data) => assert_eq!(update_data.0.len(), 2),
+            response => panic!("Expected challenge updates, got {:?}", response),
+        }
+        perform(
+            database,
+            &Action::SetUpdateSchedule(1, "c".into(), UpdateSchedule::Off),
+        );
+        clock.set((sunday + Duration::days(7)).and_hms(18, 0, 0));
+        assert!(perform(database, &Action::CheckDateMaybeSendChallengeUpdates).is_empty());
+        assert_eq!(
+            reply_text(perform(database, &Action::SetPollTime(2, None))),
+            "You will get your poll at 22:00 from now on."
+        );
+    }
+
+    #[test]
+    fn settings() {
+        for_each_backend(run_settings);
+    }
+}
@@ -1,10 +1,116 @@
+use chrono::NaiveTime;
+use chrono_tz::Tz;
+use serde::Serialize;
+
+#[derive(Debug, Serialize)]
 pub struct User {
-    pub id: i32,
+    pub chat_id: i64,
     pub data: UserData,
+    /// The IANA timezone of the user. Without one, the time of the server is used.
+    pub timezone: Option<Tz>,
+    /// The local time the user gets their poll at. Without one, the usual time is used.
+    pub poll_time: Option<NaiveTime>,
+    /// Set when messages to the user could not be delivered, until they resume.
+    pub inactive: bool,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Serialize)]
 pub struct UserData {
     pub user_id: i64,
     pub name: String,
 }
+
+#[cfg(test)]
+mod tests {
+    use chrono::NaiveDate;
+
+    use crate::{
+        action::Action,
+        clock::FakeClock,
+        database::storage::Storage,
+        response::Response,
+        test_utils::{
+            create_challenge, for_each_backend, join_challenge, perform, reply_text, signup,
+        },
+    };
+
+    fn run_timezones(database: &dyn Storage, clock: &FakeClock) {
+        let first = NaiveDate::from_ymd(2021, 2, 1);
+        clock.set(first.and_hms(0, 0, 0));
+        assert_eq!(
+            reply_text(perform(
+                database,
+                &Action::SetTimezone(1, Some(chrono_tz::Pacific::Auckland))
+            )),
+            "Error: You have not signed up yet. Send a /signup to @deshittify_bot privately"
+        );
+        signup(database, &[1, 2]);
+        assert_eq!(
+            reply_text(perform(
+                database,
+                &Action::SetTimezone(1, Some(chrono_tz::Pacific::Auckland))
+            )),
+            "Your timezone is now Pacific/Auckland. It is 2021-02-01 13:00 there."
+        );
+        perform(
+            database,
+            &Action::SetTimezone(2, Some(chrono_tz::America::Los_Angeles)),
+        );
+        let challenge_id = create_challenge(database, "c", -1, 30);
+        join_challenge(database, 2, challenge_id);
+        let poll_dates = |response: Response| match response {
+            Response::TaskPolls(polls) => polls
+                .data
+                .iter()
+                .map(|poll| (poll.user_id, poll.date))
+                .collect::<Vec<_>>(),
+            response => panic!("Expected polls, got {:?}", response),
+        };
+        // The update goes out at 17:00 in the timezone of the owner
+        clock.set(first.and_hms(3, 59, 0));
+        assert!(perform(database, &Action::CheckDateMaybeSendChallengeUpdates).is_empty());
+        clock.set(first.and_hms(4, 0, 0));
+        match perform(database, &Action::CheckDateMaybeSendChallengeUpdates) {
+            // One for everyone in the challenge, as of their own date
+            Response::ChallengeUpdates(update_data) => assert_eq!(
+                update_data
+                    .0
+                    .iter()
+                    .map(|update| (update.chat_id, update.today))
+                    .collect::<Vec<_>>(),
+                vec![(10, first), (20, first.pred())]
+            ),
+            response => panic!("Expected challenge updates, got {:?}", response),
+        }
+        assert!(perform(database, &Action::CheckDateMaybeSendChallengeUpdates).is_empty());
+        match perform(database, &Action::ShowChallengeStats(2, 10, "c".into())) {
+            Response::ChallengeUpdates(update_data) => {
+                assert_eq!(update_data.0[0].today, first.pred())
+            }
+            response => panic!("Expected challenge updates, got {:?}", response),
+        }
+        // 22:00 in Auckland, but 01:00 in Los Angeles
+        clock.set(first.and_hms(9, 0, 0));
+        assert_eq!(
+            poll_dates(perform(database, &Action::CheckDateMaybeSendPolls)),
+            vec![(1, first)]
+        );
+        assert!(perform(database, &Action::CheckDateMaybeSendPolls).is_empty());
+        // 22:00 in Los Angeles, while it is the next morning in Auckland
+        clock.set(first.succ().and_hms(6, 0, 0));
+        assert_eq!(
+            poll_dates(perform(database, &Action::CheckDateMaybeSendPolls)),
+            vec![(2, first)]
+        );
+        assert_eq!(
+            reply_text(perform(database, &Action::SetTimezone(2, None))),
+            "You are on the time of the server again."
+        );
+        assert_eq!(database.get_timezone(&2).unwrap(), None);
+    }
+
+    #[test]
+    fn timezones() {
+        for_each_backend(run_timezones);
+    }
+}
@@ -0,0 +1,23 @@
+use serde::Serialize;
+
+use super::{challenge::Challenge, check_in::CheckIn, pause::Pause, task::Task, user::User};
+
+/// Everything that is stored about a single user.
+#[derive(Debug, Serialize)]
+pub struct UserExport {
+    pub user: User,
+    pub challenges: Vec<ChallengeExport>,
+    pub pauses: Vec<Pause>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ChallengeExport {
+    pub challenge: Challenge,
+    pub tasks: Vec<TaskExport>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct TaskExport {
+    pub task: Task,
+    pub check_ins: Vec<CheckIn>,
+}
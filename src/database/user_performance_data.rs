@@ -1,42 +1,207 @@
-use chrono::{Duration, Local};
+use chrono::NaiveDate;
 
-use crate::{task_handling::get_done_fraction, time_frame::TimeFrame};
+use crate::{
+    report_window::ReportWindow,
+    task_handling::{get_streak, Streak},
+    time_frame::TimeFrame,
+};
 
 use super::{
-    challenge_data::ChallengeData, task_performance_data::TaskPerformanceData, user::UserData,
+    challenge_data::ChallengeData, pause::Pause, period::Period, task_data::TaskData,
+    task_kind::TaskKind, task_performance_data::TaskPerformanceData, user::UserData,
 };
 
 #[derive(Debug)]
 pub struct UserPerformanceData {
     pub user: UserData,
     pub task_performance: Vec<TaskPerformanceData>,
+    pub pauses: Vec<Pause>,
 }
 
 impl UserPerformanceData {
-    pub fn get_all_time_average(&self, challenge: &ChallengeData) -> f64 {
-        self.get_average_fraction_for_timeframe(&challenge.time_frame)
+    /// None if the user was paused during the whole challenge.
+    pub fn get_all_time_average(&self, challenge: &ChallengeData) -> Option<f64> {
+        self.get_average_for_time_frame(&challenge.time_frame)
+    }
+
+    /// The average of the last seven days, not counting today.
+    /// None if the user was paused during all of them.
+    pub fn get_weekly_average(&self, today: NaiveDate) -> Option<f64> {
+        self.get_average_for_time_frame(&ReportWindow::LastDays(7).get_time_frame(today))
+    }
+
+    /// The pause the user is in on the given day, if any.
+    pub fn get_pause(&self, date: NaiveDate) -> Option<&Pause> {
+        self.pauses.iter().find(|pause| pause.contains(date))
     }
 
-    pub fn get_weekly_average(&self) -> f64 {
-        let today = Local::today().naive_local();
-        let time_frame = TimeFrame {
-            start: today.checked_sub_signed(Duration::days(8)).unwrap(),
-            end: today.checked_sub_signed(Duration::days(1)).unwrap(),
+    /// The days in a row on which the user checked in on at least one of their
+    /// tasks. Slips of things to avoid do not count.
+    pub fn get_streak(&self, challenge: &ChallengeData, today: NaiveDate) -> Streak {
+        let active_days: Vec<(NaiveDate, f64)> = self
+            .task_performance
+            .iter()
+            .filter(|performance| performance.task.kind == TaskKind::Positive)
+            .flat_map(|performance| performance.timestamps.iter())
+            .map(|date| (*date, 1.0))
+            .collect();
+        let any_task_daily = TaskData {
+            name: String::new(),
+            count: 1,
+            period: Period::Day,
+            unit: None,
+            kind: TaskKind::Positive,
+            weight: 1.0,
         };
-        self.get_average_fraction_for_timeframe(&time_frame)
+        get_streak(
+            &any_task_daily,
+            &active_days,
+            &challenge.time_frame,
+            challenge.time_frame.start,
+            &self.pauses,
+            today,
+        )
     }
 
-    fn get_average_fraction_for_timeframe(&self, time_frame: &TimeFrame) -> f64 {
-        if self.task_performance.len() == 0 {
-            1.0
+    /// The average of the done fractions of all tasks, weighted by the weights of the tasks.
+    /// Paused days are left out. None if none of the tasks counts in the time frame.
+    pub fn get_average_for_time_frame(&self, time_frame: &TimeFrame) -> Option<f64> {
+        let weighted_fractions: Vec<(f64, f64)> = self
+            .task_performance
+            .iter()
+            .filter_map(|performance| {
+                performance
+                    .get_done_fraction(time_frame, &self.pauses)
+                    .map(|fraction| (performance.task.weight, fraction))
+            })
+            .collect();
+        let total_weight: f64 = weighted_fractions.iter().map(|(weight, _)| weight).sum();
+        if weighted_fractions.is_empty() || total_weight == 0.0 {
+            None
         } else {
-            self.task_performance
-                .iter()
-                .map(|performance| {
-                    get_done_fraction(&performance.task, &performance.timestamps, time_frame)
-                })
-                .sum::<f64>()
-                / self.task_performance.len() as f64
+            Some(
+                weighted_fractions
+                    .iter()
+                    .map(|(weight, fraction)| weight * fraction)
+                    .sum::<f64>()
+                    / total_weight,
+            )
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use chrono::{Duration, NaiveDate};
+
+    use crate::{
+        action::Action,
+        clock::{Clock, FakeClock},
+        database::{
+            period::Period, scoring::Scoring, storage::Storage,
+            task_performance_data::TaskPerformanceData, user::UserData,
+        },
+        task_handling::get_done_fraction,
+        test_utils::{create_challenge, for_each_backend, gym, perform, reply_text, signup, task},
+        time_frame::TimeFrame,
+    };
+
+    use super::UserPerformanceData;
+
+    #[test]
+    fn weekly_average() {
+        let today = NaiveDate::from_ymd(2021, 2, 8);
+        let performance = |timestamps: Vec<NaiveDate>| UserPerformanceData {
+            user: UserData {
+                user_id: 1,
+                name: "a".into(),
+            },
+            task_performance: vec![TaskPerformanceData {
+                task: task("floss", 1, Period::Day),
+                active_time_frame: Some(TimeFrame::new(today - Duration::days(30), today)),
+                scoring: Scoring::Default,
+                timestamps,
+                amounts: vec![],
+            }],
+            pauses: vec![],
+        };
+        // Exactly the seven days before today
+        let last_week = (1..=7).map(|days_ago| today - Duration::days(days_ago));
+        assert_eq!(
+            performance(last_week.collect()).get_weekly_average(today),
+            Some(1.0)
+        );
+        let outside = vec![today - Duration::days(8), today];
+        assert_eq!(performance(outside).get_weekly_average(today), Some(0.0));
+    }
+
+    fn run_task_dates(database: &dyn Storage, clock: &FakeClock) {
+        let today = clock.today();
+        signup(database, &[1]);
+        let challenge_id = create_challenge(database, "c", -10, 30);
+        let floss = task("floss", 1, Period::Day);
+        reply_text(perform(database, &Action::AddTask(1, "c".into(), floss)));
+        let last_week = TimeFrame::new(today - Duration::days(7), today - Duration::days(1));
+        let gym_fraction = get_done_fraction(&gym(), &[], &last_week);
+        let update_data = database.get_challenge_update_data().unwrap();
+        let user_performance = &update_data.0[0].user_performance[0];
+        let floss_performance = user_performance
+            .task_performance
+            .iter()
+            .find(|performance| performance.task.name == "floss")
+            .unwrap();
+        // The new task only counts from today on
+        assert_eq!(
+            floss_performance.active_time_frame.as_ref().unwrap().start,
+            today
+        );
+        assert_eq!(floss_performance.get_done_fraction(&last_week, &[]), None);
+        assert_eq!(
+            user_performance.get_weekly_average(today),
+            Some(gym_fraction)
+        );
+        assert_eq!(
+            reply_text(perform(
+                database,
+                &Action::RetireTask(1, "c".into(), "floss".into())
+            )),
+            "Task floss is retired, today is its last day."
+        );
+        assert_eq!(
+            reply_text(perform(
+                database,
+                &Action::RetireTask(1, "c".into(), "floss".into())
+            )),
+            "Error: Task floss is already retired."
+        );
+        assert_eq!(
+            reply_text(perform(
+                database,
+                &Action::RetireTask(1, "c".into(), "run".into())
+            )),
+            "Error: You have no task called run in c."
+        );
+        // Retired tasks are still asked about on their last day
+        let floss = database
+            .get_tasks_for_challenge_and_user(challenge_id as i64, 1)
+            .unwrap()
+            .into_iter()
+            .find(|task| task.data.name == "floss")
+            .unwrap();
+        assert_eq!(floss.active_until, Some(today));
+        assert!(!floss.is_active(today + Duration::days(1)));
+        assert_eq!(database.get_user_tasks().unwrap().data[0].options.len(), 2);
+        // The days on which a retired task was active still count
+        perform(database, &Action::RetireTask(1, "c".into(), "gym".into()));
+        let update_data = database.get_challenge_update_data().unwrap();
+        assert_eq!(
+            update_data.0[0].user_performance[0].get_weekly_average(today),
+            Some(gym_fraction)
+        );
+    }
+
+    #[test]
+    fn task_dates() {
+        for_each_backend(run_task_dates);
+    }
+}
@@ -0,0 +1,12 @@
+use chrono_tz::Tz;
+
+use super::{challenge::Challenge, task::Task};
+
+#[derive(Debug)]
+pub struct UserTask {
+    pub user_id: i32,
+    pub chat_id: i64,
+    pub timezone: Option<Tz>,
+    pub challenge: Challenge,
+    pub task: Task,
+}
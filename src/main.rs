@@ -1,15 +1,34 @@
-use crate::telegram::run_bot;
-use anyhow::Result;
+use crate::{replay::replay_database, telegram::run_bot};
+use anyhow::{anyhow, Result};
+use std::path::Path;
 
 pub mod action;
 pub mod action_handling;
+pub mod clock;
 pub mod config;
+pub mod csv_export;
 pub mod database;
+pub mod replay;
+pub mod report_window;
 pub mod response;
+pub mod scoring_strategy;
 pub mod task_handling;
 pub mod telegram;
+#[cfg(test)]
+pub mod test_utils;
 pub mod time_frame;
 
 fn main() -> Result<()> {
-    run_bot()
+    let args: Vec<String> = std::env::args().collect();
+    match args.get(1).map(|arg| arg.as_str()) {
+        Some("replay") => match (args.get(2), args.get(3)) {
+            (Some(log_path), Some(target_path)) => {
+                replay_database(Path::new(log_path), Path::new(target_path))
+            }
+            _ => Err(anyhow!(
+                "Usage: deshittify replay <database with log> <new database>"
+            )),
+        },
+        _ => run_bot(),
+    }
 }
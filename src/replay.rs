@@ -0,0 +1,187 @@
+use anyhow::{anyhow, Result};
+use std::{path::Path, sync::Arc};
+
+use crate::{
+    action_handling::handle_action,
+    clock::{Clock, FakeClock, SystemClock},
+    database::{action_log_entry::ActionLogEntry, storage::Storage, Database},
+};
+
+/// A logged action which succeeded originally but failed during the replay.
+#[derive(Debug)]
+pub struct ReplayFailure {
+    pub entry: ActionLogEntry,
+    pub error: String,
+}
+
+/// Performs the logged actions again, in order, to rebuild the state of the bot
+/// in an empty database. The entries are copied into the action log of the new database.
+/// The database has to use the given clock, which is set to the time and offset of each entry
+/// before it is performed, so that actions which depend on the date see the same day as back then.
+/// Actions which fail anyway are collected instead of stopping the replay.
+pub fn replay(
+    database: &dyn Storage,
+    clock: &FakeClock,
+    entries: &[ActionLogEntry],
+) -> Result<Vec<ReplayFailure>> {
+    let mut failures = vec![];
+    for entry in entries.iter() {
+        clock.set_with_offset(entry.timestamp, entry.utc_offset);
+        // Checking for polls and updates at the logged time records the same users
+        // and challenges as back then. Nothing is sent, the check-ins are in the following entries.
+        let result = handle_action(database, &entry.action).map(|_| ());
+        if let Err(err) = result {
+            if entry.response_kind != "Error" {
+                failures.push(ReplayFailure {
+                    entry: entry.clone(),
+                    error: err.to_string(),
+                });
+            }
+        }
+        database.write_action_log_entry(entry)?;
+    }
+    Ok(failures)
+}
+
+/// Rebuilds the database at target_path from the action log of the database at log_path.
+pub fn replay_database(log_path: &Path, target_path: &Path) -> Result<()> {
+    if target_path.exists() {
+        return Err(anyhow!(
+            "{} already exists, refusing to replay into it",
+            target_path.display()
+        ));
+    }
+    let entries = Database::new(log_path)?.get_action_log()?;
+    let clock = Arc::new(FakeClock::new(match entries.first() {
+        Some(entry) => entry.timestamp,
+        None => SystemClock.now(),
+    }));
+    let failures = replay(
+        &Database::with_clock(target_path, clock.clone())?,
+        &clock,
+        &entries,
+    )?;
+    for failure in failures.iter() {
+        eprintln!(
+            "{} {:?}: {}",
+            failure.entry.timestamp, failure.entry.action, failure.error
+        );
+    }
+    println!(
+        "Replayed {} actions into {}, {} failed",
+        entries.len(),
+        target_path.display(),
+        failures.len()
+    );
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{path::Path, sync::Arc};
+
+    use chrono::{Duration, FixedOffset, NaiveDate};
+
+    use crate::{
+        action::{Action, ActionOrigin, UserPollDateInfo},
+        action_handling::perform_action,
+        clock::FakeClock,
+        database::{
+            challenge_data::ChallengeData, memory::MemoryDatabase, scoring::Scoring,
+            storage::Storage, Database,
+        },
+        test_utils::gym,
+        time_frame::TimeFrame,
+    };
+
+    use super::replay;
+
+    #[test]
+    fn replay_rebuilds_state() {
+        let today = NaiveDate::from_ymd(2021, 1, 4);
+        let database = MemoryDatabase::with_clock(Arc::new(FakeClock::at_date(today)));
+        let origin = ActionOrigin {
+            user_id: Some(1),
+            chat_id: Some(10),
+        };
+        let actions = [
+            Action::SignupUser(1, 10, "a".into()),
+            Action::CreateNewChallenge(ChallengeData {
+                name: "c".into(),
+                time_frame: TimeFrame::new(today - Duration::days(1), today + Duration::days(30)),
+                owner_id: Some(1),
+                scoring: Scoring::Default,
+            }),
+            Action::SubscribeToChallenge(1, 1, "a".into()),
+            Action::AddTask(1, "c".into(), gym()),
+            // Fails, but is logged anyway
+            Action::SignupUser(1, 10, "a".into()),
+            Action::Nothing,
+            Action::WritePollInfo(vec![UserPollDateInfo {
+                user_id: 1,
+                date: today,
+                task_id: 1,
+                poll_id: "poll".into(),
+                task_index: 0,
+            }]),
+            Action::ModifyUserTaskTimestamps("poll".into(), vec![0]),
+        ];
+        for action in actions.iter() {
+            perform_action(&database, &origin, action);
+        }
+        let log = database.get_action_log().unwrap();
+        assert_eq!(log.len(), actions.len() - 1);
+        assert_eq!(log[0].origin, origin);
+        assert_eq!(log[4].response_kind, "Error");
+        // The challenge is long over, but the actions are replayed on the day they happened
+        let clock = Arc::new(FakeClock::at_date(today + Duration::days(365)));
+        let rebuilt = Database::with_clock(Path::new(":memory:"), clock.clone()).unwrap();
+        let failures = replay(&rebuilt, &clock, &log).unwrap();
+        assert!(failures.is_empty(), "{:?}", failures);
+        assert_eq!(rebuilt.get_action_log().unwrap().len(), log.len());
+        assert_eq!(rebuilt.get_timestamps_for_task(1, 1).unwrap(), vec![today]);
+    }
+
+    #[test]
+    fn replay_in_timezones() {
+        // Half past midnight on a server an hour ahead of UTC, but still the evening before in London
+        let now = NaiveDate::from_ymd(2021, 1, 5).and_hms(0, 30, 0);
+        let yesterday_in_london = NaiveDate::from_ymd(2021, 1, 3);
+        let clock = Arc::new(FakeClock::new(now));
+        clock.set_with_offset(now, FixedOffset::east(3600));
+        let database = MemoryDatabase::with_clock(clock);
+        let origin = ActionOrigin {
+            user_id: Some(1),
+            chat_id: Some(10),
+        };
+        let actions = [
+            Action::SignupUser(1, 10, "a".into()),
+            Action::SetTimezone(1, Some(chrono_tz::Europe::London)),
+            Action::CreateNewChallenge(ChallengeData {
+                name: "c".into(),
+                time_frame: TimeFrame::new(yesterday_in_london, now.date() + Duration::days(30)),
+                owner_id: Some(1),
+                scoring: Scoring::Default,
+            }),
+            Action::SubscribeToChallenge(1, 1, "a".into()),
+            Action::AddTask(1, "c".into(), gym()),
+            Action::PauseUser(1, Some(yesterday_in_london), None),
+        ];
+        for action in actions.iter() {
+            perform_action(&database, &origin, action);
+        }
+        let log = database.get_action_log().unwrap();
+        assert!(log.iter().all(|entry| entry.response_kind != "Error"));
+        // Replayed on a server on UTC
+        let clock = Arc::new(FakeClock::at_date(now.date() + Duration::days(365)));
+        let rebuilt = Database::with_clock(Path::new(":memory:"), clock.clone()).unwrap();
+        let failures = replay(&rebuilt, &clock, &log).unwrap();
+        assert!(failures.is_empty(), "{:?}", failures);
+        let tasks = rebuilt.get_tasks_for_challenge_and_user(1, 1).unwrap();
+        assert_eq!(tasks[0].active_from, now.date().pred());
+        assert_eq!(
+            rebuilt.get_action_log().unwrap()[0].utc_offset,
+            FixedOffset::east(3600)
+        );
+    }
+}
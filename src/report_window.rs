@@ -0,0 +1,298 @@
+use anyhow::anyhow;
+use chrono::{Datelike, Duration, NaiveDate};
+use serde::{Deserialize, Serialize};
+use std::str::FromStr;
+
+use crate::time_frame::TimeFrame;
+
+/// The days a performance report is about, relative to today.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum ReportWindow {
+    /// From monday up to today.
+    ThisWeek,
+    LastWeek,
+    /// From the first of the month up to today.
+    ThisMonth,
+    LastMonth,
+    /// The given number of days before today.
+    LastDays(u32),
+    Range(NaiveDate, NaiveDate),
+}
+
+impl FromStr for ReportWindow {
+    type Err = anyhow::Error;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "thisweek" | "week" => Ok(Self::ThisWeek),
+            "lastweek" => Ok(Self::LastWeek),
+            "thismonth" | "month" => Ok(Self::ThisMonth),
+            "lastmonth" => Ok(Self::LastMonth),
+            _ => parse_last_days(s)
+                .or_else(|| parse_range(s))
+                .ok_or_else(|| {
+                    anyhow!("Unknown report window. Use thisweek, lastweek, thismonth, lastmonth, a number of days such as 30d or a range such as 2021-01-01..2021-01-31.")
+                }),
+        }
+    }
+}
+
+/// Parses "30d", "30days" or "last30d".
+fn parse_last_days(s: &str) -> Option<ReportWindow> {
+    let s = s.strip_prefix("last").unwrap_or(s);
+    let n = s
+        .strip_suffix("days")
+        .or_else(|| s.strip_suffix('d'))?
+        .parse::<u32>()
+        .ok()?;
+    match n {
+        0 => None,
+        _ => Some(ReportWindow::LastDays(n)),
+    }
+}
+
+/// Parses "2021-01-01..2021-01-31".
+fn parse_range(s: &str) -> Option<ReportWindow> {
+    let mut dates = s.splitn(2, "..").map(|date| date.parse::<NaiveDate>().ok());
+    let start = dates.next()??;
+    let end = dates.next()??;
+    if end < start {
+        return None;
+    }
+    Some(ReportWindow::Range(start, end))
+}
+
+impl ReportWindow {
+    pub fn get_time_frame(&self, today: NaiveDate) -> TimeFrame {
+        let monday = today - Duration::days(today.weekday().num_days_from_monday() as i64);
+        let first_of_month = today.with_day(1).unwrap();
+        match self {
+            ReportWindow::ThisWeek => TimeFrame::new(monday, today),
+            ReportWindow::LastWeek => {
+                TimeFrame::new(monday - Duration::days(7), monday - Duration::days(1))
+            }
+            ReportWindow::ThisMonth => TimeFrame::new(first_of_month, today),
+            ReportWindow::LastMonth => get_month(first_of_month.pred()),
+            ReportWindow::LastDays(n) => {
+                TimeFrame::new(today - Duration::days(*n as i64), today.pred())
+            }
+            ReportWindow::Range(start, end) => TimeFrame::new(*start, *end),
+        }
+    }
+
+    /// The window right before this one which it is compared against, e.g. the
+    /// same days of last week for this week or the 30 days before the last 30 days.
+    pub fn get_previous_time_frame(&self, today: NaiveDate) -> TimeFrame {
+        let time_frame = self.get_time_frame(today);
+        match self {
+            ReportWindow::ThisWeek | ReportWindow::LastWeek => TimeFrame::new(
+                time_frame.start - Duration::days(7),
+                time_frame.end - Duration::days(7),
+            ),
+            ReportWindow::ThisMonth => {
+                let previous_month = get_month(time_frame.start.pred());
+                let end = previous_month
+                    .start
+                    .with_day(today.day())
+                    .unwrap_or(previous_month.end);
+                TimeFrame::new(previous_month.start, end)
+            }
+            ReportWindow::LastMonth => get_month(time_frame.start.pred()),
+            ReportWindow::LastDays(_) | ReportWindow::Range(_, _) => {
+                let length = time_frame.end - time_frame.start + Duration::days(1);
+                TimeFrame::new(time_frame.start - length, time_frame.start.pred())
+            }
+        }
+    }
+
+    pub fn name(&self) -> String {
+        match self {
+            ReportWindow::ThisWeek => "this week".to_owned(),
+            ReportWindow::LastWeek => "last week".to_owned(),
+            ReportWindow::ThisMonth => "this month".to_owned(),
+            ReportWindow::LastMonth => "last month".to_owned(),
+            ReportWindow::LastDays(n) => format!("the last {} days", n),
+            ReportWindow::Range(start, end) => format!("{} to {}", start, end),
+        }
+    }
+
+    /// How to refer to the window returned by [ReportWindow::get_previous_time_frame].
+    pub fn previous_name(&self) -> String {
+        match self {
+            ReportWindow::ThisWeek => "last week".to_owned(),
+            ReportWindow::LastWeek => "the week before".to_owned(),
+            ReportWindow::ThisMonth => "last month".to_owned(),
+            ReportWindow::LastMonth => "the month before".to_owned(),
+            ReportWindow::LastDays(n) => format!("the {} days before", n),
+            ReportWindow::Range(_, _) => "the same number of days before".to_owned(),
+        }
+    }
+}
+
+/// The whole month containing the given day.
+fn get_month(day_in_month: NaiveDate) -> TimeFrame {
+    let start = day_in_month.with_day(1).unwrap();
+    let next_month = (start + Duration::days(31)).with_day(1).unwrap();
+    TimeFrame::new(start, next_month.pred())
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{Duration, NaiveDate};
+
+    use crate::{
+        action::{Action, UserPollDateInfo},
+        clock::{Clock, FakeClock},
+        database::{period::Period, storage::Storage},
+        response::Response,
+        test_utils::{create_challenge, for_each_backend, perform, reply_text, signup, task},
+    };
+
+    use super::ReportWindow;
+
+    #[test]
+    fn parse_report_windows() {
+        assert_eq!(
+            "week".parse::<ReportWindow>().unwrap(),
+            ReportWindow::ThisWeek
+        );
+        assert_eq!(
+            "lastmonth".parse::<ReportWindow>().unwrap(),
+            ReportWindow::LastMonth
+        );
+        assert_eq!(
+            "30d".parse::<ReportWindow>().unwrap(),
+            ReportWindow::LastDays(30)
+        );
+        assert_eq!(
+            "last14days".parse::<ReportWindow>().unwrap(),
+            ReportWindow::LastDays(14)
+        );
+        assert_eq!(
+            "2021-01-01..2021-01-31".parse::<ReportWindow>().unwrap(),
+            ReportWindow::Range(
+                NaiveDate::from_ymd(2021, 1, 1),
+                NaiveDate::from_ymd(2021, 1, 31)
+            )
+        );
+        assert!("0d".parse::<ReportWindow>().is_err());
+        assert!("2021-01-31..2021-01-01".parse::<ReportWindow>().is_err());
+        assert!("yesterday".parse::<ReportWindow>().is_err());
+    }
+
+    #[test]
+    fn report_time_frames() {
+        // A Wednesday
+        let today = NaiveDate::from_ymd(2021, 3, 31);
+        let check = |window: ReportWindow,
+                     current: (u32, u32, u32, u32),
+                     previous: (u32, u32, u32, u32)| {
+            let time_frame = window.get_time_frame(today);
+            let previous_time_frame = window.get_previous_time_frame(today);
+            let date = |month, day| NaiveDate::from_ymd(2021, month, day);
+            assert_eq!(
+                (time_frame.start, time_frame.end),
+                (date(current.0, current.1), date(current.2, current.3))
+            );
+            assert_eq!(
+                (previous_time_frame.start, previous_time_frame.end),
+                (date(previous.0, previous.1), date(previous.2, previous.3))
+            );
+        };
+        check(ReportWindow::ThisWeek, (3, 29, 3, 31), (3, 22, 3, 24));
+        check(ReportWindow::LastWeek, (3, 22, 3, 28), (3, 15, 3, 21));
+        // February is shorter than March
+        check(ReportWindow::ThisMonth, (3, 1, 3, 31), (2, 1, 2, 28));
+        check(ReportWindow::LastMonth, (2, 1, 2, 28), (1, 1, 1, 31));
+        check(ReportWindow::LastDays(7), (3, 24, 3, 30), (3, 17, 3, 23));
+        check(
+            ReportWindow::Range(
+                NaiveDate::from_ymd(2021, 3, 10),
+                NaiveDate::from_ymd(2021, 3, 12),
+            ),
+            (3, 10, 3, 12),
+            (3, 7, 3, 9),
+        );
+    }
+
+    fn run_report(database: &dyn Storage, clock: &FakeClock) {
+        let today = clock.today();
+        signup(database, &[1]);
+        let challenge_id = create_challenge(database, "c", -20, 30);
+        let floss = task("floss", 1, Period::Day);
+        database
+            .insert_task(&1, &challenge_id, &floss, today - Duration::days(20))
+            .unwrap();
+        let floss_id = database
+            .get_tasks_for_challenge_and_user(challenge_id as i64, 1)
+            .unwrap()
+            .into_iter()
+            .find(|task| task.data.name == "floss")
+            .unwrap()
+            .id;
+        // Floss every day of the last week, but not in the week before
+        let info: Vec<UserPollDateInfo> = (1..=7)
+            .map(|days_ago| UserPollDateInfo {
+                user_id: 1,
+                date: today - Duration::days(days_ago),
+                task_id: floss_id,
+                poll_id: format!("poll{}", days_ago),
+                task_index: 0,
+            })
+            .collect();
+        perform(database, &Action::WritePollInfo(info));
+        for days_ago in 1..=7 {
+            perform(
+                database,
+                &Action::ModifyUserTaskTimestamps(format!("poll{}", days_ago), vec![0]),
+            );
+        }
+        let report = match perform(
+            database,
+            &Action::ShowReport(1, 10, ReportWindow::LastDays(7), None),
+        ) {
+            Response::Report(report) => report,
+            response => panic!("Expected a report, got {:?}", response),
+        };
+        assert_eq!(report.time_frame.start, today - Duration::days(7));
+        assert_eq!(report.previous_time_frame.end, today - Duration::days(8));
+        assert_eq!(report.challenges.len(), 1);
+        let user_performance = &report.challenges[0].user_performance[0];
+        let floss_performance = user_performance
+            .task_performance
+            .iter()
+            .find(|performance| performance.task.name == "floss")
+            .unwrap();
+        assert_eq!(
+            floss_performance.get_done_fraction(&report.time_frame, &[]),
+            Some(1.0)
+        );
+        assert_eq!(
+            floss_performance.get_done_fraction(&report.previous_time_frame, &[]),
+            Some(0.0)
+        );
+        assert!(
+            user_performance.get_average_for_time_frame(&report.time_frame)
+                > user_performance.get_average_for_time_frame(&report.previous_time_frame)
+        );
+        assert_eq!(
+            reply_text(perform(
+                database,
+                &Action::ShowReport(1, 10, ReportWindow::ThisWeek, Some("d".into()))
+            )),
+            "Error: No challenge with this name found for this user"
+        );
+        signup(database, &[2]);
+        assert_eq!(
+            reply_text(perform(
+                database,
+                &Action::ShowReport(2, 20, ReportWindow::ThisWeek, None)
+            )),
+            "Error: You are not part of any running challenge."
+        );
+    }
+
+    #[test]
+    fn report() {
+        for_each_backend(run_report);
+    }
+}
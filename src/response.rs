@@ -1,5 +1,15 @@
-use crate::database::{
-    challenge::Challenge, challenge_performance_data::ChallengePerformanceData,
+use chrono::NaiveDate;
+use chrono_tz::Tz;
+
+use crate::{
+    action::UserPollDateInfo,
+    config,
+    database::{
+        challenge::Challenge, challenge_performance_data::ChallengePerformanceData,
+        delivery::Delivery,
+    },
+    report_window::ReportWindow,
+    time_frame::TimeFrame,
 };
 
 #[derive(Debug)]
@@ -10,6 +20,40 @@ pub enum Response {
     Nothing,
     TaskPolls(UserTaskData),
     ChallengeUpdates(ChallengeUpdateData),
+    /// The forecasts of the week, one per recipient.
+    ForecastNudges(ChallengeUpdateData),
+    Document(DocumentData),
+    UserDeletionPrompt(i32),
+    AmountPrompt(AmountPromptData),
+    Streaks(ChallengePerformanceData),
+    Report(ReportData),
+    /// What is left to do in the current periods of the tasks of everyone in the challenges.
+    Forecast(Vec<ChallengePerformanceData>),
+}
+
+#[derive(Debug)]
+pub struct DocumentData {
+    pub chat_id: i64,
+    pub file_name: String,
+    pub content: Vec<u8>,
+}
+
+/// Asks the user how much of a quantitative task they did on the given day.
+#[derive(Debug)]
+pub struct AmountPromptData {
+    pub chat_id: i64,
+    pub task_name: String,
+    pub unit: String,
+    pub date: NaiveDate,
+}
+
+/// The performance in a window of days, compared to the window before it.
+#[derive(Debug)]
+pub struct ReportData {
+    pub window: ReportWindow,
+    pub time_frame: TimeFrame,
+    pub previous_time_frame: TimeFrame,
+    pub challenges: Vec<ChallengePerformanceData>,
 }
 
 #[derive(Debug)]
@@ -17,12 +61,211 @@ pub struct UserTaskData {
     pub data: Vec<PollData>,
 }
 
+/// The deliveries of scheduled updates are tracked, one per update in the same order.
+/// Updates which were asked for have none.
 #[derive(Debug)]
-pub struct ChallengeUpdateData(pub Vec<ChallengePerformanceData>);
+pub struct ChallengeUpdateData(pub Vec<ChallengePerformanceData>, pub Vec<Delivery>);
 
 #[derive(Debug)]
 pub struct PollData {
     pub chat_id: i64,
     pub user_id: i32,
-    pub task_names: Vec<String>,
+    pub timezone: Option<Tz>,
+    /// The day the poll is about, in the timezone of the user.
+    pub date: NaiveDate,
+    /// The poll is about a past day on which it was not sent.
+    pub late: bool,
+    pub options: Vec<PollOption>,
+}
+
+impl PollData {
+    pub fn delivery(&self) -> Delivery {
+        Delivery::poll(self.user_id, self.chat_id, self.date)
+    }
+
+    /// Telegram allows only a limited number of options per poll,
+    /// so the options are split into as many polls as needed.
+    pub fn parts(&self) -> Vec<&[PollOption]> {
+        self.options.chunks(config::MAX_POLL_OPTIONS).collect()
+    }
+
+    /// Which task each option of a sent part of the poll belongs to.
+    pub fn get_poll_date_infos(&self, part: &[PollOption], poll_id: &str) -> Vec<UserPollDateInfo> {
+        part.iter()
+            .enumerate()
+            .map(|(i, option)| UserPollDateInfo {
+                user_id: self.user_id,
+                date: self.date,
+                poll_id: poll_id.to_owned(),
+                task_id: option.task_id,
+                task_index: i as i32,
+            })
+            .collect()
+    }
+}
+
+#[derive(Debug)]
+pub struct PollOption {
+    pub task_id: i64,
+    pub text: String,
+}
+
+impl Response {
+    /// A short name of the variant, used in the action log.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Response::Reply(_) => "Reply",
+            Response::SendHelp => "SendHelp",
+            Response::SubscriptionPrompt(_) => "SubscriptionPrompt",
+            Response::Nothing => "Nothing",
+            Response::TaskPolls(_) => "TaskPolls",
+            Response::ChallengeUpdates(_) => "ChallengeUpdates",
+            Response::ForecastNudges(_) => "ForecastNudges",
+            Response::Document(_) => "Document",
+            Response::UserDeletionPrompt(_) => "UserDeletionPrompt",
+            Response::AmountPrompt(_) => "AmountPrompt",
+            Response::Streaks(_) => "Streaks",
+            Response::Report(_) => "Report",
+            Response::Forecast(_) => "Forecast",
+        }
+    }
+
+    /// Whether there is nothing to send, e.g. because the polls for today went out already.
+    pub fn is_empty(&self) -> bool {
+        match self {
+            Response::Nothing => true,
+            Response::TaskPolls(user_task_data) => user_task_data.data.is_empty(),
+            Response::ChallengeUpdates(update_data) | Response::ForecastNudges(update_data) => {
+                update_data.0.is_empty()
+            }
+            _ => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::Duration;
+
+    use crate::{
+        action::{Action, DeliveryOutcome, DeliveryReport},
+        clock::{Clock, FakeClock},
+        config,
+        database::{period::Period, storage::Storage},
+        test_utils::{create_challenge, for_each_backend, perform, reply_text, signup, task},
+    };
+
+    use super::Response;
+
+    fn run_long_polls(database: &dyn Storage, clock: &FakeClock) {
+        let today = clock.today();
+        signup(database, &[1]);
+        create_challenge(database, "c", -1, 30);
+        let water = task("water", 12, Period::Day);
+        reply_text(perform(database, &Action::AddTask(1, "c".into(), water)));
+        // Too many options for a single poll
+        let user_tasks = database.get_user_tasks().unwrap();
+        let poll = &user_tasks.data[0];
+        assert_eq!(poll.options.len(), 13);
+        let parts = poll.parts();
+        assert_eq!(
+            parts.iter().map(|part| part.len()).collect::<Vec<_>>(),
+            vec![config::MAX_POLL_OPTIONS, 3]
+        );
+        assert_eq!(parts[1][0].text, "water (10/12)");
+        // Each part is a poll of its own
+        let infos = parts
+            .iter()
+            .enumerate()
+            .flat_map(|(i, part)| poll.get_poll_date_infos(part, &format!("poll{}", i)))
+            .collect();
+        perform(database, &Action::WritePollInfo(infos));
+        perform(
+            database,
+            &Action::ModifyUserTaskTimestamps("poll1".into(), vec![0, 1, 2]),
+        );
+        let water_id = parts[1][0].task_id;
+        assert_eq!(
+            database.get_timestamps_for_task(water_id, 1).unwrap(),
+            vec![today, today, today]
+        );
+    }
+
+    #[test]
+    fn long_polls() {
+        for_each_backend(run_long_polls);
+    }
+
+    fn run_long_poll_retry(database: &dyn Storage, clock: &FakeClock) {
+        let today = clock.today();
+        signup(database, &[1]);
+        create_challenge(database, "c", -1, 30);
+        let water = task("water", 12, Period::Day);
+        reply_text(perform(database, &Action::AddTask(1, "c".into(), water)));
+        let check_for_poll = || match perform(database, &Action::CheckDateMaybeSendPolls) {
+            Response::TaskPolls(mut polls) => polls.data.pop(),
+            response => panic!("Expected polls, got {:?}", response),
+        };
+        clock.set(today.and_hms(22, 0, 0));
+        let poll = check_for_poll().unwrap();
+        assert_eq!(poll.options.len(), 13);
+        // The first part goes out, the second one fails
+        let parts = poll.parts();
+        perform(
+            database,
+            &Action::WritePollInfo(poll.get_poll_date_infos(parts[0], "poll0")),
+        );
+        perform(
+            database,
+            &Action::RecordDeliveries(vec![DeliveryReport {
+                delivery: poll.delivery(),
+                outcome: DeliveryOutcome::Failed,
+            }]),
+        );
+        // Only the second part is sent again
+        clock.advance(Duration::seconds(config::DELIVERY_RETRY_DELAY_SECS));
+        let retry = check_for_poll().unwrap();
+        let texts: Vec<&str> = retry
+            .options
+            .iter()
+            .map(|option| option.text.as_str())
+            .collect();
+        assert_eq!(
+            texts,
+            vec!["water (10/12)", "water (11/12)", "water (12/12)"]
+        );
+        perform(
+            database,
+            &Action::WritePollInfo(retry.get_poll_date_infos(&retry.options, "poll1")),
+        );
+        perform(
+            database,
+            &Action::RecordDeliveries(vec![DeliveryReport {
+                delivery: retry.delivery(),
+                outcome: DeliveryOutcome::Delivered,
+            }]),
+        );
+        clock.advance(Duration::seconds(config::DELIVERY_RETRY_DELAY_SECS * 4));
+        assert!(perform(database, &Action::CheckDateMaybeSendPolls).is_empty());
+        perform(
+            database,
+            &Action::ModifyUserTaskTimestamps("poll0".into(), (0..10).collect()),
+        );
+        perform(
+            database,
+            &Action::ModifyUserTaskTimestamps("poll1".into(), vec![0, 1, 2]),
+        );
+        assert_eq!(
+            database
+                .get_timestamps_for_task(retry.options[0].task_id, 1)
+                .unwrap()
+                .len(),
+            12
+        );
+    }
+
+    #[test]
+    fn long_poll_retry() {
+        for_each_backend(run_long_poll_retry);
+    }
 }
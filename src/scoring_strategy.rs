@@ -0,0 +1,154 @@
+/// How much of a task was done in a single period, e.g. one day or one week.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PeriodProgress {
+    /// The number of check-ins, or the summed amount for quantitative tasks.
+    pub done: f64,
+    /// What should have been done in the period. For periods which are only partly
+    /// inside the time frame, this is the count scaled down to the days inside of it.
+    pub target: f64,
+    /// The count of the task, i.e. the target of a full period.
+    pub count: f64,
+}
+
+/// Turns the progress in the periods of a task into the fraction of the task which was done.
+/// Time frames without any periods count as fully done.
+pub trait ScoringStrategy {
+    fn get_fraction(&self, periods: &[PeriodProgress]) -> f64;
+
+    /// Explains the strategy to the participants of a challenge.
+    fn describe(&self) -> &'static str;
+}
+
+/// The fraction of each period is what was done, up to the count, divided by the
+/// target rounded down. The fractions of all periods are averaged.
+pub struct DefaultScoring;
+
+/// Everything done compared to the sum of all targets, so that doing more in one
+/// period makes up for another. Capped at 100%.
+pub struct RawRatioScoring;
+
+/// Like the default, but doing more than the target gives a bonus of up to 20%.
+pub struct OverachievementBonusScoring;
+
+/// A period only counts if its target was reached.
+pub struct AllOrNothingScoring;
+
+const MAX_OVERACHIEVEMENT: f64 = 1.2;
+
+impl ScoringStrategy for DefaultScoring {
+    fn get_fraction(&self, periods: &[PeriodProgress]) -> f64 {
+        average(periods.iter().map(|period| {
+            let target = period.target.floor();
+            if target == 0.0 {
+                1.0
+            } else {
+                period.done.min(period.count) / target
+            }
+        }))
+    }
+
+    fn describe(&self) -> &'static str {
+        "each period counts up to its target"
+    }
+}
+
+impl ScoringStrategy for RawRatioScoring {
+    fn get_fraction(&self, periods: &[PeriodProgress]) -> f64 {
+        let target: f64 = periods.iter().map(|period| period.target).sum();
+        if target == 0.0 {
+            return 1.0;
+        }
+        let done: f64 = periods.iter().map(|period| period.done).sum();
+        (done / target).min(1.0)
+    }
+
+    fn describe(&self) -> &'static str {
+        "everything done compared to the total target"
+    }
+}
+
+impl ScoringStrategy for OverachievementBonusScoring {
+    fn get_fraction(&self, periods: &[PeriodProgress]) -> f64 {
+        average(periods.iter().map(|period| {
+            if period.target == 0.0 {
+                1.0
+            } else {
+                (period.done / period.target).min(MAX_OVERACHIEVEMENT)
+            }
+        }))
+    }
+
+    fn describe(&self) -> &'static str {
+        "up to 120% for doing more than the target"
+    }
+}
+
+impl ScoringStrategy for AllOrNothingScoring {
+    fn get_fraction(&self, periods: &[PeriodProgress]) -> f64 {
+        average(periods.iter().map(|period| {
+            if period.done >= period.target {
+                1.0
+            } else {
+                0.0
+            }
+        }))
+    }
+
+    fn describe(&self) -> &'static str {
+        "only periods in which the target was reached count"
+    }
+}
+
+fn average(fractions: impl Iterator<Item = f64>) -> f64 {
+    let fractions: Vec<f64> = fractions.collect();
+    match fractions.len() {
+        0 => 1.0,
+        len => fractions.iter().sum::<f64>() / len as f64,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        AllOrNothingScoring, DefaultScoring, OverachievementBonusScoring, PeriodProgress,
+        RawRatioScoring, ScoringStrategy,
+    };
+
+    fn progress(done: f64, target: f64) -> PeriodProgress {
+        PeriodProgress {
+            done,
+            target,
+            count: 3.0,
+        }
+    }
+
+    #[test]
+    fn strategies() {
+        // Overdone in the first period, half done in the second and a partial
+        // period with a target of 1.5 in which nothing was done
+        let periods = [progress(5.0, 3.0), progress(1.5, 3.0), progress(0.0, 1.5)];
+        assert_eq!(
+            DefaultScoring.get_fraction(&periods),
+            (1.0 + 0.5 + 0.0) / 3.0
+        );
+        assert_eq!(RawRatioScoring.get_fraction(&periods), 6.5 / 7.5);
+        assert_eq!(
+            OverachievementBonusScoring.get_fraction(&periods),
+            (1.2 + 0.5 + 0.0) / 3.0
+        );
+        assert_eq!(AllOrNothingScoring.get_fraction(&periods), 1.0 / 3.0);
+        for strategy in [
+            &DefaultScoring as &dyn ScoringStrategy,
+            &RawRatioScoring,
+            &OverachievementBonusScoring,
+            &AllOrNothingScoring,
+        ]
+        .iter()
+        {
+            assert_eq!(strategy.get_fraction(&[]), 1.0);
+            assert_eq!(strategy.get_fraction(&[progress(0.0, 0.0)]), 1.0);
+        }
+        // A target which rounds down to zero is always reached by default
+        assert_eq!(DefaultScoring.get_fraction(&[progress(0.0, 0.5)]), 1.0);
+    }
+}
@@ -1,7 +1,8 @@
 use chrono::{Datelike, Duration, NaiveDate, Weekday};
 
 use crate::{
-    database::{period::Period, task_data::TaskData},
+    database::{pause::Pause, period::Period, task_data::TaskData, task_kind::TaskKind},
+    scoring_strategy::{DefaultScoring, PeriodProgress, ScoringStrategy},
     time_frame::TimeFrame,
 };
 
@@ -10,87 +11,380 @@ pub fn get_done_fraction(
     done_timestamps: &[NaiveDate],
     time_frame: &TimeFrame,
 ) -> f64 {
-    match task.period {
-        Period::Week => get_done_fraction_weekly(
-            task.count,
-            done_timestamps,
-            &time_frame.start,
-            &time_frame.end,
-        ),
-        Period::Month => get_done_fraction_monthly(
+    let done_amounts: Vec<(NaiveDate, f64)> = done_timestamps
+        .iter()
+        .map(|timestamp| (*timestamp, 1.0))
+        .collect();
+    get_done_fraction_for_amounts(task, &done_amounts, time_frame)
+}
+
+/// Like [get_done_fraction], but every check-in comes with an amount. The summed
+/// amounts are compared against the count of the task, so that a task can be
+/// something like running 20 km per week. The task counts from the start of the time frame.
+pub fn get_done_fraction_for_amounts(
+    task: &TaskData,
+    done_amounts: &[(NaiveDate, f64)],
+    time_frame: &TimeFrame,
+) -> f64 {
+    get_scored_fraction(
+        task,
+        done_amounts,
+        time_frame,
+        time_frame.start,
+        &DefaultScoring,
+    )
+}
+
+/// Like [get_done_fraction_for_amounts], but the progress in the periods of the task
+/// is turned into a fraction by the given scoring strategy.
+/// Things to avoid are always scored by their budget.
+/// The first day is the one the task counts from, see [Period::is_due].
+pub fn get_scored_fraction(
+    task: &TaskData,
+    done_amounts: &[(NaiveDate, f64)],
+    time_frame: &TimeFrame,
+    first_day: NaiveDate,
+    scoring: &dyn ScoringStrategy,
+) -> f64 {
+    if task.kind == TaskKind::Negative {
+        return get_done_fraction_negative(
             task.count,
-            done_timestamps,
-            &time_frame.start,
-            &time_frame.end,
-        ),
-        Period::Day => todo!(),
-        Period::OneTime => get_done_fraction_onetime(
+            &task.period,
+            done_amounts,
+            time_frame,
+            first_day,
+        );
+    }
+    let progress = match task.period {
+        Period::Week => {
+            get_progress_weekly(task.count, done_amounts, &time_frame.start, &time_frame.end)
+        }
+        Period::Month => {
+            get_progress_monthly(task.count, done_amounts, &time_frame.start, &time_frame.end)
+        }
+        Period::Day | Period::EveryNDays(_) | Period::Weekdays(_) => get_progress_daily(
             task.count,
-            done_timestamps,
+            &task.period,
+            done_amounts,
             &time_frame.start,
             &time_frame.end,
+            first_day,
         ),
+        Period::OneTime => {
+            get_progress_onetime(task.count, done_amounts, &time_frame.start, &time_frame.end)
+        }
+    };
+    scoring.get_fraction(&progress)
+}
+
+/// Like [get_done_fraction_for_amounts], but the paused days neither count for nor
+/// against the user: check-ins on them are ignored and the time frame is split into
+/// the parts between the pauses. The fractions of the parts are weighted by the number
+/// of due days in them. Returns None if the whole time frame is paused.
+pub fn get_done_fraction_with_pauses(
+    task: &TaskData,
+    done_amounts: &[(NaiveDate, f64)],
+    time_frame: &TimeFrame,
+    first_day: NaiveDate,
+    pauses: &[Pause],
+    scoring: &dyn ScoringStrategy,
+) -> Option<f64> {
+    let is_paused = |day: NaiveDate| pauses.iter().any(|pause| pause.contains(day));
+    let done_amounts: Vec<(NaiveDate, f64)> = done_amounts
+        .iter()
+        .filter(|(day, _)| !is_paused(*day))
+        .cloned()
+        .collect();
+    let mut weighted_sum = 0.0;
+    let mut total_weight = 0.0;
+    for part in split_time_frame(time_frame, is_paused) {
+        let due_days = part
+            .start
+            .iter_days()
+            .take_while(|day| *day <= part.end)
+            .filter(|day| task.period.is_due(*day, first_day))
+            .count() as f64;
+        weighted_sum +=
+            due_days * get_scored_fraction(task, &done_amounts, &part, first_day, scoring);
+        total_weight += due_days;
+    }
+    match total_weight as usize {
+        0 => None,
+        _ => Some(weighted_sum / total_weight),
     }
 }
 
-fn get_done_fraction_weekly(
-    count: i32,
-    done_timestamps: &[NaiveDate],
-    start_date: &NaiveDate,
-    end_date: &NaiveDate,
-) -> f64 {
-    let mut fractions = vec![];
-    let week_day_counts = get_week_day_counts(&start_date, &end_date);
-    let refs = Box::new(done_timestamps.iter().map(move |x| *x));
-    for (week_start, week_end, days_in_week) in week_day_counts.iter() {
-        let done_count = count_days_in_range(refs.clone(), week_start, week_end);
-        let done_count = done_count.min(count as usize) as f64;
-        let should_have_done_count = (count as f64 * (*days_in_week as f64 / 7.0)).floor() as f64;
-        if should_have_done_count == 0.0 {
-            fractions.push(1.0);
+/// Splits the time frame into the runs of consecutive days which are not excluded.
+pub fn split_time_frame(
+    time_frame: &TimeFrame,
+    is_excluded: impl Fn(NaiveDate) -> bool,
+) -> Vec<TimeFrame> {
+    let mut parts: Vec<TimeFrame> = vec![];
+    for day in time_frame
+        .start
+        .iter_days()
+        .take_while(|day| *day <= time_frame.end)
+    {
+        if is_excluded(day) {
             continue;
         }
-        fractions.push(done_count / should_have_done_count);
+        match parts.last_mut() {
+            Some(part) if part.end.succ() == day => part.end = day,
+            _ => parts.push(TimeFrame::new(day, day)),
+        }
+    }
+    parts
+}
+
+/// The number of periods in a row in which a task was done.
+#[derive(Debug, Default, PartialEq)]
+pub struct Streak {
+    /// The streak which is still going on. A period which is still running only
+    /// adds to it once it is done, but does not break it before it is over.
+    pub current: usize,
+    pub longest: usize,
+}
+
+/// Counts the periods of the task in which it was fully done, from the start of the
+/// time frame up to today. Periods which are paused entirely are skipped.
+pub fn get_streak(
+    task: &TaskData,
+    done_amounts: &[(NaiveDate, f64)],
+    time_frame: &TimeFrame,
+    first_day: NaiveDate,
+    pauses: &[Pause],
+    today: NaiveDate,
+) -> Streak {
+    let mut streak = Streak::default();
+    for period in get_periods(&task.period, time_frame, first_day)
+        .iter()
+        .take_while(|period| period.start <= today)
+    {
+        let is_running = period.end >= today;
+        let fraction = match get_done_fraction_with_pauses(
+            task,
+            done_amounts,
+            period,
+            first_day,
+            pauses,
+            &DefaultScoring,
+        ) {
+            Some(fraction) => fraction,
+            None => continue,
+        };
+        if fraction >= 1.0 {
+            streak.current += 1;
+            streak.longest = streak.longest.max(streak.current);
+        } else if !is_running {
+            streak.current = 0;
+        }
     }
+    streak
+}
+
+/// Splits the time frame into the periods of the task, cut off at the
+/// boundaries of the time frame. The first day is the one the task counts from.
+pub fn get_periods(
+    period: &Period,
+    time_frame: &TimeFrame,
+    first_day: NaiveDate,
+) -> Vec<TimeFrame> {
+    let clip = |(start, end, _): (NaiveDate, NaiveDate, usize)| {
+        TimeFrame::new(start.max(time_frame.start), end.min(time_frame.end))
+    };
+    match period {
+        Period::Day | Period::EveryNDays(_) | Period::Weekdays(_) => time_frame
+            .start
+            .iter_days()
+            .take_while(|day| *day <= time_frame.end)
+            .filter(|day| period.is_due(*day, first_day))
+            .map(|day| TimeFrame::new(day, day))
+            .collect(),
+        Period::Week => get_week_day_counts(&time_frame.start, &time_frame.end)
+            .into_iter()
+            .map(clip)
+            .collect(),
+        Period::Month => get_month_day_counts(&time_frame.start, &time_frame.end)
+            .into_iter()
+            .map(clip)
+            .collect(),
+        Period::OneTime => vec![time_frame.clone()],
+    }
+}
+
+/// How a task is doing in the period which is currently running, e.g. the week so far.
+#[derive(Debug, PartialEq)]
+pub struct Forecast {
+    /// The check-ins, or the summed amount for quantitative tasks, in the period so far.
+    pub done: f64,
+    /// What the period needs for 100%. For things to avoid, this is the budget.
+    pub required: f64,
+    /// The days left in the period, including today.
+    pub remaining_days: i64,
+    pub period_end: NaiveDate,
+}
+
+impl Forecast {
+    /// What still has to be done, or for things to avoid, what is left of the budget.
+    pub fn remaining(&self) -> f64 {
+        (self.required - self.done).max(0.0)
+    }
+}
+
+/// Projects the period of the task which contains today onto the time frame.
+/// Periods which are only partly inside the time frame need proportionally less,
+/// just like when they are scored.
+/// None if today is outside of the time frame or the task is not due today.
+pub fn get_forecast(
+    task: &TaskData,
+    done_amounts: &[(NaiveDate, f64)],
+    time_frame: &TimeFrame,
+    first_day: NaiveDate,
+    today: NaiveDate,
+) -> Option<Forecast> {
+    if today < time_frame.start || today > time_frame.end {
+        return None;
+    }
+    let contains_today =
+        |(start, end, _): &(NaiveDate, NaiveDate, usize)| *start <= today && today <= *end;
+    let count = task.count as f64;
+    let (start, end, required) = match &task.period {
+        Period::Week => {
+            let (week_start, week_end, days_in_week) =
+                get_week_day_counts(&time_frame.start, &time_frame.end)
+                    .into_iter()
+                    .find(contains_today)?;
+            (week_start, week_end, count * days_in_week as f64 / 7.0)
+        }
+        Period::Month => {
+            let (month_start, month_end, days_in_month) =
+                get_month_day_counts(&time_frame.start, &time_frame.end)
+                    .into_iter()
+                    .find(contains_today)?;
+            let total_days_in_month = (month_end - month_start).num_days() + 1;
+            (
+                month_start,
+                month_end,
+                count * days_in_month as f64 / total_days_in_month as f64,
+            )
+        }
+        Period::OneTime => (time_frame.start, time_frame.end, count),
+        Period::Day | Period::EveryNDays(_) | Period::Weekdays(_) => {
+            if !task.period.is_due(today, first_day) {
+                return None;
+            }
+            (today, today, count)
+        }
+    };
+    let start = start.max(time_frame.start);
+    let end = end.min(time_frame.end);
+    Some(Forecast {
+        done: sum_amounts_in_range(done_amounts, &start, &end),
+        required,
+        remaining_days: (end - today).num_days() + 1,
+        period_end: end,
+    })
+}
+
+/// For things to avoid, every period in which the slips stay within the budget counts as fully done.
+/// Periods which are only partly inside the time frame get a proportionally smaller budget.
+/// Going over the budget reduces the fraction of that period to budget / slips.
+fn get_done_fraction_negative(
+    count: i32,
+    period: &Period,
+    slips: &[(NaiveDate, f64)],
+    time_frame: &TimeFrame,
+    first_day: NaiveDate,
+) -> f64 {
+    let fractions: Vec<f64> = get_periods(period, time_frame, first_day)
+        .iter()
+        .map(|part| {
+            let days_in_part = (part.end - part.start).num_days() + 1;
+            let days_in_period = match period {
+                Period::Week => 7,
+                Period::Month => end_of_month(&part.start).day() as i64,
+                _ => days_in_part,
+            };
+            let allowed = count as f64 * days_in_part as f64 / days_in_period as f64;
+            let slipped = sum_amounts_in_range(slips, &part.start, &part.end);
+            if slipped <= allowed {
+                1.0
+            } else {
+                allowed / slipped
+            }
+        })
+        .collect();
     average(&fractions).unwrap_or(1.0)
 }
 
-fn get_done_fraction_monthly(
+/// Every day of the time frame on which the task is due counts on its own.
+/// A day can have more than one check-in for tasks which have to be done several times a day.
+fn get_progress_daily(
     count: i32,
-    done_timestamps: &[NaiveDate],
+    period: &Period,
+    done_amounts: &[(NaiveDate, f64)],
     start_date: &NaiveDate,
     end_date: &NaiveDate,
-) -> f64 {
-    let mut fractions = vec![];
-    let month_day_counts = get_month_day_counts(&start_date, &end_date);
-    let refs = Box::new(done_timestamps.iter().map(move |x| *x));
-    for (month_start, month_end, days_in_month) in month_day_counts.iter() {
-        let done_count = count_days_in_range(refs.clone(), month_start, month_end);
-        let done_count = done_count.min(count as usize) as f64;
-        let total_days_in_month = (*month_end - *month_start).num_days() + 1;
-        let should_have_done_count =
-            (count as f64 * (*days_in_month as f64 / total_days_in_month as f64)).floor() as f64;
-        if should_have_done_count == 0.0 {
-            fractions.push(1.0);
-            continue;
-        }
-        fractions.push(done_count / should_have_done_count);
-    }
-    average(&fractions).unwrap_or(1.0)
+    first_day: NaiveDate,
+) -> Vec<PeriodProgress> {
+    start_date
+        .iter_days()
+        .take_while(|day| day <= end_date)
+        .filter(|day| period.is_due(*day, first_day))
+        .map(|day| PeriodProgress {
+            done: sum_amounts_in_range(done_amounts, &day, &day),
+            target: count as f64,
+            count: count as f64,
+        })
+        .collect()
 }
 
-fn get_done_fraction_onetime(
+fn get_progress_weekly(
     count: i32,
-    done_timestamps: &[NaiveDate],
+    done_amounts: &[(NaiveDate, f64)],
     start_date: &NaiveDate,
     end_date: &NaiveDate,
-) -> f64 {
-    let done_count = count_days_in_range(
-        Box::new(done_timestamps.iter().map(|date| date.clone())),
-        start_date,
-        end_date,
-    );
-    (done_count as f64 / count as f64).min(1.0)
+) -> Vec<PeriodProgress> {
+    get_week_day_counts(&start_date, &end_date)
+        .iter()
+        .map(|(week_start, week_end, days_in_week)| PeriodProgress {
+            done: sum_amounts_in_range(done_amounts, week_start, week_end),
+            target: count as f64 * (*days_in_week as f64 / 7.0),
+            count: count as f64,
+        })
+        .collect()
+}
+
+fn get_progress_monthly(
+    count: i32,
+    done_amounts: &[(NaiveDate, f64)],
+    start_date: &NaiveDate,
+    end_date: &NaiveDate,
+) -> Vec<PeriodProgress> {
+    get_month_day_counts(&start_date, &end_date)
+        .iter()
+        .map(|(month_start, month_end, days_in_month)| {
+            let total_days_in_month = (*month_end - *month_start).num_days() + 1;
+            PeriodProgress {
+                done: sum_amounts_in_range(done_amounts, month_start, month_end),
+                target: count as f64 * (*days_in_month as f64 / total_days_in_month as f64),
+                count: count as f64,
+            }
+        })
+        .collect()
+}
+
+fn get_progress_onetime(
+    count: i32,
+    done_amounts: &[(NaiveDate, f64)],
+    start_date: &NaiveDate,
+    end_date: &NaiveDate,
+) -> Vec<PeriodProgress> {
+    vec![PeriodProgress {
+        done: sum_amounts_in_range(done_amounts, start_date, end_date),
+        target: count as f64,
+        count: count as f64,
+    }]
 }
 
 fn average(numbers: &[f64]) -> Option<f64> {
@@ -193,6 +487,18 @@ fn get_month_day_count(
     )
 }
 
+fn sum_amounts_in_range(
+    done_amounts: &[(NaiveDate, f64)],
+    start_date: &NaiveDate,
+    end_date: &NaiveDate,
+) -> f64 {
+    done_amounts
+        .iter()
+        .filter(|(day, _)| start_date <= day && day <= end_date)
+        .map(|(_, amount)| amount)
+        .sum()
+}
+
 fn count_days_in_range<'a>(
     days: Box<dyn Iterator<Item = NaiveDate> + 'a>,
     start_date: &NaiveDate,
@@ -207,11 +513,18 @@ mod tests {
     use chrono::NaiveDate;
 
     use crate::{
-        database::{period::Period, task_data::TaskData},
+        database::{pause::Pause, period::Period, task_data::TaskData, task_kind::TaskKind},
+        scoring_strategy::{
+            AllOrNothingScoring, DefaultScoring, OverachievementBonusScoring, RawRatioScoring,
+        },
         time_frame::TimeFrame,
     };
 
-    use super::{get_done_fraction, get_month_day_count, get_week_day_count, get_week_day_counts};
+    use super::{
+        get_done_fraction, get_done_fraction_for_amounts, get_done_fraction_with_pauses,
+        get_forecast, get_month_day_count, get_periods, get_scored_fraction, get_streak,
+        get_week_day_count, get_week_day_counts, split_time_frame, Forecast, Streak,
+    };
 
     #[test]
     fn weekly() {
@@ -223,6 +536,9 @@ mod tests {
             name: "".into(),
             count: 7,
             period: Period::Week,
+            unit: None,
+            kind: TaskKind::Positive,
+            weight: 1.0,
         };
         let timestamps = &[timeframe.start, timeframe.end];
         let fraction = get_done_fraction(&task_data, timestamps, &timeframe);
@@ -262,6 +578,9 @@ mod tests {
             name: "".into(),
             count: 1,
             period: Period::Week,
+            unit: None,
+            kind: TaskKind::Positive,
+            weight: 1.0,
         };
         let fraction = get_done_fraction(&task_data, timestamps, &timeframe);
         assert_eq!(fraction, (1.0 + 1.0) / 2.0);
@@ -277,6 +596,9 @@ mod tests {
             name: "".into(),
             count: 1,
             period: Period::Month,
+            unit: None,
+            kind: TaskKind::Positive,
+            weight: 1.0,
         };
         let timestamps = &[
             NaiveDate::from_ymd(1970, 01, 01),
@@ -402,6 +724,9 @@ mod tests {
             name: "".into(),
             count: 2,
             period: Period::OneTime,
+            unit: None,
+            kind: TaskKind::Positive,
+            weight: 1.0,
         };
         let timestamps = &[];
         let fraction = get_done_fraction(&task_data, timestamps, &timeframe);
@@ -429,4 +754,590 @@ mod tests {
         let fraction = get_done_fraction(&task_data, timestamps, &timeframe);
         assert_eq!(fraction, 0.5);
     }
+
+    #[test]
+    fn test_get_periods() {
+        let timeframe = TimeFrame::new(
+            NaiveDate::from_ymd(1970, 1, 1),
+            NaiveDate::from_ymd(1970, 2, 10),
+        );
+        let weeks = get_periods(&Period::Week, &timeframe, timeframe.start);
+        assert_eq!(weeks.len(), 7);
+        assert_eq!(weeks[0].start, timeframe.start);
+        assert_eq!(weeks[0].end, NaiveDate::from_ymd(1970, 1, 4));
+        assert_eq!(weeks[1].start, NaiveDate::from_ymd(1970, 1, 5));
+        assert_eq!(weeks[6].end, timeframe.end);
+        let months = get_periods(&Period::Month, &timeframe, timeframe.start);
+        assert_eq!(months.len(), 2);
+        assert_eq!(months[0].end, NaiveDate::from_ymd(1970, 1, 31));
+        assert_eq!(months[1].start, NaiveDate::from_ymd(1970, 2, 1));
+        assert_eq!(months[1].end, timeframe.end);
+        assert_eq!(
+            get_periods(&Period::Day, &timeframe, timeframe.start).len(),
+            41
+        );
+        assert_eq!(
+            get_periods(&Period::OneTime, &timeframe, timeframe.start).len(),
+            1
+        );
+    }
+
+    #[test]
+    fn daily() {
+        let timeframe = TimeFrame::new(
+            NaiveDate::from_ymd(1970, 1, 1),
+            NaiveDate::from_ymd(1970, 1, 4),
+        );
+        let task_data = TaskData {
+            name: "".into(),
+            count: 1,
+            period: Period::Day,
+            unit: None,
+            kind: TaskKind::Positive,
+            weight: 1.0,
+        };
+        let timestamps = &[
+            NaiveDate::from_ymd(1969, 12, 31), // Outside of the time frame
+            NaiveDate::from_ymd(1970, 1, 1),
+            NaiveDate::from_ymd(1970, 1, 3),
+            NaiveDate::from_ymd(1970, 1, 3), // Doing it twice doesnt make up for another day
+        ];
+        let fraction = get_done_fraction(&task_data, timestamps, &timeframe);
+        assert_eq!(fraction, 2.0 / 4.0);
+        // Three times a day
+        let task_data = TaskData {
+            name: "".into(),
+            count: 3,
+            period: Period::Day,
+            unit: None,
+            kind: TaskKind::Positive,
+            weight: 1.0,
+        };
+        let timestamps = &[
+            NaiveDate::from_ymd(1970, 1, 1),
+            NaiveDate::from_ymd(1970, 1, 1),
+            NaiveDate::from_ymd(1970, 1, 1),
+            NaiveDate::from_ymd(1970, 1, 1),
+            NaiveDate::from_ymd(1970, 1, 2),
+            NaiveDate::from_ymd(1970, 1, 4),
+            NaiveDate::from_ymd(1970, 1, 4),
+        ];
+        let fraction = get_done_fraction(&task_data, timestamps, &timeframe);
+        assert_eq!(fraction, (1.0 + 1.0 / 3.0 + 0.0 + 2.0 / 3.0) / 4.0);
+        // A single day at the boundary of a challenge is scored on its own
+        let last_day = TimeFrame::new(timeframe.end, timeframe.end);
+        let fraction = get_done_fraction(&task_data, timestamps, &last_day);
+        assert_eq!(fraction, 2.0 / 3.0);
+    }
+
+    #[test]
+    fn recurring() {
+        // 1970-01-05 is a Monday
+        let timeframe = TimeFrame::new(
+            NaiveDate::from_ymd(1970, 1, 5),
+            NaiveDate::from_ymd(1970, 1, 18),
+        );
+        let task_data = TaskData {
+            name: "".into(),
+            count: 1,
+            period: "mon,wed,fri".parse().unwrap(),
+            unit: None,
+            kind: TaskKind::Positive,
+            weight: 1.0,
+        };
+        let timestamps = &[
+            NaiveDate::from_ymd(1970, 1, 5),
+            NaiveDate::from_ymd(1970, 1, 6), // Not due, doesnt count
+            NaiveDate::from_ymd(1970, 1, 7),
+            NaiveDate::from_ymd(1970, 1, 16),
+        ];
+        let fraction = get_done_fraction(&task_data, timestamps, &timeframe);
+        assert_eq!(fraction, 3.0 / 6.0);
+        assert_eq!(
+            get_periods(&task_data.period, &timeframe, timeframe.start).len(),
+            6
+        );
+        let task_data = TaskData {
+            name: "".into(),
+            count: 1,
+            period: Period::EveryNDays(7),
+            unit: None,
+            kind: TaskKind::Positive,
+            weight: 1.0,
+        };
+        // Due on the first day and a week later
+        let timestamps = &[
+            NaiveDate::from_ymd(1970, 1, 5),
+            NaiveDate::from_ymd(1970, 1, 8), // Not due, doesnt count
+        ];
+        let fraction = get_done_fraction(&task_data, timestamps, &timeframe);
+        assert_eq!(fraction, 1.0 / 2.0);
+        // No due day in the part of the time frame which is looked at
+        let part = TimeFrame::new(
+            NaiveDate::from_ymd(1970, 1, 6),
+            NaiveDate::from_ymd(1970, 1, 11),
+        );
+        assert_eq!(
+            get_scored_fraction(&task_data, &[], &part, timeframe.start, &DefaultScoring),
+            1.0
+        );
+    }
+
+    #[test]
+    fn amounts() {
+        // 1970-01-05 is a Monday
+        let timeframe = TimeFrame::new(
+            NaiveDate::from_ymd(1970, 1, 5),
+            NaiveDate::from_ymd(1970, 1, 18),
+        );
+        let task_data = TaskData {
+            name: "run".into(),
+            count: 20,
+            period: Period::Week,
+            unit: Some("km".into()),
+            kind: TaskKind::Positive,
+            weight: 1.0,
+        };
+        let amounts = &[
+            (NaiveDate::from_ymd(1970, 1, 5), 5.0),
+            (NaiveDate::from_ymd(1970, 1, 7), 10.0),
+            (NaiveDate::from_ymd(1970, 1, 12), 15.0),
+            (NaiveDate::from_ymd(1970, 1, 14), 10.5),
+        ];
+        let fraction = get_done_fraction_for_amounts(&task_data, amounts, &timeframe);
+        // Overachieving in the second week does not make up for the first one
+        assert_eq!(fraction, (15.0 / 20.0 + 1.0) / 2.0);
+        let task_data = TaskData {
+            name: "read".into(),
+            count: 10,
+            period: Period::Day,
+            unit: Some("pages".into()),
+            kind: TaskKind::Positive,
+            weight: 1.0,
+        };
+        let timeframe = TimeFrame::new(
+            NaiveDate::from_ymd(1970, 1, 5),
+            NaiveDate::from_ymd(1970, 1, 6),
+        );
+        let amounts = &[
+            (NaiveDate::from_ymd(1970, 1, 5), 2.5),
+            (NaiveDate::from_ymd(1970, 1, 5), 2.5),
+        ];
+        let fraction = get_done_fraction_for_amounts(&task_data, amounts, &timeframe);
+        assert_eq!(fraction, (5.0 / 10.0 + 0.0) / 2.0);
+    }
+
+    #[test]
+    fn negative() {
+        // 1970-01-05 is a Monday
+        let timeframe = TimeFrame::new(
+            NaiveDate::from_ymd(1970, 1, 5),
+            NaiveDate::from_ymd(1970, 1, 21),
+        );
+        let task_data = TaskData {
+            name: "sugar".into(),
+            count: 2,
+            period: Period::Week,
+            unit: None,
+            kind: TaskKind::Negative,
+            weight: 1.0,
+        };
+        assert_eq!(get_done_fraction(&task_data, &[], &timeframe), 1.0);
+        let timestamps = &[
+            // Within the budget in the first week
+            NaiveDate::from_ymd(1970, 1, 5),
+            NaiveDate::from_ymd(1970, 1, 6),
+            // Twice the budget in the second week
+            NaiveDate::from_ymd(1970, 1, 12),
+            NaiveDate::from_ymd(1970, 1, 13),
+            NaiveDate::from_ymd(1970, 1, 14),
+            NaiveDate::from_ymd(1970, 1, 15),
+            // Three of seven days in the last week give a budget of 6/7
+            NaiveDate::from_ymd(1970, 1, 19),
+        ];
+        let fraction = get_done_fraction(&task_data, timestamps, &timeframe);
+        assert_eq!(fraction, (1.0 + 0.5 + 6.0 / 7.0) / 3.0);
+        let task_data = TaskData {
+            name: "sugar".into(),
+            count: 0,
+            period: Period::Day,
+            unit: None,
+            kind: TaskKind::Negative,
+            weight: 1.0,
+        };
+        let fraction = get_done_fraction(&task_data, timestamps, &timeframe);
+        assert_eq!(fraction, 10.0 / 17.0);
+    }
+
+    #[test]
+    fn streaks() {
+        // 1970-01-05 is a Monday
+        let timeframe = TimeFrame::new(
+            NaiveDate::from_ymd(1970, 1, 5),
+            NaiveDate::from_ymd(1970, 2, 1),
+        );
+        let task_data = TaskData {
+            name: "gym".into(),
+            count: 2,
+            period: Period::Week,
+            unit: None,
+            kind: TaskKind::Positive,
+            weight: 1.0,
+        };
+        let done = |days: &[u32]| -> Vec<(NaiveDate, f64)> {
+            days.iter()
+                .map(|day| (NaiveDate::from_ymd(1970, 1, *day), 1.0))
+                .collect()
+        };
+        // Done in the first two weeks, missed in the third, done in the fourth
+        let amounts = done(&[5, 6, 12, 18, 20, 26, 27]);
+        let today = NaiveDate::from_ymd(1970, 2, 1);
+        assert_eq!(
+            get_streak(
+                &task_data,
+                &amounts,
+                &timeframe,
+                timeframe.start,
+                &[],
+                today
+            ),
+            Streak {
+                current: 1,
+                longest: 2
+            }
+        );
+        // The third week is still running and has not been done yet, which does not break the streak
+        let today = NaiveDate::from_ymd(1970, 1, 20);
+        assert_eq!(
+            get_streak(
+                &task_data,
+                &amounts,
+                &timeframe,
+                timeframe.start,
+                &[],
+                today
+            ),
+            Streak {
+                current: 2,
+                longest: 2
+            }
+        );
+        // Before the challenge there is no streak
+        let today = NaiveDate::from_ymd(1970, 1, 1);
+        assert_eq!(
+            get_streak(
+                &task_data,
+                &amounts,
+                &timeframe,
+                timeframe.start,
+                &[],
+                today
+            ),
+            Streak::default()
+        );
+        let task_data = TaskData {
+            name: "sugar".into(),
+            count: 0,
+            period: Period::Day,
+            unit: None,
+            kind: TaskKind::Negative,
+            weight: 1.0,
+        };
+        let today = NaiveDate::from_ymd(1970, 1, 10);
+        assert_eq!(
+            get_streak(
+                &task_data,
+                &done(&[7]),
+                &timeframe,
+                timeframe.start,
+                &[],
+                today
+            ),
+            Streak {
+                current: 3,
+                longest: 3
+            }
+        );
+    }
+
+    #[test]
+    fn pauses() {
+        let timeframe = TimeFrame::new(
+            NaiveDate::from_ymd(1970, 1, 5),
+            NaiveDate::from_ymd(1970, 1, 14),
+        );
+        let task_data = TaskData {
+            name: "gym".into(),
+            count: 1,
+            period: Period::Day,
+            unit: None,
+            kind: TaskKind::Positive,
+            weight: 1.0,
+        };
+        let pause = |start: u32, end: Option<u32>| Pause {
+            id: 0,
+            start: NaiveDate::from_ymd(1970, 1, start),
+            end: end.map(|end| NaiveDate::from_ymd(1970, 1, end)),
+        };
+        let amounts: Vec<(NaiveDate, f64)> = [5, 6, 9]
+            .iter()
+            .map(|day| (NaiveDate::from_ymd(1970, 1, *day), 1.0))
+            .collect();
+        let pauses = [pause(8, Some(10))];
+        let parts = split_time_frame(&timeframe, |day| pauses[0].contains(day));
+        assert_eq!(parts.len(), 2);
+        assert_eq!(
+            (parts[0].start, parts[0].end),
+            (timeframe.start, NaiveDate::from_ymd(1970, 1, 7))
+        );
+        assert_eq!(
+            (parts[1].start, parts[1].end),
+            (NaiveDate::from_ymd(1970, 1, 11), timeframe.end)
+        );
+        // The check-in on a paused day does not count
+        assert_eq!(
+            get_done_fraction_with_pauses(
+                &task_data,
+                &amounts,
+                &timeframe,
+                timeframe.start,
+                &pauses,
+                &DefaultScoring
+            ),
+            Some(2.0 / 7.0)
+        );
+        // Paused until further notice
+        let pauses = [pause(8, Some(10)), pause(12, None)];
+        assert_eq!(
+            get_done_fraction_with_pauses(
+                &task_data,
+                &amounts,
+                &timeframe,
+                timeframe.start,
+                &pauses,
+                &DefaultScoring
+            ),
+            Some(2.0 / 4.0)
+        );
+        let pauses = [pause(1, None)];
+        assert_eq!(
+            get_done_fraction_with_pauses(
+                &task_data,
+                &amounts,
+                &timeframe,
+                timeframe.start,
+                &pauses,
+                &DefaultScoring
+            ),
+            None
+        );
+        // Paused days do not break a streak
+        let amounts: Vec<(NaiveDate, f64)> = [5, 6, 11, 12]
+            .iter()
+            .map(|day| (NaiveDate::from_ymd(1970, 1, *day), 1.0))
+            .collect();
+        let today = NaiveDate::from_ymd(1970, 1, 12);
+        assert_eq!(
+            get_streak(
+                &task_data,
+                &amounts,
+                &timeframe,
+                timeframe.start,
+                &[pause(7, Some(10))],
+                today
+            ),
+            Streak {
+                current: 4,
+                longest: 4
+            }
+        );
+        assert_eq!(
+            get_streak(
+                &task_data,
+                &amounts,
+                &timeframe,
+                timeframe.start,
+                &[],
+                today
+            ),
+            Streak {
+                current: 2,
+                longest: 2
+            }
+        );
+    }
+
+    #[test]
+    fn scoring() {
+        // 1970-01-05 is a Monday
+        let timeframe = TimeFrame::new(
+            NaiveDate::from_ymd(1970, 1, 5),
+            NaiveDate::from_ymd(1970, 1, 18),
+        );
+        let task_data = TaskData {
+            name: "gym".into(),
+            count: 2,
+            period: Period::Week,
+            unit: None,
+            kind: TaskKind::Positive,
+            weight: 1.0,
+        };
+        // Three times in the first week, once in the second
+        let amounts: Vec<(NaiveDate, f64)> = [5, 6, 7, 12]
+            .iter()
+            .map(|day| (NaiveDate::from_ymd(1970, 1, *day), 1.0))
+            .collect();
+        let score = |scoring| {
+            get_scored_fraction(&task_data, &amounts, &timeframe, timeframe.start, scoring)
+        };
+        assert_eq!(score(&DefaultScoring), (1.0 + 0.5) / 2.0);
+        assert_eq!(score(&RawRatioScoring), 1.0);
+        assert_eq!(score(&OverachievementBonusScoring), (1.2 + 0.5) / 2.0);
+        assert_eq!(score(&AllOrNothingScoring), 0.5);
+    }
+
+    #[test]
+    fn forecast_budget() {
+        // 1970-01-05 is a Monday, the challenge starts on a Wednesday
+        let timeframe = TimeFrame::new(
+            NaiveDate::from_ymd(1970, 1, 7),
+            NaiveDate::from_ymd(1970, 1, 31),
+        );
+        let task_data = TaskData {
+            name: "beer".into(),
+            count: 7,
+            period: Period::Week,
+            unit: None,
+            kind: TaskKind::Negative,
+            weight: 1.0,
+        };
+        let first_week = TimeFrame::new(timeframe.start, NaiveDate::from_ymd(1970, 1, 11));
+        let slips = |count: u32| -> Vec<(NaiveDate, f64)> {
+            (0..count)
+                .map(|_| (NaiveDate::from_ymd(1970, 1, 8), 1.0))
+                .collect()
+        };
+        // The budget of the first week is scaled down to its five days, like when it is scored
+        let forecast = get_forecast(
+            &task_data,
+            &slips(5),
+            &timeframe,
+            timeframe.start,
+            NaiveDate::from_ymd(1970, 1, 9),
+        )
+        .unwrap();
+        assert_eq!((forecast.done, forecast.required), (5.0, 5.0));
+        let score = |count| {
+            get_scored_fraction(
+                &task_data,
+                &slips(count),
+                &first_week,
+                timeframe.start,
+                &DefaultScoring,
+            )
+        };
+        assert_eq!(score(5), 1.0);
+        assert_eq!(score(6), 5.0 / 6.0);
+    }
+
+    #[test]
+    fn forecast() {
+        // 1970-01-05 is a Monday, the challenge starts on a Wednesday
+        let timeframe = TimeFrame::new(
+            NaiveDate::from_ymd(1970, 1, 7),
+            NaiveDate::from_ymd(1970, 1, 31),
+        );
+        let task_data = TaskData {
+            name: "gym".into(),
+            count: 7,
+            period: Period::Week,
+            unit: None,
+            kind: TaskKind::Positive,
+            weight: 1.0,
+        };
+        let amounts: Vec<(NaiveDate, f64)> = [8, 9, 12]
+            .iter()
+            .map(|day| (NaiveDate::from_ymd(1970, 1, *day), 1.0))
+            .collect();
+        // Only five days of the first week are in the challenge
+        assert_eq!(
+            get_forecast(
+                &task_data,
+                &amounts,
+                &timeframe,
+                timeframe.start,
+                NaiveDate::from_ymd(1970, 1, 9)
+            ),
+            Some(Forecast {
+                done: 2.0,
+                required: 5.0,
+                remaining_days: 3,
+                period_end: NaiveDate::from_ymd(1970, 1, 11),
+            })
+        );
+        let forecast = get_forecast(
+            &task_data,
+            &amounts,
+            &timeframe,
+            timeframe.start,
+            NaiveDate::from_ymd(1970, 1, 14),
+        )
+        .unwrap();
+        assert_eq!(forecast.done, 1.0);
+        assert_eq!(forecast.remaining(), 6.0);
+        assert_eq!(forecast.remaining_days, 5);
+        assert_eq!(
+            get_forecast(
+                &task_data,
+                &amounts,
+                &timeframe,
+                timeframe.start,
+                NaiveDate::from_ymd(1970, 2, 1)
+            ),
+            None
+        );
+        let task_data = TaskData {
+            period: Period::Month,
+            count: 31,
+            ..task_data
+        };
+        let forecast = get_forecast(
+            &task_data,
+            &amounts,
+            &timeframe,
+            timeframe.start,
+            NaiveDate::from_ymd(1970, 1, 20),
+        )
+        .unwrap();
+        assert_eq!(forecast.required, 25.0);
+        assert_eq!(forecast.remaining(), 22.0);
+        assert_eq!(forecast.remaining_days, 12);
+        let task_data = TaskData {
+            period: Period::Weekdays(vec![chrono::Weekday::Mon]),
+            count: 1,
+            ..task_data
+        };
+        assert_eq!(
+            get_forecast(
+                &task_data,
+                &amounts,
+                &timeframe,
+                timeframe.start,
+                NaiveDate::from_ymd(1970, 1, 13)
+            ),
+            None
+        );
+        assert_eq!(
+            get_forecast(
+                &task_data,
+                &amounts,
+                &timeframe,
+                timeframe.start,
+                NaiveDate::from_ymd(1970, 1, 12)
+            )
+            .unwrap()
+            .remaining(),
+            0.0
+        );
+    }
 }
@@ -0,0 +1,42 @@
+use anyhow::{anyhow, Result};
+use std::fmt;
+use std::str::FromStr;
+
+/// The data attached to the inline keyboard buttons sent by the bot.
+#[derive(Debug, PartialEq)]
+pub enum CallbackData {
+    SubscribeToChallenge(i32),
+    ConfirmUserDeletion(i32),
+    CancelUserDeletion,
+}
+
+impl FromStr for CallbackData {
+    type Err = anyhow::Error;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parts = s.splitn(2, ':');
+        match (parts.next(), parts.next()) {
+            // Subscription prompts used to contain only the challenge id
+            (Some(challenge_id), None) if challenge_id.parse::<i32>().is_ok() => {
+                Ok(Self::SubscribeToChallenge(challenge_id.parse()?))
+            }
+            (Some("subscribe"), Some(challenge_id)) => {
+                Ok(Self::SubscribeToChallenge(challenge_id.parse()?))
+            }
+            (Some("deleteme"), Some(user_id)) => Ok(Self::ConfirmUserDeletion(user_id.parse()?)),
+            (Some("cancel"), None) => Ok(Self::CancelUserDeletion),
+            _ => Err(anyhow!("Unknown callback data: {}", s)),
+        }
+    }
+}
+
+impl fmt::Display for CallbackData {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CallbackData::SubscribeToChallenge(challenge_id) => {
+                write!(f, "subscribe:{}", challenge_id)
+            }
+            CallbackData::ConfirmUserDeletion(user_id) => write!(f, "deleteme:{}", user_id),
+            CallbackData::CancelUserDeletion => write!(f, "cancel"),
+        }
+    }
+}
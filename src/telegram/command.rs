@@ -1,25 +1,66 @@
-use chrono::NaiveDate;
-use teloxide::utils::command::BotCommand;
+use anyhow::anyhow;
+use chrono::{NaiveDate, NaiveTime};
+use chrono_tz::Tz;
+use std::str::FromStr;
+use teloxide::utils::command::{BotCommand, ParseError};
 
-use crate::database::period::Period;
+use crate::{
+    database::{
+        period::Period,
+        scoring::Scoring,
+        task_data::TaskTarget,
+        update_schedule::{parse_time, UpdateSchedule},
+    },
+    report_window::ReportWindow,
+};
 
 #[derive(BotCommand)]
 #[command(rename = "lowercase", description = "These commands are supported:")]
 pub enum Command {
     #[command(description = "Display help text.")]
     Help,
-    #[command(description = "Create a new challenge", parse_with = "split")]
+    #[command(
+        description = "Create a new challenge. Optionally followed by how to score it: default, ratio (everything done compared to the total target), bonus (up to 120% for doing more) or allornothing (only fully done periods count)",
+        parse_with = "parse_create_challenge"
+    )]
     CreateNewChallenge {
         name: String,
         start: NaiveDate,
         end: NaiveDate,
+        scoring: Option<Scoring>,
     },
-    #[command(description = "Add a new task", parse_with = "split")]
+    #[command(
+        description = "Add a new task. The count can have a unit (20km), max2 makes it something to avoid. The period is d, w, m, o, every few days (3d) or weekdays (mon,wed,fri or weekends). Optionally followed by a weight",
+        parse_with = "parse_add_task"
+    )]
     AddTask {
         challenge_name: String,
         task_name: String,
-        count: i32,
+        count: TaskTarget,
         period: Period,
+        weight: Option<f64>,
+    },
+    #[command(description = "Change how much a task counts", parse_with = "split")]
+    Weight {
+        challenge_name: String,
+        task_name: String,
+        weight: f64,
+    },
+    #[command(
+        description = "Allow or forbid weights in a challenge you created (yes or no)",
+        parse_with = "split"
+    )]
+    AllowWeights {
+        challenge_name: String,
+        allowed: YesNo,
+    },
+    #[command(
+        description = "Stop a task from today on. The days it was active still count",
+        parse_with = "split"
+    )]
+    Retire {
+        challenge_name: String,
+        task_name: String,
     },
     #[command(description = "Sign up for reminders", parse_with = "split")]
     Signup,
@@ -29,4 +70,277 @@ pub enum Command {
     )]
     SendPoll,
     SendUpdates,
+    #[command(
+        description = "Tell me how much you did of a task with a unit",
+        parse_with = "split"
+    )]
+    Amount {
+        amount: f64,
+    },
+    #[command(description = "List your challenges")]
+    Challenges,
+    #[command(description = "Show how everyone is doing in a challenge")]
+    Stats {
+        challenge_name: String,
+    },
+    #[command(
+        description = "Show how many days, weeks or months in a row everyone kept up their tasks"
+    )]
+    Streaks {
+        challenge_name: String,
+    },
+    #[command(
+        description = "Show how everyone did compared to before: thisweek, lastweek, thismonth, lastmonth, a number of days (30d) or a range (2021-01-01..2021-01-31). Optionally followed by a challenge",
+        parse_with = "parse_report"
+    )]
+    Report {
+        window: ReportWindow,
+        challenge_name: Option<String>,
+    },
+    #[command(
+        description = "Show how much of your tasks is left to do this week, month or day to reach 100%. Optionally followed by a challenge",
+        parse_with = "parse_forecast"
+    )]
+    Forecast {
+        challenge_name: Option<String>,
+    },
+    #[command(description = "Archive a finished challenge")]
+    Archive {
+        challenge_name: String,
+    },
+    #[command(
+        description = "Pause all your tasks, e.g. during holidays. Optionally give the first day (yesterday at the earliest) and the last day (YYYY-MM-DD), without a last day the pause lasts until you /resume",
+        parse_with = "parse_pause"
+    )]
+    Pause {
+        from: Option<NaiveDate>,
+        to: Option<NaiveDate>,
+    },
+    #[command(
+        description = "End your pause. Also brings the polls back if they could not be sent to you"
+    )]
+    Resume,
+    #[command(
+        description = "Set your timezone, such as Europe/Berlin, so that polls come in your evening. Without one, the time of the server is used",
+        parse_with = "parse_timezone"
+    )]
+    Timezone {
+        timezone: Option<Tz>,
+    },
+    #[command(
+        description = "Show your settings. Change the time of your poll with poll 23:30 (or poll default), or when a challenge you created gets its updates with update <challenge> daily 18:00, weekly sun 18:00 or off",
+        parse_with = "parse_settings"
+    )]
+    Settings {
+        change: Option<SettingChange>,
+    },
+    #[command(description = "Send me everything the bot stores about you")]
+    Export,
+    #[command(description = "Send the check-ins of everyone in a challenge as a CSV file")]
+    ExportCsv {
+        challenge_name: String,
+    },
+    #[command(description = "Delete everything the bot stores about you")]
+    DeleteMe,
+    #[command(description = "Restore the database from a backup (admins only)")]
+    Restore {
+        backup_date: String,
+    },
+}
+
+pub struct YesNo(pub bool);
+
+impl FromStr for YesNo {
+    type Err = anyhow::Error;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "yes" | "y" | "on" => Ok(YesNo(true)),
+            "no" | "n" | "off" => Ok(YesNo(false)),
+            _ => Err(anyhow!("Expected yes or no.")),
+        }
+    }
+}
+
+pub enum SettingChange {
+    /// None goes back to the usual time.
+    PollTime(Option<NaiveTime>),
+    UpdateSchedule(String, UpdateSchedule),
+}
+
+impl FromStr for SettingChange {
+    type Err = anyhow::Error;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let args: Vec<&str> = s.split_whitespace().collect();
+        match args.as_slice() {
+            ["poll", "default"] => Ok(SettingChange::PollTime(None)),
+            ["poll", time] => Ok(SettingChange::PollTime(Some(parse_time(time)?))),
+            ["update", challenge_name, schedule @ ..] if !schedule.is_empty() => {
+                Ok(SettingChange::UpdateSchedule(
+                    challenge_name.to_string(),
+                    schedule.join(" ").parse()?,
+                ))
+            }
+            _ => Err(anyhow!(
+                "Expected poll 23:30 or update <challenge> daily 18:00."
+            )),
+        }
+    }
+}
+
+/// Like the split parser, but the scoring at the end is optional.
+fn parse_create_challenge(
+    input: String,
+) -> Result<(String, NaiveDate, NaiveDate, Option<Scoring>), ParseError> {
+    let args: Vec<&str> = input.split_whitespace().collect();
+    if args.len() < 3 {
+        return Err(ParseError::TooFewArguments {
+            expected: 3,
+            found: args.len(),
+            message: "Expected a name, a start and an end date".to_owned(),
+        });
+    }
+    if args.len() > 4 {
+        return Err(ParseError::TooManyArguments {
+            expected: 4,
+            found: args.len(),
+            message: format!("Excess argument: {}", args[4]),
+        });
+    }
+    let scoring = match args.get(3) {
+        Some(scoring) => Some(
+            scoring
+                .parse::<Scoring>()
+                .map_err(|err| ParseError::IncorrectFormat(err.into()))?,
+        ),
+        None => None,
+    };
+    Ok((
+        args[0].to_owned(),
+        args[1]
+            .parse()
+            .map_err(|err: chrono::ParseError| ParseError::IncorrectFormat(err.into()))?,
+        args[2]
+            .parse()
+            .map_err(|err: chrono::ParseError| ParseError::IncorrectFormat(err.into()))?,
+        scoring,
+    ))
+}
+
+/// Like the split parser, but the weight at the end is optional.
+fn parse_add_task(
+    input: String,
+) -> Result<(String, String, TaskTarget, Period, Option<f64>), ParseError> {
+    let args: Vec<&str> = input.split_whitespace().collect();
+    if args.len() < 4 {
+        return Err(ParseError::TooFewArguments {
+            expected: 4,
+            found: args.len(),
+            message: "Expected a challenge, a task name, a count and a period".to_owned(),
+        });
+    }
+    if args.len() > 5 {
+        return Err(ParseError::TooManyArguments {
+            expected: 5,
+            found: args.len(),
+            message: format!("Excess argument: {}", args[5]),
+        });
+    }
+    let incorrect_format = |err: anyhow::Error| ParseError::IncorrectFormat(err.into());
+    let weight = match args.get(4) {
+        Some(weight) => Some(
+            weight
+                .parse::<f64>()
+                .map_err(|err| incorrect_format(err.into()))?,
+        ),
+        None => None,
+    };
+    Ok((
+        args[0].to_owned(),
+        args[1].to_owned(),
+        args[2].parse().map_err(incorrect_format)?,
+        args[3].parse().map_err(incorrect_format)?,
+        weight,
+    ))
+}
+
+/// Both dates of a pause are optional.
+fn parse_pause(input: String) -> Result<(Option<NaiveDate>, Option<NaiveDate>), ParseError> {
+    let args: Vec<&str> = input.split_whitespace().collect();
+    if args.len() > 2 {
+        return Err(ParseError::TooManyArguments {
+            expected: 2,
+            found: args.len(),
+            message: format!("Excess argument: {}", args[2]),
+        });
+    }
+    let dates = args
+        .iter()
+        .map(|arg| arg.parse::<NaiveDate>())
+        .collect::<Result<Vec<NaiveDate>, _>>()
+        .map_err(|err| ParseError::IncorrectFormat(err.into()))?;
+    Ok((dates.first().copied(), dates.get(1).copied()))
+}
+
+/// The window defaults to the last seven days, the challenge is optional.
+fn parse_report(input: String) -> Result<(ReportWindow, Option<String>), ParseError> {
+    let args: Vec<&str> = input.split_whitespace().collect();
+    if args.len() > 2 {
+        return Err(ParseError::TooManyArguments {
+            expected: 2,
+            found: args.len(),
+            message: format!("Excess argument: {}", args[2]),
+        });
+    }
+    let window = match args.first() {
+        Some(window) => window
+            .parse()
+            .map_err(|err: anyhow::Error| ParseError::IncorrectFormat(err.into()))?,
+        None => ReportWindow::LastDays(7),
+    };
+    Ok((window, args.get(1).map(|name| name.to_string())))
+}
+
+/// The challenge is optional.
+fn parse_forecast(input: String) -> Result<(Option<String>,), ParseError> {
+    let args: Vec<&str> = input.split_whitespace().collect();
+    if args.len() > 1 {
+        return Err(ParseError::TooManyArguments {
+            expected: 1,
+            found: args.len(),
+            message: format!("Excess argument: {}", args[1]),
+        });
+    }
+    Ok((args.first().map(|name| name.to_string()),))
+}
+
+/// The timezone is optional.
+fn parse_timezone(input: String) -> Result<(Option<Tz>,), ParseError> {
+    let input = input.trim();
+    if input.is_empty() {
+        return Ok((None,));
+    }
+    input
+        .parse::<Tz>()
+        .map(|timezone| (Some(timezone),))
+        .map_err(|_| {
+            ParseError::IncorrectFormat(
+                anyhow!(
+                    "Unknown timezone {}, expected something like Europe/Berlin.",
+                    input
+                )
+                .into(),
+            )
+        })
+}
+
+/// Without a change, the settings are shown.
+fn parse_settings(input: String) -> Result<(Option<SettingChange>,), ParseError> {
+    let input = input.trim();
+    if input.is_empty() {
+        return Ok((None,));
+    }
+    input
+        .parse()
+        .map(|change| (Some(change),))
+        .map_err(|err: anyhow::Error| ParseError::IncorrectFormat(err.into()))
 }
@@ -1,31 +1,43 @@
+pub mod callback_data;
 pub mod command;
 pub mod response_handling;
 
-use anyhow::Result;
+use anyhow::{anyhow, Result};
+use chrono::NaiveDate;
+use itertools::Itertools;
 
+use teloxide::prelude::*;
 use teloxide::types::{CallbackQuery, PollAnswer};
-use teloxide::{
-    prelude::*,
-    types::{InlineKeyboardButtonKind, MessageKind},
-};
 
 use self::{
-    command::Command,
+    callback_data::CallbackData,
+    command::{Command, SettingChange},
     response_handling::perform_reponse_to_callback_query,
     response_handling::{
         perform_reponse_to_poll_answer, perform_response_to_command, send_challenge_updates,
-        send_user_task_polls,
+        send_forecast_nudges, send_user_task_polls,
     },
 };
-use crate::{action_handling::perform_action, config};
+use crate::{
+    action_handling::perform_action,
+    clock::{Clock, SystemClock},
+    config,
+    database::{backup::BackupConfig, Database},
+};
 use crate::{
     database::{challenge_data::ChallengeData, task_data::TaskData},
     response::Response,
 };
 
-use crate::{action::Action, time_frame::TimeFrame};
+use crate::{
+    action::{Action, ActionOrigin},
+    time_frame::TimeFrame,
+};
 
-use std::sync::atomic::AtomicU64;
+use std::{
+    path::Path,
+    sync::{atomic::AtomicU64, Arc, Mutex},
+};
 
 use lazy_static::lazy_static;
 use tokio::{
@@ -37,109 +49,269 @@ lazy_static! {
     static ref MESSAGES_TOTAL: AtomicU64 = AtomicU64::new(0);
 }
 
+type SharedDatabase = Arc<Mutex<Database>>;
+
+fn perform(database: &SharedDatabase, origin: &ActionOrigin, action: &Action) -> Response {
+    let database = database.lock().unwrap();
+    perform_action(&*database, origin, action)
+}
+
 #[tokio::main]
 pub async fn run_bot() -> Result<()> {
     teloxide::enable_logging!();
     log::info!("Starting deshittify_bot...");
 
+    let clock: Arc<dyn Clock> = Arc::new(SystemClock);
+    let database = Arc::new(Mutex::new(Database::with_clock(
+        Path::new(config::DEFAULT_DB_PATH),
+        clock.clone(),
+    )?));
+
     let bot = Bot::from_env();
     let bot_name = "deshittify";
 
-    let user_task_poll_sender = user_task_polls_send_thread(Bot::from_env());
-    let challenge_status_update_sender = challenge_updates_send_thread(Bot::from_env());
+    let user_task_poll_sender = user_task_polls_send_thread(Bot::from_env(), database.clone());
+    let challenge_status_update_sender =
+        challenge_updates_send_thread(Bot::from_env(), database.clone());
+    let forecast_nudge_sender = forecast_nudges_send_thread(Bot::from_env(), database.clone());
+    let backup_config = BackupConfig::from_env();
+    let backup_taker = backup_thread(database.clone(), backup_config.clone(), clock.clone());
 
+    let command_database = database.clone();
+    let callback_query_database = database.clone();
+    let poll_database = database;
     let dispatcher = Dispatcher::new(bot)
         .messages_handler(move |rx: DispatcherHandlerRx<Message>| {
-            rx.commands(bot_name).for_each(|(cx, command)| async move {
-                handle_command(cx, command).await.log_on_error().await;
+            rx.commands(bot_name).for_each(move |(cx, command)| {
+                let database = command_database.clone();
+                let backup_config = backup_config.clone();
+                async move {
+                    handle_command(cx, command, &database, &backup_config)
+                        .await
+                        .log_on_error()
+                        .await;
+                }
             })
         })
         .callback_queries_handler(move |rx: DispatcherHandlerRx<CallbackQuery>| {
-            rx.for_each(|cx| async move {
-                handle_callback_query(cx).await.log_on_error().await;
+            rx.for_each(move |cx| {
+                let database = callback_query_database.clone();
+                async move {
+                    handle_callback_query(cx, &database)
+                        .await
+                        .log_on_error()
+                        .await;
+                }
             })
         })
         .poll_answers_handler(move |rx: DispatcherHandlerRx<PollAnswer>| {
-            rx.for_each(|cx| async move {
-                handle_poll(cx).await.log_on_error().await;
+            rx.for_each(move |cx| {
+                let database = poll_database.clone();
+                async move {
+                    handle_poll(cx, &database).await.log_on_error().await;
+                }
             })
         });
     let handler = dispatcher.dispatch();
 
-    let (res1, res2, _) = join!(
+    let (res1, res2, res3, res4, _) = join!(
         user_task_poll_sender,
         challenge_status_update_sender,
+        forecast_nudge_sender,
+        backup_taker,
         handler
     );
     res1?;
     res2?;
+    res3?;
+    res4?;
 
     Ok(())
 }
 
-async fn challenge_updates_send_thread(bot: Bot) -> Result<()> {
+async fn challenge_updates_send_thread(bot: Bot, database: SharedDatabase) -> Result<()> {
     loop {
-        let response = perform_action(&Action::CheckDateMaybeSendChallengeUpdates);
-        if let Response::ChallengeUpdates(user_task_data) = response {
-            let action = send_challenge_updates(&bot, &user_task_data).await?;
-            perform_action(&action);
+        let response = perform(
+            &database,
+            &ActionOrigin::default(),
+            &Action::CheckDateMaybeSendChallengeUpdates,
+        );
+        if let Response::ChallengeUpdates(update_data) = response {
+            for action in send_challenge_updates(&bot, &update_data).await? {
+                perform(&database, &ActionOrigin::default(), &action);
+            }
         }
         delay_for(Duration::from_secs(config::DATE_CHECK_TIMEOUT_SECS)).await;
     }
 }
 
-async fn user_task_polls_send_thread(bot: Bot) -> Result<()> {
+async fn forecast_nudges_send_thread(bot: Bot, database: SharedDatabase) -> Result<()> {
     loop {
-        let response = perform_action(&Action::CheckDateMaybeSendPolls);
+        let response = perform(
+            &database,
+            &ActionOrigin::default(),
+            &Action::CheckDateMaybeSendForecastNudges,
+        );
+        if let Response::ForecastNudges(nudge_data) = response {
+            for action in send_forecast_nudges(&bot, &nudge_data).await {
+                perform(&database, &ActionOrigin::default(), &action);
+            }
+        }
+        delay_for(Duration::from_secs(config::DATE_CHECK_TIMEOUT_SECS)).await;
+    }
+}
+
+async fn user_task_polls_send_thread(bot: Bot, database: SharedDatabase) -> Result<()> {
+    loop {
+        let response = perform(
+            &database,
+            &ActionOrigin::default(),
+            &Action::CheckDateMaybeSendPolls,
+        );
         if let Response::TaskPolls(user_task_data) = response {
-            let action = send_user_task_polls(&bot, &user_task_data).await?;
-            perform_action(&action);
+            for action in send_user_task_polls(&bot, &user_task_data).await {
+                perform(&database, &ActionOrigin::default(), &action);
+            }
         }
         delay_for(Duration::from_secs(config::DATE_CHECK_TIMEOUT_SECS)).await;
     }
 }
 
-async fn handle_command(message: UpdateWithCx<Message>, command: Command) -> Result<()> {
-    let action = convert_message_to_action(&message, command)
-        .unwrap_or_else(|err| Action::ErrorMessage(format!("Error: {}", err.to_string())));
-    let response = perform_action(&action);
-    let maybe_action = perform_response_to_command(&response, &message).await?;
-    if let Some(new_action) = maybe_action {
-        perform_action(&new_action);
+async fn backup_thread(
+    database: SharedDatabase,
+    backup_config: BackupConfig,
+    clock: Arc<dyn Clock>,
+) -> Result<()> {
+    loop {
+        if !clock.is_before_hour(config::HOUR_TO_BACKUP_AT) {
+            let result = database.lock().unwrap().backup_and_rotate(&backup_config);
+            match result {
+                Ok(Some(path)) => log::info!("Wrote backup to {}", path.display()),
+                Ok(None) => {}
+                Err(err) => log::error!("Backup failed: {:?}", err),
+            }
+        }
+        delay_for(Duration::from_secs(config::DATE_CHECK_TIMEOUT_SECS)).await;
+    }
+}
+
+async fn handle_command(
+    message: UpdateWithCx<Message>,
+    command: Command,
+    database: &SharedDatabase,
+    backup_config: &BackupConfig,
+) -> Result<()> {
+    let action = match command {
+        Command::Restore { backup_date } => {
+            restore_backup(&message, &backup_date, database, backup_config)
+        }
+        command => convert_message_to_action(&message, command),
+    }
+    .unwrap_or_else(|err| Action::ErrorMessage(format!("Error: {}", err.to_string())));
+    let origin = ActionOrigin {
+        user_id: message.update.from().map(|user| user.id),
+        chat_id: Some(message.update.chat.id),
+    };
+    let response = perform(database, &origin, &action);
+    for new_action in perform_response_to_command(&response, &message).await? {
+        perform(database, &origin, &new_action);
     }
     Ok(())
 }
 
-async fn handle_callback_query(message: UpdateWithCx<CallbackQuery>) -> Result<()> {
+async fn handle_callback_query(
+    message: UpdateWithCx<CallbackQuery>,
+    database: &SharedDatabase,
+) -> Result<()> {
     let action = convert_callback_query_to_action(&message)
         .unwrap_or_else(|err| Action::ErrorMessage(format!("Error: {}", err.to_string())));
-    let response = perform_action(&action);
+    let origin = ActionOrigin {
+        user_id: Some(message.update.from.id),
+        chat_id: message
+            .update
+            .message
+            .as_ref()
+            .map(|message| message.chat.id),
+    };
+    let response = perform(database, &origin, &action);
     perform_reponse_to_callback_query(&response, &message).await
 }
 
-async fn handle_poll(message: UpdateWithCx<PollAnswer>) -> Result<()> {
+async fn handle_poll(message: UpdateWithCx<PollAnswer>, database: &SharedDatabase) -> Result<()> {
     let action = convert_poll_to_action(&message)
         .unwrap_or_else(|err| Action::ErrorMessage(format!("Error: {}", err.to_string())));
-    let response = perform_action(&action);
+    let origin = ActionOrigin {
+        user_id: Some(message.update.user.id),
+        chat_id: None,
+    };
+    let response = perform(database, &origin, &action);
     perform_reponse_to_poll_answer(&response, &message).await
 }
 
+fn is_admin(user_id: i32) -> bool {
+    std::env::var(config::ADMINS_ENV_VAR)
+        .unwrap_or_default()
+        .split(',')
+        .any(|admin| admin.trim().parse() == Ok(user_id))
+}
+
+/// Restoring replaces the whole database, so it bypasses the usual actions
+/// and is only allowed for the admins listed in the environment.
+/// Without a date, the available backups are listed instead.
+fn restore_backup(
+    message: &UpdateWithCx<Message>,
+    backup_date: &str,
+    database: &SharedDatabase,
+    backup_config: &BackupConfig,
+) -> Result<Action> {
+    let user_id = message.update.from().unwrap().id;
+    if !is_admin(user_id) {
+        return Err(anyhow!("Only admins can restore backups."));
+    }
+    if backup_date.trim().is_empty() {
+        let backups = backup_config.list_backups()?;
+        return Ok(Action::Reply(format!(
+            "Available backups: {}",
+            backups.iter().map(|date| date.to_string()).join(", ")
+        )));
+    }
+    let date = NaiveDate::parse_from_str(backup_date.trim(), "%Y-%m-%d")?;
+    log::warn!("User {} restores the backup from {}", user_id, date);
+    database
+        .lock()
+        .unwrap()
+        .restore_backup(backup_config, date)?;
+    Ok(Action::Reply(format!("Restored the backup from {}.", date)))
+}
+
 fn convert_callback_query_to_action(message: &UpdateWithCx<CallbackQuery>) -> Result<Action> {
-    if let MessageKind::Common(x) = &message.update.message.as_ref().unwrap().kind {
-        if let InlineKeyboardButtonKind::CallbackData(data) =
-            &x.reply_markup.as_ref().unwrap().inline_keyboard[0][0].kind
-        {
-            let challenge_id: i32 = data.parse()?;
-            let user_id = message.update.from.id;
+    let data: CallbackData = message
+        .update
+        .data
+        .as_ref()
+        .ok_or_else(|| anyhow!("Callback query without data"))?
+        .parse()?;
+    let user_id = message.update.from.id;
+    match data {
+        CallbackData::SubscribeToChallenge(challenge_id) => {
             let user_name = message.update.from.first_name.clone();
-            return Ok(Action::SubscribeToChallenge(
+            Ok(Action::SubscribeToChallenge(
                 user_id,
                 challenge_id,
                 user_name,
-            ));
+            ))
         }
-    };
-    unimplemented!()
+        CallbackData::ConfirmUserDeletion(user_id_to_delete) => {
+            if user_id_to_delete == user_id {
+                Ok(Action::DeleteUser(user_id))
+            } else {
+                Ok(Action::Nothing)
+            }
+        }
+        CallbackData::CancelUserDeletion => {
+            Ok(Action::Reply("Okay, nothing was deleted.".to_owned()))
+        }
+    }
 }
 
 fn convert_poll_to_action(message: &UpdateWithCx<PollAnswer>) -> Result<Action> {
@@ -154,24 +326,33 @@ fn convert_poll_to_action(message: &UpdateWithCx<PollAnswer>) -> Result<Action>
 fn convert_message_to_action(message: &UpdateWithCx<Message>, command: Command) -> Result<Action> {
     match command {
         Command::Help => Ok(Action::SendHelp),
-        Command::CreateNewChallenge { name, start, end } => {
-            Ok(Action::CreateNewChallenge(ChallengeData {
-                name,
-                time_frame: TimeFrame::new(start, end),
-            }))
-        }
+        Command::CreateNewChallenge {
+            name,
+            start,
+            end,
+            scoring,
+        } => Ok(Action::CreateNewChallenge(ChallengeData {
+            name,
+            time_frame: TimeFrame::new(start, end),
+            owner_id: message.update.from().map(|user| user.id),
+            scoring: scoring.unwrap_or_default(),
+        })),
         Command::AddTask {
             challenge_name,
             task_name,
             count,
             period,
+            weight,
         } => Ok(Action::AddTask(
             message.update.from().unwrap().id,
             challenge_name,
             TaskData {
                 name: task_name,
-                count,
+                count: count.count,
                 period,
+                unit: count.unit,
+                kind: count.kind,
+                weight: weight.unwrap_or(1.0),
             },
         )),
         Command::Signup => {
@@ -189,6 +370,95 @@ fn convert_message_to_action(message: &UpdateWithCx<Message>, command: Command)
                 ))
             }
         }
+        Command::Export => Ok(Action::ExportUserData(message.update.from().unwrap().id)),
+        Command::Amount { amount } => Ok(Action::RecordAmount(
+            message.update.from().unwrap().id,
+            amount,
+        )),
+        Command::Weight {
+            challenge_name,
+            task_name,
+            weight,
+        } => Ok(Action::SetTaskWeight(
+            message.update.from().unwrap().id,
+            challenge_name,
+            task_name,
+            weight,
+        )),
+        Command::AllowWeights {
+            challenge_name,
+            allowed,
+        } => Ok(Action::SetWeightsAllowed(
+            message.update.from().unwrap().id,
+            challenge_name,
+            allowed.0,
+        )),
+        Command::Retire {
+            challenge_name,
+            task_name,
+        } => Ok(Action::RetireTask(
+            message.update.from().unwrap().id,
+            challenge_name,
+            task_name,
+        )),
+        Command::Challenges => Ok(Action::ListChallenges(message.update.from().unwrap().id)),
+        Command::Stats { challenge_name } => Ok(Action::ShowChallengeStats(
+            message.update.from().unwrap().id,
+            message.update.chat.id,
+            challenge_name,
+        )),
+        Command::Streaks { challenge_name } => Ok(Action::ShowStreaks(
+            message.update.from().unwrap().id,
+            message.update.chat.id,
+            challenge_name,
+        )),
+        Command::Report {
+            window,
+            challenge_name,
+        } => Ok(Action::ShowReport(
+            message.update.from().unwrap().id,
+            message.update.chat.id,
+            window,
+            challenge_name,
+        )),
+        Command::Forecast { challenge_name } => Ok(Action::ShowForecast(
+            message.update.from().unwrap().id,
+            message.update.chat.id,
+            challenge_name,
+        )),
+        Command::Archive { challenge_name } => Ok(Action::ArchiveChallenge(
+            message.update.from().unwrap().id,
+            challenge_name,
+        )),
+        Command::ExportCsv { challenge_name } => Ok(Action::ExportChallengeCsv(
+            message.update.from().unwrap().id,
+            message.update.chat.id,
+            challenge_name,
+        )),
+        Command::Pause { from, to } => Ok(Action::PauseUser(
+            message.update.from().unwrap().id,
+            from,
+            to,
+        )),
+        Command::Resume => Ok(Action::ResumeUser(message.update.from().unwrap().id)),
+        Command::Timezone { timezone } => Ok(Action::SetTimezone(
+            message.update.from().unwrap().id,
+            timezone,
+        )),
+        Command::Settings { change } => {
+            let user_id = message.update.from().unwrap().id;
+            Ok(match change {
+                None => Action::ShowSettings(user_id),
+                Some(SettingChange::PollTime(poll_time)) => Action::SetPollTime(user_id, poll_time),
+                Some(SettingChange::UpdateSchedule(challenge_name, schedule)) => {
+                    Action::SetUpdateSchedule(user_id, challenge_name, schedule)
+                }
+            })
+        }
+        Command::DeleteMe => Ok(Action::RequestUserDeletion(
+            message.update.from().unwrap().id,
+        )),
+        Command::Restore { .. } => unreachable!("Restoring is handled in handle_command"),
         Command::SendPoll => Ok(Action::CheckDateMaybeSendPolls),
         Command::SendUpdates => Ok(Action::CheckDateMaybeSendChallengeUpdates),
     }
@@ -1,27 +1,38 @@
 use anyhow::{Context, Result};
 
-use chrono::Local;
+use chrono::NaiveDate;
 use teloxide::types::{
-    InlineKeyboardButton, InlineKeyboardButtonKind, InlineKeyboardMarkup, Message, ReplyMarkup,
+    InlineKeyboardButton, InlineKeyboardButtonKind, InlineKeyboardMarkup, InputFile, Message,
+    ReplyMarkup,
 };
 use teloxide::utils::command::BotCommand;
 use teloxide::{
     prelude::*,
+    requests::RequestWithFile,
     types::{CallbackQuery, MediaKind, MessageKind, PollAnswer},
+    ApiErrorKind, KnownApiErrorKind, RequestError,
 };
 
 use crate::{
-    action::{Action, UserPollDateInfo},
-    database::{challenge::Challenge, challenge_performance_data::ChallengePerformanceData},
-    response::{ChallengeUpdateData, Response},
+    action::{Action, DeliveryOutcome, DeliveryReport},
+    database::{
+        challenge::Challenge, challenge_performance_data::ChallengePerformanceData,
+        delivery::Delivery, pause::Pause, period::Period, task_data::TaskData, task_kind::TaskKind,
+        task_performance_data::TaskPerformanceData,
+    },
+    report_window::ReportWindow,
+    response::{
+        AmountPromptData, ChallengeUpdateData, DocumentData, PollData, ReportData, Response,
+    },
+    task_handling::{Forecast, Streak},
 };
 
-use super::command::Command;
+use super::{callback_data::CallbackData, command::Command};
 
 pub async fn perform_response_to_command(
     response: &Response,
     message: &UpdateWithCx<Message>,
-) -> Result<Option<Action>> {
+) -> Result<Vec<Action>> {
     match response {
         Response::Reply(text) => {
             message.answer(text).send().await?;
@@ -33,84 +44,439 @@ pub async fn perform_response_to_command(
             send_subscription_prompt(challenge, message).await?;
         }
         Response::TaskPolls(task_polls) => {
-            return Ok(Some(send_user_task_polls(&message.bot, task_polls).await?));
+            return Ok(send_user_task_polls(&message.bot, task_polls).await);
         }
         Response::ChallengeUpdates(challenge_updates) => {
-            return Ok(Some(
-                send_challenge_updates(&message.bot, challenge_updates).await?,
-            ));
+            return send_challenge_updates(&message.bot, challenge_updates).await;
+        }
+        Response::ForecastNudges(nudges) => {
+            return Ok(send_forecast_nudges(&message.bot, nudges).await);
+        }
+        Response::Document(document) => {
+            send_document(&message.bot, document).await?;
+            if message.update.chat.id != document.chat_id {
+                message
+                    .answer("I sent you a private message.")
+                    .send()
+                    .await?;
+            }
+        }
+        Response::UserDeletionPrompt(user_id) => {
+            send_user_deletion_prompt(*user_id, message).await?;
+        }
+        Response::AmountPrompt(prompt) => {
+            message
+                .answer(get_amount_prompt_text(prompt))
+                .send()
+                .await?;
+        }
+        Response::Streaks(challenge_performance) => {
+            message
+                .answer(get_streaks_text(challenge_performance))
+                .send()
+                .await?;
+        }
+        Response::Report(report) => {
+            message.answer(get_report_text(report)).send().await?;
+        }
+        Response::Forecast(challenges) => {
+            let texts: Vec<String> = challenges.iter().map(get_forecast_text).collect();
+            message.answer(texts.join("\n\n")).send().await?;
         }
         Response::Nothing => {}
     };
-    Ok(None)
+    Ok(vec![])
 }
 
+/// Sends every poll, even if some of them fail. Polls with too many options
+/// are sent in parts, a poll counts as delivered once all of its parts are.
+/// Parts which went out are left out when the poll is retried, see
+/// [remove_sent_options](crate::database::storage::Storage::remove_sent_options).
+/// Returns the actions which record the polls and how sending them went.
 pub async fn send_user_task_polls(
     bot: &Bot,
     task_polls: &crate::response::UserTaskData,
-) -> Result<Action> {
+) -> Vec<Action> {
     let mut user_poll_date_infos = vec![];
+    let mut reports = vec![];
     for poll_data in task_polls.data.iter() {
-        let send_poll = bot
-            .send_poll(
-                poll_data.chat_id,
-                "Which tasks did you do today?",
-                poll_data.task_names.clone(),
-            )
-            .allows_multiple_answers(true)
-            .is_anonymous(false)
-            .send()
-            .await?;
-        for (i, task) in poll_data.task_names.iter().enumerate() {
-            user_poll_date_infos.push(UserPollDateInfo {
-                user_id: poll_data.user_id,
-                date: Local::today().naive_local(),
-                poll_id: get_poll_id(&send_poll),
-                task_id: task.clone(),
-                task_index: i as i32,
-            });
+        let parts = poll_data.parts();
+        let mut result = Ok(());
+        for (i, part) in parts.iter().enumerate() {
+            let sent = bot
+                .send_poll(
+                    poll_data.chat_id,
+                    get_poll_question(poll_data, i, parts.len()),
+                    part.iter()
+                        .map(|option| option.text.clone())
+                        .collect::<Vec<String>>(),
+                )
+                .allows_multiple_answers(true)
+                .is_anonymous(false)
+                .send()
+                .await
+                .context("While sending poll");
+            match sent {
+                Ok(send_poll) => user_poll_date_infos
+                    .extend(poll_data.get_poll_date_infos(part, &get_poll_id(&send_poll))),
+                Err(err) => {
+                    result = Err(err);
+                    break;
+                }
+            }
         }
+        reports.push(get_delivery_report(poll_data.delivery(), &result));
+    }
+    vec![
+        Action::WritePollInfo(user_poll_date_infos),
+        Action::RecordDeliveries(reports),
+    ]
+}
+
+fn get_poll_question(poll_data: &PollData, part: usize, num_parts: usize) -> String {
+    let question = if poll_data.late {
+        format!(
+            "I missed asking you on {}. Which tasks did you do that day?",
+            poll_data.date.format("%A, %Y-%m-%d")
+        )
+    } else {
+        "Which tasks did you do today?".to_owned()
+    };
+    if num_parts > 1 {
+        format!("{} ({}/{})", question, part + 1, num_parts)
+    } else {
+        question
     }
-    Ok(Action::WritePollInfo(user_poll_date_infos))
 }
 
+/// Scheduled updates are all sent and their deliveries recorded, updates which
+/// were asked for stop at the first error.
 pub async fn send_challenge_updates(
     bot: &Bot,
     update_data: &ChallengeUpdateData,
-) -> Result<Action> {
-    for challenge_performance in update_data.0.iter() {
-        send_text(
-            &bot,
+) -> Result<Vec<Action>> {
+    let mut reports = vec![];
+    for (i, challenge_performance) in update_data.0.iter().enumerate() {
+        let result = send_text(
+            bot,
             &challenge_performance.chat_id,
             &get_challenge_performance_text(challenge_performance),
         )
-        .await?;
+        .await;
+        match update_data.1.get(i) {
+            Some(delivery) => reports.push(get_delivery_report(delivery.clone(), &result)),
+            None => result?,
+        }
+    }
+    if reports.is_empty() {
+        return Ok(vec![]);
+    }
+    Ok(vec![Action::RecordDeliveries(reports)])
+}
+
+/// Whether sending worked. Failures are only logged, so that the other chats still get their message.
+fn get_delivery_report<T>(delivery: Delivery, result: &Result<T>) -> DeliveryReport {
+    let outcome = match result {
+        Ok(_) => DeliveryOutcome::Delivered,
+        Err(err) => {
+            log::warn!("Could not deliver {:?}: {:?}", delivery, err);
+            get_failure_outcome(err)
+        }
+    };
+    DeliveryReport { delivery, outcome }
+}
+
+/// Only network errors and errors on the side of telegram are worth retrying.
+/// After a refusal, telegram will not deliver anything to the chat until the user does something.
+fn get_failure_outcome(err: &anyhow::Error) -> DeliveryOutcome {
+    match err.downcast_ref::<RequestError>() {
+        Some(RequestError::ApiError {
+            kind:
+                ApiErrorKind::Known(
+                    KnownApiErrorKind::BotBlocked
+                    | KnownApiErrorKind::BotKicked
+                    | KnownApiErrorKind::UserDeactivated
+                    | KnownApiErrorKind::ChatNotFound
+                    | KnownApiErrorKind::CantInitiateConversation,
+                ),
+            ..
+        }) => DeliveryOutcome::Refused,
+        Some(RequestError::NetworkError(_)) | Some(RequestError::RetryAfter(_)) => {
+            DeliveryOutcome::Failed
+        }
+        Some(RequestError::ApiError { status_code, .. })
+            if status_code.is_server_error() || status_code.as_u16() == 429 =>
+        {
+            DeliveryOutcome::Failed
+        }
+        _ => DeliveryOutcome::Rejected,
+    }
+}
+
+/// Sends everyone in the challenges what is left to do this week.
+/// Returns the action which records how sending the nudges went.
+pub async fn send_forecast_nudges(bot: &Bot, nudge_data: &ChallengeUpdateData) -> Vec<Action> {
+    let mut reports = vec![];
+    for (challenge_performance, delivery) in nudge_data.0.iter().zip(nudge_data.1.iter()) {
+        let result = send_text(
+            bot,
+            &challenge_performance.chat_id,
+            &get_forecast_text(challenge_performance),
+        )
+        .await;
+        reports.push(get_delivery_report(delivery.clone(), &result));
     }
-    Ok(Action::Nothing)
+    vec![Action::RecordDeliveries(reports)]
+}
+
+fn get_amount_prompt_text(prompt: &AmountPromptData) -> String {
+    format!(
+        "How many {} of {} did you do on {}? Answer with /amount <number>",
+        prompt.unit, prompt.task_name, prompt.date
+    )
 }
 
 fn get_percent(fraction: f64) -> i64 {
     (fraction * 100.0).round() as i64
 }
 
+/// A percentage together with the change since the previous window, e.g. "80% (+5%)".
+fn get_percent_with_delta(fraction: Option<f64>, previous_fraction: Option<f64>) -> String {
+    match (fraction, previous_fraction) {
+        (Some(fraction), Some(previous_fraction)) => format!(
+            "{}% ({:+}%)",
+            get_percent(fraction),
+            get_percent(fraction) - get_percent(previous_fraction)
+        ),
+        (Some(fraction), None) => format!("{}%", get_percent(fraction)),
+        (None, _) => "–".to_owned(),
+    }
+}
+
+fn get_report_text(report: &ReportData) -> String {
+    let mut lines = vec![format!(
+        "Report for {} ({} to {}), compared to {}",
+        report.window.name(),
+        report.time_frame.start,
+        report.time_frame.end,
+        report.window.previous_name()
+    )];
+    for challenge_performance in report.challenges.iter() {
+        lines.push(format!("\n{}", challenge_performance.challenge.data.name));
+        for user_performance in challenge_performance.user_performance.iter() {
+            lines.push(format!(
+                "{}: {}",
+                user_performance.user.name,
+                get_percent_with_delta(
+                    user_performance.get_average_for_time_frame(&report.time_frame),
+                    user_performance.get_average_for_time_frame(&report.previous_time_frame)
+                )
+            ));
+            for task_performance in user_performance.task_performance.iter() {
+                let get_fraction = |time_frame| {
+                    task_performance.get_done_fraction(time_frame, &user_performance.pauses)
+                };
+                lines.push(format!(
+                    "  {}: {}",
+                    task_performance.task.name,
+                    get_percent_with_delta(
+                        get_fraction(&report.time_frame),
+                        get_fraction(&report.previous_time_frame)
+                    )
+                ));
+            }
+        }
+    }
+    lines.join("\n")
+}
+
+fn get_streak_text(streak: &Streak, period: &Period) -> String {
+    format!("{} {}", streak.current, period.unit_name(streak.current))
+}
+
+/// Lists the current and longest streaks of everyone in the challenge and of each of their tasks.
+fn get_streaks_text(challenge_performance: &ChallengePerformanceData) -> String {
+    let today = challenge_performance.today;
+    let challenge = &challenge_performance.challenge.data;
+    let mut lines = vec![format!("Streaks in {}", challenge.name)];
+    for user_performance in challenge_performance.user_performance.iter() {
+        let streak = user_performance.get_streak(challenge, today);
+        lines.push(format!(
+            "{}: {} in a row (longest: {})",
+            user_performance.user.name,
+            get_streak_text(&streak, &Period::Day),
+            streak.longest
+        ));
+        for task_performance in user_performance.task_performance.iter() {
+            let streak =
+                task_performance.get_streak(&challenge.time_frame, &user_performance.pauses, today);
+            lines.push(format!(
+                "  {}: {} in a row (longest: {})",
+                task_performance.task.name,
+                get_streak_text(&streak, &task_performance.task.period),
+                streak.longest
+            ));
+        }
+    }
+    lines.join("\n")
+}
+
+fn get_forecast_text(challenge_performance: &ChallengePerformanceData) -> String {
+    let today = challenge_performance.today;
+    let challenge = &challenge_performance.challenge.data;
+    let mut lines = vec![format!("Still to do in {}", challenge.name)];
+    for user_performance in challenge_performance.user_performance.iter() {
+        if user_performance.get_pause(today).is_some() {
+            lines.push(format!("{}: paused", user_performance.user.name));
+            continue;
+        }
+        lines.push(format!("{}:", user_performance.user.name));
+        for task_performance in user_performance.task_performance.iter() {
+            if let Some(forecast) = task_performance.get_forecast(&challenge.time_frame, today) {
+                lines.push(format!(
+                    "  {}",
+                    get_task_forecast_text(&task_performance.task, &forecast)
+                ));
+            }
+        }
+    }
+    lines.join("\n")
+}
+
+/// E.g. "gym: 1 of 3 done, 2 more in the 4 days until 2021-01-10".
+fn get_task_forecast_text(task: &TaskData, forecast: &Forecast) -> String {
+    let amount = |amount: f64| format_forecast_amount(amount, task);
+    let until = match forecast.remaining_days {
+        1 => "today".to_owned(),
+        days => format!("in the {} days until {}", days, forecast.period_end),
+    };
+    match task.kind {
+        TaskKind::Positive if forecast.remaining() == 0.0 => format!(
+            "{}: {} of {} done, target reached",
+            task.name,
+            amount(forecast.done),
+            amount(forecast.required)
+        ),
+        TaskKind::Positive => format!(
+            "{}: {} of {} done, {} more {}",
+            task.name,
+            amount(forecast.done),
+            amount(forecast.required),
+            amount(forecast.remaining()),
+            until
+        ),
+        TaskKind::Negative => format!(
+            "{}: {} of {} allowed used, {} left {}",
+            task.name,
+            amount(forecast.done),
+            amount(forecast.required),
+            amount(forecast.remaining()),
+            until
+        ),
+    }
+}
+
+/// Check-ins are whole numbers, so a partial target is rounded up for things to do
+/// and rounded down for the budget of things to avoid. Amounts keep one decimal.
+fn format_forecast_amount(amount: f64, task: &TaskData) -> String {
+    match (&task.unit, task.kind) {
+        (Some(unit), _) => format!("{}{}", (amount * 10.0).round() / 10.0, unit),
+        (None, TaskKind::Positive) => amount.ceil().to_string(),
+        (None, TaskKind::Negative) => amount.floor().to_string(),
+    }
+}
+
+/// Describes how much of the budget of something to avoid has been used up in the current period.
+/// The budget of a period which is only partly inside the challenge or the active days of the task
+/// is scaled down, just like when it is scored.
+/// None if the task is not active or not due today.
+fn get_budget_text(
+    task_performance: &TaskPerformanceData,
+    challenge: &Challenge,
+    today: NaiveDate,
+) -> Option<String> {
+    let task = &task_performance.task;
+    let forecast = task_performance.get_forecast(&challenge.data.time_frame, today)?;
+    let amount = |amount: f64| format_forecast_amount(amount, task);
+    let unit = if task.unit.is_some() { "" } else { " slips" };
+    let verdict = if forecast.done > forecast.required {
+        "over the limit"
+    } else {
+        "fine"
+    };
+    Some(format!(
+        "{}: {} of {}{} allowed {} ({})",
+        task.name,
+        amount(forecast.done),
+        amount(forecast.required),
+        unit,
+        task.period.current_period_name(),
+        verdict
+    ))
+}
+
 fn get_challenge_performance_text(challenge_performance: &ChallengePerformanceData) -> String {
+    let today = challenge_performance.today;
+    let time_frame = &challenge_performance.challenge.data.time_frame;
     let lines: Vec<String> = challenge_performance
         .user_performance
         .iter()
         .map(|user_performance| {
-            let alltime_percent = get_percent(
+            let alltime_percent = get_percent_with_delta(
                 user_performance.get_all_time_average(&challenge_performance.challenge.data),
+                None,
             );
-            let weekly_percent = get_percent(user_performance.get_weekly_average());
-            format!(
-                "{}:\t{}%\t(Last 7 days: {}%)",
-                user_performance.user.name, alltime_percent, weekly_percent
-            )
+            // Paused users would always have a perfect week, so tell the others why instead.
+            let recent = match user_performance.get_pause(today) {
+                Some(Pause { end: Some(end), .. }) => format!("(Paused until {})", end),
+                Some(Pause { end: None, .. }) => "(Paused until further notice)".to_owned(),
+                None => {
+                    let last_week = ReportWindow::LastDays(7);
+                    format!(
+                        "(Last 7 days: {})",
+                        get_percent_with_delta(
+                            user_performance.get_weekly_average(today),
+                            user_performance.get_average_for_time_frame(
+                                &last_week.get_previous_time_frame(today)
+                            )
+                        )
+                    )
+                }
+            };
+            let streak = user_performance.get_streak(&challenge_performance.challenge.data, today);
+            let mut line = format!(
+                "{}:\t{}\t{}\tStreak: {}",
+                user_performance.user.name,
+                alltime_percent,
+                recent,
+                get_streak_text(&streak, &Period::Day)
+            );
+            for task_performance in user_performance.task_performance.iter() {
+                if task_performance.task.kind == TaskKind::Negative {
+                    if let Some(budget) =
+                        get_budget_text(task_performance, &challenge_performance.challenge, today)
+                    {
+                        line.push_str(&format!("\n  {}", budget));
+                    }
+                }
+                let streak =
+                    task_performance.get_streak(time_frame, &user_performance.pauses, today);
+                if streak.current > 1 {
+                    line.push_str(&format!(
+                        "\n  {}: {} in a row",
+                        task_performance.task.name,
+                        get_streak_text(&streak, &task_performance.task.period)
+                    ));
+                }
+            }
+            line
         })
         .collect();
+    let challenge = &challenge_performance.challenge.data;
     format!(
-        "Update on challenge: {}\n{}",
-        challenge_performance.challenge.data.name,
+        "Update on challenge: {} (scoring: {})\n{}",
+        challenge.name,
+        challenge.scoring.strategy().describe(),
         lines.join("\n")
     )
 }
@@ -124,6 +490,44 @@ pub fn get_poll_id(send_poll: &Message) -> String {
     unimplemented!()
 }
 
+async fn send_document(bot: &Bot, document: &DocumentData) -> Result<()> {
+    bot.send_document(
+        document.chat_id,
+        InputFile::memory(document.file_name.clone(), document.content.clone()),
+    )
+    .send()
+    .await?
+    .context("While sending document")?;
+    Ok(())
+}
+
+async fn send_user_deletion_prompt(
+    user_id: i32,
+    message: &UpdateWithCx<Message>,
+) -> Result<Message> {
+    message
+        .answer("Do you really want to delete everything the bot stores about you? This includes your subscriptions, tasks and all check-ins and can not be undone.")
+        .reply_markup(ReplyMarkup::InlineKeyboardMarkup(
+            InlineKeyboardMarkup::new(vec![vec![
+                InlineKeyboardButton::new(
+                    "Delete my data",
+                    InlineKeyboardButtonKind::CallbackData(
+                        CallbackData::ConfirmUserDeletion(user_id).to_string(),
+                    ),
+                ),
+                InlineKeyboardButton::new(
+                    "Cancel",
+                    InlineKeyboardButtonKind::CallbackData(
+                        CallbackData::CancelUserDeletion.to_string(),
+                    ),
+                ),
+            ]]),
+        ))
+        .send()
+        .await
+        .context("")
+}
+
 async fn send_subscription_prompt(
     challenge: &Challenge,
     message: &UpdateWithCx<Message>,
@@ -133,7 +537,9 @@ async fn send_subscription_prompt(
         .reply_markup(ReplyMarkup::InlineKeyboardMarkup(
             InlineKeyboardMarkup::new(vec![vec![InlineKeyboardButton::new(
                 "Subscribe",
-                InlineKeyboardButtonKind::CallbackData(format!("{}", challenge.id)),
+                InlineKeyboardButtonKind::CallbackData(
+                    CallbackData::SubscribeToChallenge(challenge.id as i32).to_string(),
+                ),
             )]]),
         ))
         .send()
@@ -166,11 +572,22 @@ async fn send_text(bot: &Bot, chat_id: &i64, text: &str) -> Result<()> {
 
 pub async fn perform_reponse_to_poll_answer(
     response: &crate::response::Response,
-    _message: &UpdateWithCx<PollAnswer>,
+    message: &UpdateWithCx<PollAnswer>,
 ) -> Result<()> {
     match response {
-        Response::Nothing => {}
-        _ => assert!(false),
+        Response::AmountPrompt(prompt) => {
+            send_text(
+                &message.bot,
+                &prompt.chat_id,
+                &get_amount_prompt_text(prompt),
+            )
+            .await?;
+        }
+        // Poll answers come without a chat, so errors go to the private chat with the user.
+        Response::Reply(text) => {
+            send_text(&message.bot, &(message.update.user.id as i64), text).await?;
+        }
+        _ => {}
     }
     Ok(())
 }
@@ -0,0 +1,142 @@
+use chrono::{Duration, NaiveDate};
+use std::{path::Path, sync::Arc};
+
+use crate::{
+    action::{Action, ActionOrigin, DeliveryOutcome, DeliveryReport, UserPollDateInfo},
+    action_handling::perform_action,
+    clock::FakeClock,
+    database::{
+        challenge_data::ChallengeData, memory::MemoryDatabase, period::Period, scoring::Scoring,
+        storage::Storage, task_data::TaskData, task_kind::TaskKind, Database,
+    },
+    response::Response,
+    time_frame::TimeFrame,
+};
+
+/// Runs the test once on a memory and once on an sqlite database. Each of
+/// them gets its own clock, which starts at noon of Monday, 2021-02-01.
+pub fn for_each_backend(test: impl Fn(&dyn Storage, &FakeClock)) {
+    let clock = Arc::new(FakeClock::at_date(NaiveDate::from_ymd(2021, 2, 1)));
+    test(&MemoryDatabase::with_clock(clock.clone()), &clock);
+    let clock = Arc::new(FakeClock::at_date(NaiveDate::from_ymd(2021, 2, 1)));
+    test(
+        &Database::with_clock(Path::new(":memory:"), clock.clone()).unwrap(),
+        &clock,
+    );
+}
+
+pub fn perform(database: &dyn Storage, action: &Action) -> Response {
+    perform_action(database, &ActionOrigin::default(), action)
+}
+
+pub fn reply_text(response: Response) -> String {
+    match response {
+        Response::Reply(text) => text,
+        _ => panic!("Expected a reply, got {:?}", response),
+    }
+}
+
+/// A task to do `count` times per period, without a unit and with the usual weight.
+pub fn task(name: &str, count: i32, period: Period) -> TaskData {
+    TaskData {
+        name: name.into(),
+        count,
+        period,
+        unit: None,
+        kind: TaskKind::Positive,
+        weight: 1.0,
+    }
+}
+
+pub fn gym() -> TaskData {
+    task("gym", 3, Period::Week)
+}
+
+/// Signs up the users, each in a chat with ten times their id.
+pub fn signup(database: &dyn Storage, user_ids: &[i32]) {
+    for user_id in user_ids {
+        database
+            .signup_user(
+                user_id,
+                &(*user_id as i64 * 10),
+                &format!("user{}", user_id),
+            )
+            .unwrap();
+    }
+}
+
+/// Creates a challenge owned by user 1 from `start` to `end` days from today,
+/// subscribes them and gives them the gym task. Returns the id of the challenge.
+pub fn create_challenge(database: &dyn Storage, name: &str, start: i64, end: i64) -> i32 {
+    let today = database.clock().today();
+    let challenge = database
+        .add_challenge(&ChallengeData {
+            name: name.into(),
+            time_frame: TimeFrame::new(today + Duration::days(start), today + Duration::days(end)),
+            owner_id: Some(1),
+            scoring: Scoring::Default,
+        })
+        .unwrap();
+    join_challenge(database, 1, challenge.id as i32);
+    challenge.id as i32
+}
+
+/// Subscribes the user to the challenge and gives them the gym task from its start.
+pub fn join_challenge(database: &dyn Storage, user_id: i32, challenge_id: i32) {
+    database
+        .subscribe_to_challenge(&user_id, &challenge_id)
+        .unwrap();
+    let challenge = database
+        .get_challenges_for_user(&user_id)
+        .unwrap()
+        .into_iter()
+        .find(|challenge| challenge.id == challenge_id as i64)
+        .unwrap();
+    database
+        .insert_task(
+            &user_id,
+            &challenge_id,
+            &gym(),
+            challenge.data.time_frame.start,
+        )
+        .unwrap();
+}
+
+/// Checks the user in on the task on the given day by answering a poll.
+pub fn check_in(database: &dyn Storage, user_id: i32, task_id: i64, date: NaiveDate) {
+    let poll_id = format!("{}-{}-{}", user_id, task_id, date);
+    perform(
+        database,
+        &Action::WritePollInfo(vec![UserPollDateInfo {
+            user_id,
+            date,
+            task_id,
+            poll_id: poll_id.clone(),
+            task_index: 0,
+        }]),
+    );
+    perform(
+        database,
+        &Action::ModifyUserTaskTimestamps(poll_id, vec![0]),
+    );
+}
+
+/// Checks for due polls like the send thread does and reports how sending them went.
+/// Returns the users who got one.
+pub fn send_polls(database: &dyn Storage, outcome: impl Fn(i32) -> DeliveryOutcome) -> Vec<i32> {
+    match perform(database, &Action::CheckDateMaybeSendPolls) {
+        Response::TaskPolls(polls) => {
+            let reports = polls
+                .data
+                .iter()
+                .map(|poll| DeliveryReport {
+                    delivery: poll.delivery(),
+                    outcome: outcome(poll.user_id),
+                })
+                .collect();
+            perform(database, &Action::RecordDeliveries(reports));
+            polls.data.iter().map(|poll| poll.user_id).collect()
+        }
+        response => panic!("Expected polls, got {:?}", response),
+    }
+}
@@ -1,6 +1,7 @@
 use chrono::NaiveDate;
+use serde::{Deserialize, Serialize};
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct TimeFrame {
     pub start: NaiveDate,
     pub end: NaiveDate,
@@ -10,4 +11,15 @@ impl TimeFrame {
     pub fn new(start: NaiveDate, end: NaiveDate) -> TimeFrame {
         TimeFrame { start, end }
     }
+
+    /// The days which are in both time frames, if there are any.
+    pub fn intersect(&self, other: &TimeFrame) -> Option<TimeFrame> {
+        let start = self.start.max(other.start);
+        let end = self.end.min(other.end);
+        if start <= end {
+            Some(TimeFrame::new(start, end))
+        } else {
+            None
+        }
+    }
 }